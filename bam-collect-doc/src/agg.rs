@@ -1,5 +1,6 @@
 /// Code for aggregating BAM records in windows and target regions.
 use std::cmp::min;
+use std::collections::BTreeMap;
 
 use lib_config::DepthOfCoverageConfig;
 
@@ -26,6 +27,15 @@ pub struct AggregationStats {
     pub mean_mapq: f32,
 }
 
+/// Fragment counts for a window behind the end of the aggregated region.
+#[derive(Debug, Default, Clone)]
+pub struct Overflow {
+    /// Number of fragments.
+    pub count: u32,
+    /// Sum of MAPQ values.
+    pub mapq_sum: u64,
+}
+
 /// Struct with common information for aggregator.
 #[derive(Debug)]
 pub struct BaseAggregator {
@@ -41,8 +51,31 @@ pub struct BaseAggregator {
 }
 
 impl BaseAggregator {
-    pub fn contig_length(&self) -> usize {
-        (self.contig.range().end - self.contig.range().start) as usize
+    pub fn contig_start(&self) -> usize {
+        self.contig.range().start as usize
+    }
+
+    pub fn contig_end(&self) -> usize {
+        self.contig.range().end as usize
+    }
+
+    /// Index of the first window on the contig, windows are aligned to multiples of the window
+    /// length on the contig.
+    pub fn first_window(&self) -> usize {
+        self.contig_start() / self.config.window_length
+    }
+
+    /// Number of windows overlapping with the region.
+    pub fn num_windows(&self) -> usize {
+        (self.contig_end() + self.config.window_length - 1) / self.config.window_length
+            - self.first_window()
+    }
+
+    /// Start and end of the window with the given index into the region's windows.
+    pub fn window_range(&self, window_id: usize) -> (usize, usize) {
+        let window_length = self.config.window_length;
+        let start = (self.first_window() + window_id) * window_length;
+        (start, min(self.contig_end(), start + window_length))
     }
 }
 
@@ -66,6 +99,14 @@ pub trait BamRecordAggregator {
 
     /// Number of skipped records.
     fn num_skipped(&self) -> u32;
+
+    /// Take counts for windows behind the end of the region, keyed by window start.
+    fn take_overflow(&mut self) -> BTreeMap<usize, Overflow> {
+        BTreeMap::new()
+    }
+
+    /// Add counts from a previous region for the window starting at `window_start`.
+    fn add_overflow(&mut self, _window_start: usize, _overflow: &Overflow) {}
 }
 
 /// Struct for aggregating fragment counts in a genome-wide fashion.
@@ -78,6 +119,8 @@ pub struct FragmentsAggregator {
     counters: Vec<u32>,
     /// Sum of MAPQ values.
     mapq_sums: Vec<u64>,
+    /// Counts for windows behind the end of the region.
+    overflow: BTreeMap<usize, Overflow>,
 }
 
 impl FragmentsAggregator {
@@ -90,17 +133,22 @@ impl FragmentsAggregator {
             num_processed: 0,
             num_skipped: 0,
         };
-        let contig_length = base.contig_length();
-        let num_bins = (contig_length + base.config.window_length - 1) / base.config.window_length;
+        let num_bins = base.num_windows();
 
         FragmentsAggregator {
             base,
             counters: vec![0; num_bins],
             mapq_sums: vec![0; num_bins],
+            overflow: BTreeMap::new(),
         }
     }
 
     fn put_bam_record(&mut self, record: &bam::Record) {
+        // Records starting left of the region are counted by the previous region.
+        if (record.pos() as usize) < self.base.contig_start() {
+            return;
+        }
+
         if !self.skip_mapq(record)
             && !self.skip_flags(record)
             && !self.skip_discordant(record)
@@ -116,9 +164,16 @@ impl FragmentsAggregator {
             } as u32;
 
             let window_length = self.base.config.window_length;
-            let bin = fragment_center as usize / window_length;
-            self.counters[bin] += 1;
-            self.mapq_sums[bin] += record.mapq() as u64;
+            let bin = fragment_center as usize / window_length - self.base.first_window();
+            if bin < self.counters.len() {
+                self.counters[bin] += 1;
+                self.mapq_sums[bin] += record.mapq() as u64;
+            } else {
+                let window_start = (self.base.first_window() + bin) * window_length;
+                let overflow = self.overflow.entry(window_start).or_default();
+                overflow.count += 1;
+                overflow.mapq_sum += record.mapq() as u64;
+            }
         }
     }
 
@@ -195,14 +250,14 @@ impl BamRecordAggregator for FragmentsAggregator {
     }
 
     fn get_stats(&self, window_id: usize) -> AggregationStats {
-        let window_length = self.base.config.window_length;
+        let (start, end) = self.base.window_range(window_id);
 
         AggregationStats {
             cov: self.counters[window_id] as f32,
             cov_sd: None,
 
-            start: window_id * window_length,
-            end: min(self.base.contig_length(), (window_id + 1) * window_length),
+            start,
+            end,
             mean_mapq: if self.counters[window_id] == 0 {
                 0.0
             } else {
@@ -212,8 +267,7 @@ impl BamRecordAggregator for FragmentsAggregator {
     }
 
     fn num_regions(&self) -> usize {
-        (self.base.contig_length() + self.base.config.window_length - 1)
-            / self.base.config.window_length
+        self.base.num_windows()
     }
 
     fn num_processed(&self) -> u32 {
@@ -223,6 +277,16 @@ impl BamRecordAggregator for FragmentsAggregator {
     fn num_skipped(&self) -> u32 {
         self.base.num_skipped
     }
+
+    fn take_overflow(&mut self) -> BTreeMap<usize, Overflow> {
+        std::mem::take(&mut self.overflow)
+    }
+
+    fn add_overflow(&mut self, window_start: usize, overflow: &Overflow) {
+        let bin = window_start / self.base.config.window_length - self.base.first_window();
+        self.counters[bin] += overflow.count;
+        self.mapq_sums[bin] += overflow.mapq_sum;
+    }
 }
 
 // Bin for coverage aggregation.
@@ -268,9 +332,8 @@ impl CoverageAggregator {
             num_processed: 0,
             num_skipped: 0,
         };
-        let contig_length = base.contig_length();
         let window_length = base.config.window_length;
-        let num_bins = (contig_length + window_length - 1) / window_length;
+        let num_bins = base.num_windows();
 
         CoverageAggregator {
             base,
@@ -307,15 +370,21 @@ impl BamRecordAggregator for CoverageAggregator {
         prog: &dyn std::ops::Fn(i64) -> (),
     ) -> Result<(), Error> {
         let window_length = self.base.config.window_length as usize;
+        let start = self.base.contig_start();
+        let end = self.base.contig_end();
 
         // Iterate over all pileups
         let mut window_id = None;
         for (counter, pileup) in reader.pileup().enumerate() {
             let pileup = pileup.unwrap();
             let pos = pileup.pos() as usize;
+            // Pileups outside of the region are handled by the neighbouring regions.
+            if pos < start || pos >= end {
+                continue;
+            }
 
             // On window change, push window to result.
-            let next_window_id = pos / window_length;
+            let next_window_id = pos / window_length - self.base.first_window();
             if let Some(window_id) = window_id {
                 if window_id != next_window_id {
                     self.push_window(window_id);
@@ -360,20 +429,19 @@ impl BamRecordAggregator for CoverageAggregator {
     }
 
     fn get_stats(&self, window_id: usize) -> AggregationStats {
-        let window_length = self.base.config.window_length;
+        let (start, end) = self.base.window_range(window_id);
         AggregationStats {
             cov: self.coverage[window_id as usize].cov_mean,
             cov_sd: Some(self.coverage[window_id as usize].cov_stddev),
             mean_mapq: self.coverage[window_id as usize].mapq_mean,
 
-            start: window_id * window_length,
-            end: min(self.base.contig_length(), (window_id + 1) * window_length),
+            start,
+            end,
         }
     }
 
     fn num_regions(&self) -> usize {
-        (self.base.contig_length() + self.base.config.window_length - 1)
-            / self.base.config.window_length
+        self.base.num_windows()
     }
 
     fn num_processed(&self) -> u32 {
@@ -1,26 +1,26 @@
 /// bam-collect-doc -- Collect depth of coverage evidence from BAM.
-use std::fs;
-use std::path::Path;
+use std::collections::BTreeMap;
 
 use bio_types::genome::{AbstractInterval, Interval};
-use clap::{App, Arg, ArgMatches};
-use git_version::git_version;
+use clap::{App, Arg, ArgMatches, SubCommand};
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::sorted;
-use log::{debug, info, LevelFilter};
+use log::{debug, info};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 use rust_htslib::{bam, bam::Read as BamRead, bcf, bcf::Read as BcfRead};
 use separator::Separatable;
 use tempfile::tempdir;
 
-use lib_common::bam::{build_chroms_bam, samples_from_file};
+use lib_common::bam::{build_chroms_bam, open_indexed_reader, samples_from_file};
 use lib_common::bcf::guess_bcf_format;
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
 use lib_common::doc::{load_doc_median, MedianReadDepthInfo};
 use lib_common::error::Error;
-use lib_common::parse_region;
 use lib_config::Config;
 
 mod agg;
-use agg::{BamRecordAggregator, CoverageAggregator, FragmentsAggregator};
+use agg::{BamRecordAggregator, CoverageAggregator, FragmentsAggregator, Overflow};
 mod reference;
 use reference::ReferenceStats;
 
@@ -45,23 +45,10 @@ impl Options {
     pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
         Ok(Options {
             verbosity: matches.occurrences_of("v"),
-            regions: matches
-                .value_of("regions")
-                .map(|s| {
-                    let x: Result<Vec<Interval>, Error> =
-                        s.split(',').map(|t| parse_region(&t)).collect();
-                    x
-                })
-                .transpose()?,
+            regions: parse_regions(matches)?,
             path_config: matches.value_of("config").map(|s| s.to_string()),
-            path_input: match matches.value_of("input") {
-                Some(x) => String::from(x),
-                None => return Err(Error::OptionMissing()),
-            },
-            path_output: match matches.value_of("output") {
-                Some(x) => String::from(x),
-                None => return Err(Error::OptionMissing()),
-            },
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
             overwrite: matches.occurrences_of("overwrite") > 0,
         })
     }
@@ -141,75 +128,74 @@ fn build_bcf_writer(
     )?)
 }
 
-/// Process one region.
-fn process_region(
+/// Split `regions` into chunks of at most `chunk_size` bases.
+///
+/// The chunk size is rounded up to a multiple of `window_length` and the chunks are aligned to
+/// multiples of the chunk size on the contig, such that the windows of the chunks are the same as
+/// the windows of the whole contig.  Returns pairs of region index and chunk.
+fn split_regions(
+    regions: &[Interval],
+    chunk_size: usize,
+    window_length: usize,
+) -> Vec<(usize, Interval)> {
+    let chunk_size = std::cmp::max(
+        window_length,
+        (chunk_size + window_length - 1) / window_length * window_length,
+    ) as u64;
+
+    let mut result = Vec::new();
+    for (region_no, region) in regions.iter().enumerate() {
+        let mut start = region.range().start;
+        while start < region.range().end {
+            let end = std::cmp::min((start / chunk_size + 1) * chunk_size, region.range().end);
+            result.push((
+                region_no,
+                Interval::new(region.contig().to_string(), start..end),
+            ));
+            start = end;
+        }
+    }
+
+    result
+}
+
+/// Process one chunk, return the aggregator with the chunk's counts.
+fn process_chunk(
     options: &Options,
     config: &Config,
-    contig: &Interval,
-    bcf_writer: &mut bcf::Writer,
-) -> Result<(), Error> {
-    info!(
-        "Processing contig {}:{}-{}",
-        contig.contig(),
-        (contig.range().start + 1).separated_string(),
-        contig.range().end.separated_string(),
+    chunk: &Interval,
+    progress_bar: &Option<ProgressBar>,
+) -> Result<Box<dyn BamRecordAggregator + Send>, Error> {
+    debug!(
+        "Processing chunk {}:{}-{}",
+        chunk.contig(),
+        (chunk.range().start + 1).separated_string(),
+        chunk.range().end.separated_string(),
     );
 
-    let window_length = config.collect_doc_config.window_length;
-    let ref_stats = config
-        .path_reference_fasta
-        .as_ref()
-        .map(|path| ReferenceStats::from_path(path, contig.contig(), window_length))
-        .transpose()?;
-
-    let mut aggregator: Box<dyn BamRecordAggregator> =
+    let mut aggregator: Box<dyn BamRecordAggregator + Send> =
         match config.collect_doc_config.count_kind.as_str() {
             "fragments" => Box::new(FragmentsAggregator::new(
                 config.collect_doc_config.clone(),
-                contig.clone(),
+                chunk.clone(),
             )),
             "coverage" => Box::new(CoverageAggregator::new(
                 config.collect_doc_config.clone(),
-                contig.clone(),
+                chunk.clone(),
             )),
             _ => panic!("Invalid combination of coverage/on-target regions"),
         };
 
-    // TODO: 2 blocks -> function
-    // Jump to region with BAM reader.
-    let mut bam_reader = bam::IndexedReader::from_path(&options.path_input)?;
+    // Jump to region with BAM reader, each chunk uses its own reader.
+    let mut bam_reader = open_indexed_reader(&options.path_input, config)?;
     if config.htslib_io_threads > 0 {
         bam_reader.set_threads(config.htslib_io_threads)?;
     }
-    let tid: u32 = bam_reader.header().tid(contig.contig().as_bytes()).unwrap();
-    bam_reader.fetch(tid, contig.range().start, contig.range().end)?;
+    let tid: u32 = bam_reader.header().tid(chunk.contig().as_bytes()).unwrap();
+    bam_reader.fetch(tid, chunk.range().start, chunk.range().end)?;
 
-    let progress_bar = if options.verbosity == 0 {
-        let prog_bar =
-            ProgressBar::new(((contig.range().end - contig.range().start) / 1_000) as u64);
-        prog_bar.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "scanning {msg:.green.bold} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
-            {pos:>7}/{len:7} Kbp {elapsed}/{eta}",
-                )
-                .progress_chars("=>-"),
-        );
-        prog_bar.set_message(&contig.contig());
-        Some(prog_bar)
-    } else {
-        None
-    };
-
-    // Main loop for region: pass all BAM records in region through aggregator.
-    info!("Computing coverage...");
-    aggregator.put_fetched_records(&mut bam_reader, &|pos| {
-        if let Some(prog_bar) = &progress_bar {
-            if pos >= 0 {
-                prog_bar.set_position((pos / 1_000) as u64);
-            }
-        }
-    })?;
+    // Main loop for chunk: pass all BAM records in chunk through aggregator.
+    aggregator.put_fetched_records(&mut bam_reader, &|_pos| ())?;
     debug!(
         "Processed {}, skipped {} records ({:.2}% were processed)",
         aggregator.num_processed().separated_string(),
@@ -218,13 +204,27 @@ fn process_region(
             / aggregator.num_processed() as f64,
     );
 
-    // TODO: full block -> function
-    // Create the BCF records for this region.
-    info!("Writing BCF with coverage information...");
+    if let Some(prog_bar) = progress_bar {
+        prog_bar.inc(chunk.range().end - chunk.range().start);
+    }
+
+    Ok(aggregator)
+}
+
+/// Write the BCF records for one processed chunk.
+fn write_chunk(
+    config: &Config,
+    chunk: &Interval,
+    aggregator: &dyn BamRecordAggregator,
+    ref_stats: &Option<ReferenceStats>,
+    bcf_writer: &mut bcf::Writer,
+) -> Result<(), Error> {
+    let window_length = config.collect_doc_config.window_length;
+    let rid = bcf_writer.header().name2rid(chunk.contig().as_bytes())?;
+
     for region_id in 0..aggregator.num_regions() {
         let stats = aggregator.get_stats(region_id);
         let mut record = bcf_writer.empty_record();
-        let rid = bcf_writer.header().name2rid(contig.contig().as_bytes())?;
 
         // Columns: CHROM, POS, ID, REF, ALT, (FILTER)
         let pos = stats.start;
@@ -237,7 +237,7 @@ fn process_region(
 
         record.set_rid(Some(rid));
         record.set_pos(pos as i64);
-        record.set_id(format!("{}:{}-{}", &contig.contig(), pos + 1, window_end).as_bytes())?;
+        record.set_id(format!("{}:{}-{}", &chunk.contig(), pos + 1, window_end).as_bytes())?;
         record.set_alleles(&alleles)?;
 
         // Columns: INFO
@@ -271,6 +271,104 @@ fn process_region(
     Ok(())
 }
 
+/// Process all regions in chunks with a pool of `config.worker_threads` workers.
+///
+/// The chunks are processed in batches and written out in order, such that the output does not
+/// depend on the number of workers.
+fn process_regions(
+    options: &Options,
+    config: &Config,
+    regions: &[Interval],
+    bcf_writer: &mut bcf::Writer,
+) -> Result<(), Error> {
+    let window_length = config.collect_doc_config.window_length;
+    let chunks = split_regions(regions, config.collect_doc_config.chunk_size, window_length);
+    let worker_threads = std::cmp::max(1, config.worker_threads);
+    info!(
+        "Processing {} regions in {} chunks with {} worker(s)",
+        regions.len(),
+        chunks.len(),
+        worker_threads
+    );
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(worker_threads)
+        .build()?;
+
+    let progress_bar = if options.verbosity == 0 {
+        let total: u64 = regions
+            .iter()
+            .map(|r| r.range().end - r.range().start)
+            .sum();
+        let prog_bar = ProgressBar::new(total);
+        prog_bar.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "scanning {msg:.green.bold} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
+            {pos:>10}/{len:10} bp {elapsed}/{eta}",
+                )
+                .progress_chars("=>-"),
+        );
+        prog_bar.set_message(&options.path_input);
+        Some(prog_bar)
+    } else {
+        None
+    };
+
+    // Fragment counts spilling over into the following chunks of the current region, and the
+    // reference statistics for the current contig.
+    let mut overflow: BTreeMap<usize, Overflow> = BTreeMap::new();
+    let mut current_region_no = None;
+    let mut ref_stats: Option<ReferenceStats> = None;
+
+    for batch in chunks.chunks(2 * worker_threads) {
+        let aggregators = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|(_, chunk)| process_chunk(options, config, chunk, &progress_bar))
+                .collect::<Result<Vec<_>, Error>>()
+        })?;
+
+        for ((region_no, chunk), mut aggregator) in batch.iter().zip(aggregators.into_iter()) {
+            if current_region_no != Some(*region_no) {
+                info!(
+                    "Writing BCF with coverage information for {}:{}-{}",
+                    chunk.contig(),
+                    (regions[*region_no].range().start + 1).separated_string(),
+                    regions[*region_no].range().end.separated_string(),
+                );
+                overflow.clear();
+                ref_stats = config
+                    .path_reference_fasta
+                    .as_ref()
+                    .map(|path| ReferenceStats::from_path(path, chunk.contig(), window_length))
+                    .transpose()?;
+                current_region_no = Some(*region_no);
+            }
+
+            // Add counts from previous chunks, then carry over the remaining ones.
+            let chunk_end = chunk.range().end as usize;
+            let remaining = overflow.split_off(&chunk_end);
+            for (window_start, counts) in &overflow {
+                aggregator.add_overflow(*window_start, counts);
+            }
+            overflow = remaining;
+            for (window_start, counts) in aggregator.take_overflow() {
+                let entry = overflow.entry(window_start).or_default();
+                entry.count += counts.count;
+                entry.mapq_sum += counts.mapq_sum;
+            }
+
+            write_chunk(config, chunk, aggregator.as_ref(), &ref_stats, bcf_writer)?;
+        }
+    }
+
+    if let Some(prog_bar) = &progress_bar {
+        prog_bar.finish();
+    }
+
+    Ok(())
+}
+
 fn perform_final_write(
     path_in: &str,
     path_out: &str,
@@ -313,7 +411,7 @@ fn perform_collection(options: &Options, config: &Config) -> Result<(), Error> {
     // Create output file writer and kick off processing.  This is done in its own block such
     // that the file is definitely closed when building the index below.
     let contigs = {
-        let bam_reader = bam::IndexedReader::from_path(&options.path_input)?;
+        let bam_reader = open_indexed_reader(&options.path_input, config)?;
         build_chroms_bam(bam_reader.header(), None)?
     };
 
@@ -332,13 +430,11 @@ fn perform_collection(options: &Options, config: &Config) -> Result<(), Error> {
     let tmp_out = tmp_path.join("tmp.bcf").to_str().unwrap().to_string();
     {
         let mut writer = build_bcf_writer(&tmp_out, &samples, &contigs)?;
-        for region in &regions {
-            process_region(&options, &config, &region, &mut writer)?;
-        }
+        process_regions(&options, &config, &regions, &mut writer)?;
     }
 
     info!("Done scanning BAM. Will now compute per-contig coverage medians.");
-    let doc_median_info = load_doc_median(&tmp_out)?;
+    let doc_median_info = load_doc_median(&tmp_out, 0)?;
 
     info!("Done computing per-contig coverage medians. Building final coverage file.");
     perform_final_write(&tmp_out, &options.path_output, &doc_median_info)?;
@@ -349,62 +445,27 @@ fn perform_collection(options: &Options, config: &Config) -> Result<(), Error> {
     Ok(())
 }
 
-fn main() -> Result<(), Error> {
-    // Setup command line parser and parse options.
-    let matches = App::new("maelstrom-bam-collect-doc")
-        .version(git_version!())
-        .author("Manuel Holtgrewe <manuel.holtgrewe@bihealth.de>")
+/// Build the `bam-collect-doc` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bam-collect-doc")
         .about("Collect depth of coverage evidence from BAM")
         .args(&[
-            Arg::from_usage("-v... 'Increase verbosity'"),
-            Arg::from_usage("--overwrite 'Allow overwriting of output file'"),
-            Arg::from_usage("-c, --config=[FILE] 'Sets a custom config file'"),
-            Arg::from_usage("-r, --regions=[REGIONS] 'comma-separated list of regions'"),
             Arg::from_usage("<input> 'input file to read from'"),
             Arg::from_usage("<output> 'output file to write to'"),
         ])
-        .get_matches();
+}
+
+/// Run the `bam-collect-doc` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
     let options = Options::from_arg_matches(&matches)?;
 
     // Output file must not exist yet.
-    if options.path_output != "-"
-        && options.path_output != "/dev/stdout"
-        && Path::new(&options.path_output).exists()
-        && !options.overwrite
-    {
-        return Err(Error::OutputFileExists());
-    }
-
-    // Setup logging verbosity.
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{} [{}] {}",
-                chrono::Local::now().format("[%Y-%m-%d %H:%M:%S]"),
-                record.level(),
-                message
-            ))
-        })
-        .level(if matches.is_present("v") {
-            LevelFilter::Debug
-        } else {
-            LevelFilter::Info
-        })
-        .chain(std::io::stderr())
-        .apply()
-        .unwrap();
-    info!("Starting maelstrom-bam-collect-doc");
+    check_output_exists(&options.path_output, options.overwrite)?;
+    info!("Starting maelstrom bam-collect-doc");
     info!("options: {:?}", &options);
 
     // Parse further settings from configuration file.
-    let config: Config = match &options.path_config {
-        None => toml::from_str("").unwrap(),
-        Some(path_config) => {
-            debug!("Loading config file: {}", &path_config);
-            let contents = fs::read_to_string(&path_config)?;
-            toml::from_str(&contents).unwrap()
-        }
-    };
+    let config = load_config(&matches)?;
     info!("options: {:?}", &config);
 
     perform_collection(&options, &config)?;
@@ -416,7 +477,7 @@ fn main() -> Result<(), Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::Interval;
+    use super::{BamRecordAggregator, Interval};
     use pretty_assertions::assert_eq;
     use std::fs;
     use tempdir::TempDir;
@@ -428,6 +489,8 @@ mod tests {
         path_expected: &str,
         count_kind: &str,
         regions: &Option<Vec<Interval>>,
+        config_text: &str,
+        doc_config_text: &str,
     ) -> Result<(), super::Error> {
         let path_output = String::from(tmp_dir.path().join("out.vcf").to_str().unwrap());
         let options = super::Options {
@@ -439,9 +502,11 @@ mod tests {
             overwrite: false,
         };
         let config: super::Config = toml::from_str(&format!(
-            "[collect_doc_config]\n\
-            count_kind = \"{}\"",
-            count_kind
+            "{}\n\
+            [collect_doc_config]\n\
+            count_kind = \"{}\"\n\
+            {}",
+            config_text, count_kind, doc_config_text
         ))
         .unwrap();
 
@@ -464,6 +529,8 @@ mod tests {
             "./src/tests/data/ex.expected.fragments.vcf",
             "fragments",
             &None,
+            "",
+            "",
         )?;
         Ok(())
     }
@@ -477,7 +544,83 @@ mod tests {
             "./src/tests/data/ex.expected.coverage.vcf",
             "coverage",
             &None,
+            "",
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_perform_collection_examples_fragments_parallel() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex.sorted.bam",
+            "./src/tests/data/ex.expected.fragments.vcf",
+            "fragments",
+            &None,
+            "worker_threads = 3",
+            "chunk_size = 150",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_perform_collection_examples_coverage_parallel() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex.sorted.bam",
+            "./src/tests/data/ex.expected.coverage.vcf",
+            "coverage",
+            &None,
+            "worker_threads = 3",
+            "chunk_size = 150",
         )?;
         Ok(())
     }
+
+    #[test]
+    fn test_split_regions() {
+        let regions = vec![
+            Interval::new("one".to_string(), 0..1000),
+            Interval::new("two".to_string(), 100..250),
+            Interval::new("three".to_string(), 250..700),
+        ];
+        let chunks = super::split_regions(&regions, 250, 100);
+        assert_eq!(
+            chunks,
+            vec![
+                (0, Interval::new("one".to_string(), 0..300)),
+                (0, Interval::new("one".to_string(), 300..600)),
+                (0, Interval::new("one".to_string(), 600..900)),
+                (0, Interval::new("one".to_string(), 900..1000)),
+                (1, Interval::new("two".to_string(), 100..250)),
+                (2, Interval::new("three".to_string(), 250..300)),
+                (2, Interval::new("three".to_string(), 300..600)),
+                (2, Interval::new("three".to_string(), 600..700)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window_starts_on_contig() {
+        let config: super::Config =
+            toml::from_str("[collect_doc_config]\nwindow_length = 100").unwrap();
+        let aggregator = super::FragmentsAggregator::new(
+            config.collect_doc_config,
+            Interval::new("one".to_string(), 150..420),
+        );
+        assert_eq!(aggregator.num_regions(), 4);
+        let windows: Vec<(usize, usize)> = (0..aggregator.num_regions())
+            .map(|window_id| {
+                let stats = aggregator.get_stats(window_id);
+                (stats.start, stats.end)
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            vec![(100, 200), (200, 300), (300, 400), (400, 420)]
+        );
+    }
 }
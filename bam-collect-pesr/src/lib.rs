@@ -0,0 +1,759 @@
+/// bam-collect-pesr -- Collect paired end and split read evidence from BAM.
+use std::collections::HashMap;
+
+use bio_types::genome::{AbstractInterval, Interval};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{info, warn};
+use rust_htslib::{bam, bam::Read};
+
+use lib_common::bam::library::{
+    estimate_library_insert_size, is_discordant_pair, is_split_read_left, is_split_read_right,
+    reasons_of, ReadGroupProperties, Reason,
+};
+use lib_common::bam::read_id::{sidecar_path, ReadIds};
+use lib_common::bam::realign::{ClipRealigner, ClipSide};
+use lib_common::bam::split::{clipped_side_partner, junction, sa_alignments, SplitAlignment};
+use lib_common::bam::{open_indexed_reader, open_reader};
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
+use lib_common::error::Error;
+use lib_common::read_evidence;
+use lib_config::Config;
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// List of regions to call.
+    regions: Option<Vec<Interval>>,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// Path to input file.
+    path_input: String,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file.
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            regions: parse_regions(matches)?,
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+/// Mapping quality of the mate from the `MQ` tag, if any.
+fn mate_mapq(record: &bam::Record) -> Option<u8> {
+    match record.aux(b"MQ") {
+        Some(bam::record::Aux::Integer(mapq)) => Some(mapq as u8),
+        _ => None,
+    }
+}
+
+/// Return the orientation class of the pair of `record`, `None` if the mate is not placed.
+///
+/// At the same position, the forward alignment is taken as the leftmost one such that both mates
+/// give the same class.
+fn pair_orientation(record: &bam::Record) -> Option<read_evidence::PairOrientation> {
+    if !record.is_paired() || record.mtid() < 0 {
+        return None;
+    }
+    let this = (record.tid(), record.pos(), record.is_reverse());
+    let mate = (record.mtid(), record.mpos(), record.is_mate_reverse());
+    Some(if this <= mate {
+        read_evidence::PairOrientation::from_strands(record.is_reverse(), record.is_mate_reverse())
+    } else {
+        read_evidence::PairOrientation::from_strands(record.is_mate_reverse(), record.is_reverse())
+    })
+}
+
+fn extract_evidence(
+    record: &bam::Record,
+    reader: &bam::IndexedReader,
+    config: &Config,
+    lib_properties: &ReadGroupProperties,
+    realigner: &mut Option<ClipRealigner>,
+    read_ids: &mut ReadIds,
+) -> Result<Vec<read_evidence::Record>, Error> {
+    let cigar = record.cigar();
+    let mut result = Vec::new();
+
+    // Use the reasons tagged by `bam-scan` if present, re-derive otherwise.
+    let (cl, cr, discordant) = if let Some(reasons) = reasons_of(record) {
+        (
+            reasons.contains(&Reason::LeftClip),
+            reasons.contains(&Reason::RightClip),
+            reasons.contains(&Reason::InterChromosomal)
+                || reasons.contains(&Reason::LargeTlen)
+                || reasons.contains(&Reason::WrongOrientation),
+        )
+    } else {
+        (
+            is_split_read_left(record, &cigar, config),
+            is_split_read_right(record, &cigar, config),
+            !record.is_unmapped()
+                && !record.is_mate_unmapped()
+                && is_discordant_pair(record, lib_properties),
+        )
+    };
+
+    if cl || cr {
+        let contig = std::str::from_utf8(reader.header().tid2name(record.tid() as u32))?;
+        // Take the partner from the supplementary alignment at a clipped side if any, else place
+        // the soft clips.
+        let alignments = match sa_alignments(record) {
+            Ok(alignments) => alignments,
+            Err(e) => {
+                warn!(
+                    "Skipping record {}: {}",
+                    String::from_utf8_lossy(record.qname()),
+                    e
+                );
+                return Ok(result);
+            }
+        };
+        let primary = SplitAlignment::from_record(record, contig);
+        let sa_partner = clipped_side_partner(&primary, alignments, cl, cr);
+        let placement = match realigner {
+            Some(realigner) if sa_partner.is_none() => {
+                let contig_len = reader.header().target_len(record.tid() as u32).unwrap();
+                realigner.realign(record, contig, contig_len)?
+            }
+            _ => None,
+        };
+        let strand_of = |is_reverse: bool| {
+            if is_reverse {
+                read_evidence::Strand::Reverse
+            } else {
+                read_evidence::Strand::Forward
+            }
+        };
+        let (breakpoint, clipped_bases, partner) = if let Some(sa_partner) = sa_partner {
+            let junction = junction(&primary, &sa_partner);
+            (
+                Some(junction.breakpoint),
+                Some(junction.clipped_bases),
+                Some(read_evidence::SplitPartner {
+                    contig: sa_partner.contig,
+                    start: sa_partner.start,
+                    end: sa_partner.end,
+                    strand: strand_of(sa_partner.is_reverse),
+                    breakpoint: Some(junction.partner_breakpoint),
+                    clipped_bases: Some(junction.partner_clipped_bases),
+                }),
+            )
+        } else if let Some(placement) = placement {
+            // The clipped sequence continues the read at the breakpoint.
+            let partner_breakpoint = match (placement.side, placement.is_reverse) {
+                (ClipSide::Right, false) | (ClipSide::Left, true) => placement.start,
+                _ => placement.end,
+            };
+            (
+                Some(placement.breakpoint),
+                Some(match placement.side {
+                    ClipSide::Left => cigar.leading_softclips(),
+                    ClipSide::Right => cigar.trailing_softclips(),
+                }),
+                Some(read_evidence::SplitPartner {
+                    contig: contig.to_string(),
+                    start: placement.start,
+                    end: placement.end,
+                    strand: strand_of(record.is_reverse() != placement.is_reverse),
+                    breakpoint: Some(partner_breakpoint),
+                    clipped_bases: None,
+                }),
+            )
+        } else {
+            (None, None, None)
+        };
+        result.push(read_evidence::Record::SplitRead {
+            read_id: read_ids.of_record(record)?,
+            is_first: record.is_first_in_template(),
+            contig: contig.to_string(),
+            start: record.pos(),
+            end: record.cigar().end_pos(),
+            strand: Some(strand_of(record.is_reverse())),
+            clipped_sides: match (cl, cr) {
+                (true, true) => read_evidence::Sides::Both,
+                (true, false) => read_evidence::Sides::Left,
+                (false, true) => read_evidence::Sides::Right,
+                _ => panic!("clipped record not clipped?"),
+            },
+            breakpoint,
+            clipped_bases,
+            partner,
+            mapq: Some(record.mapq()),
+            mate_mapq: mate_mapq(record),
+        })
+    }
+
+    if discordant {
+        result.push(read_evidence::Record::PairedRead {
+            read_id: read_ids.of_record(record)?,
+            is_first1: record.is_first_in_template(),
+            contig1: std::str::from_utf8(reader.header().tid2name(record.tid() as u32))?
+                .to_string(),
+            start1: record.pos(),
+            end1: record.cigar().end_pos(),
+            strand1: if record.is_reverse() {
+                read_evidence::Strand::Reverse
+            } else {
+                read_evidence::Strand::Forward
+            },
+            contig2: if record.is_paired() && record.mtid() >= 0 {
+                Some(
+                    std::str::from_utf8(reader.header().tid2name(record.mtid() as u32))?
+                        .to_string(),
+                )
+            } else {
+                None
+            },
+            start2: if record.is_paired() {
+                Some(record.mpos())
+            } else {
+                None
+            },
+            strand2: if record.is_paired() {
+                Some(if record.is_mate_reverse() {
+                    read_evidence::Strand::Reverse
+                } else {
+                    read_evidence::Strand::Forward
+                })
+            } else {
+                None
+            },
+            tlen: if record.is_paired() && record.insert_size() >= 0 {
+                Some(record.insert_size())
+            } else {
+                None
+            },
+            mapq: Some(record.mapq()),
+            mate_mapq: mate_mapq(record),
+            orientation: pair_orientation(record),
+        })
+    }
+
+    Ok(result)
+}
+
+/// One-end-anchored evidence at the current position, waiting for the unmapped mates.
+///
+/// Unmapped mates are placed at the position of their anchor, so the evidence can be completed
+/// and written once all records at that position have been seen.
+#[derive(Debug, Default)]
+struct AnchoredBuffer {
+    /// Position of the buffered records.
+    pos: i64,
+    /// Evidence of the anchors, with their read names.
+    anchors: Vec<(Vec<u8>, read_evidence::Record)>,
+    /// Sequences of the unmapped mates by read name.
+    mate_sequences: HashMap<Vec<u8>, String>,
+}
+
+impl AnchoredBuffer {
+    /// Buffer the record if it is the anchor or the unmapped mate of a one-end-anchored pair.
+    fn push(
+        &mut self,
+        record: &bam::Record,
+        reader: &bam::IndexedReader,
+        read_ids: &mut ReadIds,
+    ) -> Result<(), Error> {
+        if !record.is_paired()
+            || record.is_secondary()
+            || record.is_supplementary()
+            || record.is_unmapped() == record.is_mate_unmapped()
+        {
+            return Ok(());
+        }
+
+        if record.is_unmapped() {
+            self.mate_sequences.insert(
+                record.qname().to_vec(),
+                String::from_utf8(record.seq().as_bytes())?,
+            );
+        } else {
+            self.anchors.push((
+                record.qname().to_vec(),
+                read_evidence::Record::AnchoredRead {
+                    read_id: read_ids.of_record(record)?,
+                    is_first: record.is_first_in_template(),
+                    contig: std::str::from_utf8(reader.header().tid2name(record.tid() as u32))?
+                        .to_string(),
+                    start: record.pos(),
+                    end: record.cigar().end_pos(),
+                    strand: if record.is_reverse() {
+                        read_evidence::Strand::Reverse
+                    } else {
+                        read_evidence::Strand::Forward
+                    },
+                    mate_sequence: None,
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write out the buffered evidence with the sequences of the unmapped mates.
+    fn flush(&mut self, writer: &mut read_evidence::Writer) -> Result<(), Error> {
+        for (qname, mut evidence) in self.anchors.drain(..) {
+            if let read_evidence::Record::AnchoredRead { mate_sequence, .. } = &mut evidence {
+                *mate_sequence = self.mate_sequences.remove(&qname);
+            }
+            writer.write(&evidence)?;
+        }
+        self.mate_sequences.clear();
+        Ok(())
+    }
+}
+
+/// Perform extraction of paired read/split read signal.
+fn perform_collection(
+    options: &Options,
+    config: &Config,
+    lib_properties: &ReadGroupProperties,
+) -> Result<(), Error> {
+    // We can handle the paired read/split read signal collection with a single scan.
+    info!("Starting to scan BAM file...");
+
+    // We will scan the BAM file contig wise.
+    let mut reader = open_indexed_reader(&options.path_input, config)?;
+    let target_count = reader.header().target_count() as usize;
+    if config.htslib_io_threads > 0 {
+        reader.set_threads(config.htslib_io_threads)?;
+    }
+
+    // Evidence is written to an extended BED3 file.
+    let mut writer = read_evidence::Writer::from_path(&options.path_output)?;
+    // Optionally, soft clips are re-aligned against the reference.
+    let mut realigner = ClipRealigner::from_config(config)?;
+    // Read IDs are taken from the `xI` tag of `bam-unique` or derived from the read names.
+    let mut read_ids = ReadIds::new(config.read_ids_max_in_memory);
+
+    let regions = if let Some(regions) = &options.regions {
+        regions.clone()
+    } else {
+        (0..target_count)
+            .map(|tid| {
+                Interval::new(
+                    std::str::from_utf8(reader.header().target_names()[tid])
+                        .unwrap()
+                        .to_string(),
+                    0..(reader.header().target_len(tid as u32).unwrap() as u64),
+                )
+            })
+            .collect()
+    };
+
+    for region in regions {
+        if options.verbosity > 0 {
+            info!("Starting to scan {:?}", &region);
+        }
+        let target_id = reader
+            .header()
+            .target_names()
+            .iter()
+            .enumerate()
+            .find(|(_, name)| **name == region.contig().as_bytes())
+            .map(|(i, _)| i)
+            .ok_or(Error::InvalidRegion())?;
+        let target_len = (region.range().end - region.range().start) as i64;
+
+        reader.fetch(target_id as u32, region.range().start, region.range().end)?;
+
+        let progress_bar =
+            if options.verbosity == 0 {
+                let prog_bar = ProgressBar::new((target_len / 1_000) as u64);
+                prog_bar.set_style(ProgressStyle::default_bar()
+                .template(
+                    "scanning {msg:.green.bold} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
+                    {pos:>7}/{len:7} Kbp {elapsed}/{eta}")
+                .progress_chars("=>-"));
+                prog_bar.set_message(&format!("{:?}", &region));
+                Some(prog_bar)
+            } else {
+                None
+            };
+
+        let mut buffer = bam::Record::new();
+        let mut anchored = AnchoredBuffer::default();
+        let mut counter: usize = 0;
+        loop {
+            if !reader.read(&mut buffer)? {
+                break;
+            } else {
+                if buffer.pos() != anchored.pos {
+                    anchored.flush(&mut writer)?;
+                    anchored.pos = buffer.pos();
+                }
+                for evidence in extract_evidence(
+                    &buffer,
+                    &reader,
+                    &config,
+                    &lib_properties,
+                    &mut realigner,
+                    &mut read_ids,
+                )? {
+                    writer.write(&evidence)?;
+                }
+                if config.extract_one_end_anchored {
+                    anchored.push(&buffer, &reader, &mut read_ids)?;
+                }
+
+                counter += 1;
+                if counter % 10_000 == 0 {
+                    if let Some(prog_bar) = &progress_bar {
+                        prog_bar.set_position(buffer.pos() as u64 / 10000);
+                    }
+                }
+            }
+        }
+
+        anchored.flush(&mut writer)?;
+
+        if let Some(prog_bar) = &progress_bar {
+            prog_bar.finish();
+        }
+    }
+
+    // Closes the output file and builds the index if it is bgzipped.
+    writer.finish()?;
+
+    // The names of the read IDs allow looking up the supporting reads later on.
+    if options.path_output != "-" {
+        read_ids.write_sidecar(&sidecar_path(&options.path_output), &options.path_input)?;
+    }
+    Ok(())
+}
+
+/// Load library properties of each read group from the header written by `bam-scan`.
+fn load_library_properties(
+    path: &str,
+    config: &Config,
+) -> Result<Option<ReadGroupProperties>, Error> {
+    let reader = open_reader(path, config)?;
+    ReadGroupProperties::from_header(reader.header().as_bytes())
+}
+
+/// Build the `bam-collect-pesr` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bam-collect-pesr")
+        .about("Collect paired end and split read evidence from BAM")
+        .args(&[
+            Arg::from_usage("<input> 'input file to read from'"),
+            Arg::from_usage("<output> 'output file to write to'"),
+        ])
+}
+
+/// Run the `bam-collect-pesr` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output file and its read ID sidecar must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    if options.path_output != "-" {
+        check_output_exists(&sidecar_path(&options.path_output), options.overwrite)?;
+    }
+    info!("Starting maelstrom bam-collect-pesr");
+    info!("options: {:?}", &options);
+
+    // Parse further settings from configuration file.
+    let config = load_config(&matches)?;
+    info!("options: {:?}", &config);
+
+    // Estimate the library insert size.
+    let lib_properties = match load_library_properties(&options.path_input, &config)? {
+        Some(lib_properties) => lib_properties,
+        _ => estimate_library_insert_size(&options.path_input, &config)?,
+    };
+    info!(
+        "library properties (all read groups): {}",
+        lib_properties.fallback.summary()
+    );
+    perform_collection(&options, &config, &lib_properties)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interval;
+    use lib_common::bam::library::{example_read_group_properties, LibraryProperties};
+    use lib_common::read_evidence;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that runs `perform_collection()` and compares the result.
+    fn _perform_collection_and_test(
+        tmp_dir: &TempDir,
+        path_input: &str,
+        path_expected: &str,
+        regions: &Option<Vec<Interval>>,
+        config_text: &str,
+    ) -> Result<(), super::Error> {
+        let path_output = String::from(tmp_dir.path().join("out.tsv").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: regions.clone(),
+            path_config: None,
+            path_input: String::from(path_input),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str(config_text).unwrap();
+        let library_properties = super::ReadGroupProperties::uniform(LibraryProperties {
+            max_rlen: 100,
+            median_isize: 300.0,
+            std_dev_isize: 10.0,
+            max_normal_isize: 330,
+            ..Default::default()
+        });
+
+        super::perform_collection(&options, &config, &library_properties)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(&path_output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_hard_clipping_leading() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-hard-leading.sorted.bam",
+            "./src/tests/data/ex-pe-hard-leading.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_hard_clipping_trailing() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-hard-trailing.sorted.bam",
+            "./src/tests/data/ex-pe-hard-trailing.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_soft_clipping_negative() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-soft-neg.sorted.bam",
+            "./src/tests/data/ex-pe-soft-neg.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+    #[test]
+    fn identify_pairs_by_soft_clipping_positive() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-soft-pos.sorted.bam",
+            "./src/tests/data/ex-pe-soft-pos.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_different_tid() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-tid.sorted.bam",
+            "./src/tests/data/ex-pe-tid.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_large_tlen() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-tlen.sorted.bam",
+            "./src/tests/data/ex-pe-tlen.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_orientation() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-orient.sorted.bam",
+            "./src/tests/data/ex-pe-orient.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn pair_orientation_at_same_position() {
+        // Both mates of a pair at the same position, first forward and second reverse.
+        let mate = |flags: u16| {
+            let mut record = rust_htslib::bam::Record::new();
+            record.set_tid(0);
+            record.set_pos(100);
+            record.set_mtid(0);
+            record.set_mpos(100);
+            record.set_flags(flags);
+            record
+        };
+        let first = mate(0x1 | 0x20 | 0x40);
+        let second = mate(0x1 | 0x10 | 0x80);
+        assert_eq!(
+            super::pair_orientation(&first),
+            Some(read_evidence::PairOrientation::FR)
+        );
+        assert_eq!(
+            super::pair_orientation(&second),
+            Some(read_evidence::PairOrientation::FR)
+        );
+
+        // The mate is further right.
+        let mut first = mate(0x1 | 0x10 | 0x40);
+        first.set_mpos(200);
+        assert_eq!(
+            super::pair_orientation(&first),
+            Some(read_evidence::PairOrientation::RF)
+        );
+    }
+
+    #[test]
+    fn identify_pairs_without_read_id_tags() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-noid.sorted.bam",
+            "./src/tests/data/ex-pe-noid.expected.tsv",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_one_end_anchored_pairs() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-anchored.sorted.bam",
+            "./src/tests/data/ex-pe-anchored.expected.tsv",
+            &None,
+            "extract_one_end_anchored = true",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn place_soft_clips_by_realignment() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-realign.sorted.bam",
+            "./src/tests/data/ex-pe-realign.expected.tsv",
+            &None,
+            "realign_soft_clips = true\n\
+             path_reference_fasta = \"./src/tests/data/ex-pe-realign.fa\"",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn place_soft_clips_by_realignment_disabled() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_collection_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-realign.sorted.bam",
+            "./src/tests/data/ex-pe-realign.expected-disabled.tsv",
+            &None,
+            "path_reference_fasta = \"./src/tests/data/ex-pe-realign.fa\"",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn write_bgzipped_and_indexed() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_output = String::from(tmp_dir.path().join("out.tsv.gz").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: None,
+            path_config: None,
+            path_input: String::from("./src/tests/data/ex-pe-tid.sorted.bam"),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str("").unwrap();
+        let library_properties = super::ReadGroupProperties::uniform(LibraryProperties {
+            max_rlen: 100,
+            median_isize: 300.0,
+            std_dev_isize: 10.0,
+            max_normal_isize: 330,
+            ..Default::default()
+        });
+
+        super::perform_collection(&options, &config, &library_properties)?;
+
+        let expected: Vec<String> = fs::read_to_string("./src/tests/data/ex-pe-tid.expected.tsv")?
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').nth(3).unwrap().to_string())
+            .collect();
+        let mut reader = read_evidence::IndexedReader::from_path(&path_output)?;
+        assert_eq!(reader.contigs(), vec!["one", "two"]);
+        let mut actual = Vec::new();
+        for contig in reader.contigs() {
+            assert!(reader.fetch(&contig, 0, 10_000)?);
+            while let Some(record) = reader.read_record()? {
+                actual.push(serde_json::to_string(&record)?);
+            }
+        }
+        assert_eq!(expected, actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_library_properties_from_header() -> Result<(), super::Error> {
+        let config: super::Config = toml::from_str("").unwrap();
+        let lib_properties =
+            super::load_library_properties("./src/tests/data/ex-pe-libprops.sorted.bam", &config)?;
+
+        // Written by bam-scan for these properties.
+        assert_eq!(lib_properties, Some(example_read_group_properties()));
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+/// bam-extract-support -- Extract the reads supporting SVs into a tagged BAM file for review.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+
+use bio_types::genome::{AbstractInterval, Interval};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use itertools::Itertools;
+use log::{info, warn};
+use rust_htslib::{bam, bam::Read};
+
+use lib_common::bam::read_id::SidecarReader;
+use lib_common::bam::sort::{coordinate_order, ExternalSorter};
+use lib_common::bam::{build_index, guess_bam_format, open_indexed_reader, open_writer};
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
+use lib_common::error::Error;
+use lib_common::read_evidence;
+use lib_config::Config;
+
+/// Tag with the IDs of the SVs supported by the record.
+const SV_TAG: &[u8] = b"xV";
+
+/// Tag with the kinds of evidence (`PE`, `SR`) given by the record.
+const KIND_TAG: &[u8] = b"xK";
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// List of regions to extract the supporting reads from, all contigs if not given.
+    regions: Option<Vec<Interval>>,
+    /// IDs of the SVs to extract the supporting reads for, all if empty.
+    sv_ids: Vec<String>,
+    /// Sample to extract the supporting reads for, required for files with several samples.
+    sample: Option<String>,
+    /// Path to supporting reads file written by `vcf-annotate`.
+    path_supporting_reads: String,
+    /// Path to input PE/SR evidence file.
+    path_pesr_evidence: String,
+    /// Path to read ID sidecar written with the evidence.
+    path_read_ids: String,
+    /// Path to alignments to extract from, the source of the sidecar if not given.
+    path_bam: Option<String>,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file.
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            regions: parse_regions(matches)?,
+            sv_ids: matches
+                .values_of("sv-id")
+                .map(|values| values.map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+            sample: matches.value_of("sample").map(|s| s.to_string()),
+            path_supporting_reads: required_value(matches, "path-supporting-reads")?,
+            path_pesr_evidence: required_value(matches, "path-pesr-evidence")?,
+            path_read_ids: required_value(matches, "path-read-ids")?,
+            path_bam: matches.value_of("path-bam").map(|s| s.to_string()),
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+/// The SVs supported by one read and the kinds of evidence it gives.
+#[derive(Debug, Default)]
+struct Support {
+    /// IDs of the supported SVs.
+    sv_ids: BTreeSet<String>,
+    /// Kinds of evidence, `PE` or `SR`.
+    kinds: BTreeSet<String>,
+}
+
+/// Load the support of each read from the supporting reads file, by read ID.
+///
+/// Only lines for `options.sv_ids` and `options.sample` are considered, if given.  The read IDs
+/// are only meaningful with the sidecar of their sample, so `options.sample` is required if the
+/// file has more than one sample.
+fn load_supporting_reads(options: &Options) -> Result<BTreeMap<i64, Support>, Error> {
+    let mut result: BTreeMap<i64, Support> = BTreeMap::new();
+    let mut samples = BTreeSet::new();
+    let reader = BufReader::new(File::open(&options.path_supporting_reads)?);
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() || line.starts_with("SVID\t") {
+            continue;
+        }
+        let arr: Vec<&str> = line.split('\t').collect();
+        if arr.len() != 4 {
+            return Err(Error::InvalidSupportingReads(line));
+        }
+        let (sv_id, sample, kind) = (arr[0], arr[1], arr[2]);
+        if options.sample.is_none() && samples.insert(sample.to_string()) && samples.len() > 1 {
+            return Err(Error::SampleRequired());
+        }
+        if (!options.sv_ids.is_empty() && !options.sv_ids.iter().any(|s| s == sv_id))
+            || options.sample.as_ref().map_or(false, |s| s != sample)
+        {
+            continue;
+        }
+        let support = result.entry(arr[3].parse()?).or_default();
+        support.sv_ids.insert(sv_id.to_string());
+        support.kinds.insert(kind.to_string());
+    }
+    Ok(result)
+}
+
+/// Load the loci of the evidence records of the given reads in `regions`, merged by contig.
+///
+/// The loci include the mate and split partner positions, such that all records of the reads
+/// can be fetched from the alignments.
+fn load_evidence_loci(
+    path_pesr_evidence: &str,
+    supports: &BTreeMap<i64, Support>,
+    regions: &[Interval],
+) -> Result<BTreeMap<String, Vec<Range<i64>>>, Error> {
+    let mut loci: BTreeMap<String, Vec<Range<i64>>> = BTreeMap::new();
+    let mut push = |contig: &str, range: Range<i64>| {
+        loci.entry(contig.to_string()).or_default().push(range);
+    };
+
+    let mut reader = read_evidence::IndexedReader::from_path(path_pesr_evidence)?;
+    for region in regions {
+        if !reader.fetch(region.contig(), region.range().start, region.range().end)? {
+            continue;
+        }
+        while let Some(record) = reader.read_record()? {
+            if !supports.contains_key(&record.read_id()) {
+                continue;
+            }
+            match &record {
+                read_evidence::Record::PairedRead {
+                    contig1,
+                    start1,
+                    end1,
+                    contig2,
+                    start2,
+                    ..
+                } => {
+                    push(contig1, *start1..*end1);
+                    if let (Some(contig2), Some(start2)) = (contig2, start2) {
+                        push(contig2, *start2..(*start2 + 1));
+                    }
+                }
+                read_evidence::Record::SplitRead {
+                    contig,
+                    start,
+                    end,
+                    partner,
+                    ..
+                } => {
+                    push(contig, *start..*end);
+                    if let Some(partner) = partner {
+                        push(&partner.contig, partner.start..partner.end);
+                    }
+                }
+                read_evidence::Record::AnchoredRead {
+                    contig, start, end, ..
+                } => push(contig, *start..*end),
+            }
+        }
+    }
+
+    for ranges in loci.values_mut() {
+        ranges.sort_by_key(|range| (range.start, range.end));
+        let mut merged: Vec<Range<i64>> = Vec::new();
+        for range in ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => {
+                    last.end = std::cmp::max(last.end, range.end)
+                }
+                _ => merged.push(range),
+            }
+        }
+        *ranges = merged;
+    }
+
+    Ok(loci)
+}
+
+/// Main entry point after parsing command line and loading options.
+fn perform_extraction(options: &Options, config: &Config) -> Result<(), Error> {
+    let supports = load_supporting_reads(options)?;
+    info!("Extracting {} supporting reads...", supports.len());
+
+    // Look up the query names of the supporting reads.
+    let mut sidecar = SidecarReader::from_path(&options.path_read_ids)?;
+    let mut by_name: HashMap<Vec<u8>, &Support> = HashMap::new();
+    for (read_id, support) in &supports {
+        // On read ID collisions, the records of all names with the ID in the evidence loci are
+        // extracted.
+        let qnames = sidecar.lookup(*read_id)?;
+        if qnames.is_empty() {
+            warn!("No query name for read ID {}", read_id);
+        }
+        for qname in qnames {
+            by_name.insert(qname, support);
+        }
+    }
+
+    let path_bam = match &options.path_bam {
+        Some(path_bam) => path_bam.clone(),
+        None => sidecar.path_source().to_string(),
+    };
+    let mut reader = open_indexed_reader(&path_bam, config)?;
+
+    let regions = if let Some(regions) = &options.regions {
+        regions.clone()
+    } else {
+        let header = reader.header();
+        (0..header.target_count())
+            .map(|tid| {
+                Interval::new(
+                    String::from_utf8_lossy(header.tid2name(tid)).to_string(),
+                    0..header.target_len(tid).unwrap(),
+                )
+            })
+            .collect()
+    };
+    let loci = load_evidence_loci(&options.path_pesr_evidence, &supports, &regions)?;
+
+    info!("Fetching supporting reads from {}", &path_bam);
+    // Records overlapping several loci are fetched more than once, so sort and deduplicate them.
+    let header = bam::Header::from_template(reader.header());
+    let mut sorter = ExternalSorter::new(
+        &header,
+        coordinate_order,
+        true,
+        config.scan_sort_max_in_memory,
+    );
+    let mut record = bam::Record::new();
+    for (contig, ranges) in &loci {
+        let tid = match reader.header().tid(contig.as_bytes()) {
+            Some(tid) => tid,
+            None => {
+                warn!("Contig {} not in {}", contig, &path_bam);
+                continue;
+            }
+        };
+        for range in ranges {
+            reader.fetch(tid, range.start as u64, range.end as u64)?;
+            while reader.read(&mut record)? {
+                if let Some(support) = by_name.get(record.qname()) {
+                    let mut record = record.clone();
+                    record.push_aux(
+                        SV_TAG,
+                        &bam::record::Aux::String(support.sv_ids.iter().join(",").as_bytes()),
+                    );
+                    record.push_aux(
+                        KIND_TAG,
+                        &bam::record::Aux::String(support.kinds.iter().join(",").as_bytes()),
+                    );
+                    sorter.push(record)?;
+                }
+            }
+        }
+    }
+
+    let written = {
+        let mut writer = open_writer(&options.path_output, &header, config)?;
+        sorter.finish(&mut writer)?
+    };
+
+    // Build index for sorted BAM/CRAM output.
+    let format = guess_bam_format(&options.path_output);
+    if options.path_output != "-" && !matches!(format, bam::Format::SAM) {
+        build_index(
+            &options.path_output,
+            reader.header(),
+            std::cmp::max(1, config.htslib_io_threads),
+        )?;
+    }
+
+    info!("Wrote {} records", written);
+    Ok(())
+}
+
+/// Build the `bam-extract-support` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bam-extract-support")
+        .about("Extract the reads supporting SVs into a tagged BAM file for review")
+        .args(&[
+            Arg::from_usage("--sv-id=[ID]... 'SV to extract supporting reads for, default: all'"),
+            Arg::from_usage(
+                "--sample=[SAMPLE] 'Sample to extract supporting reads for, required if the \
+                 supporting reads file has several samples'",
+            ),
+            Arg::from_usage(
+                "--path-supporting-reads=<FILE> 'Path to supporting reads file from \
+                 vcf-annotate'",
+            ),
+            Arg::from_usage("--path-pesr-evidence=<FILE> 'Path to PE/SR evidence file'"),
+            Arg::from_usage("--path-read-ids=<FILE> 'Path to read ID sidecar of the evidence'"),
+            Arg::from_usage(
+                "--path-bam=[FILE] 'Path to BAM file to extract from, default: source of the \
+                 read ID sidecar'",
+            ),
+            Arg::from_usage("<output> 'output file to write to'"),
+        ])
+}
+
+/// Run the `bam-extract-support` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output file must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    info!("Starting maelstrom bam-extract-support");
+    info!("options: {:?}", &options);
+
+    // Parse further settings from configuration file.
+    let config = load_config(&matches)?;
+    info!("options: {:?}", &config);
+
+    perform_extraction(&options, &config)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bio_types::genome::Interval;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that runs `perform_extraction()` and compares the result.
+    fn _perform_extraction_and_test(
+        tmp_dir: &TempDir,
+        regions: Option<Vec<Interval>>,
+        sv_ids: &[&str],
+        sample: Option<&str>,
+        path_expected: &str,
+    ) -> Result<(), super::Error> {
+        let path_output = String::from(tmp_dir.path().join("out.sam").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1,
+            path_config: None,
+            regions,
+            sv_ids: sv_ids.iter().map(|s| s.to_string()).collect(),
+            sample: sample.map(|s| s.to_string()),
+            path_supporting_reads: String::from("./src/tests/data/ex-support.reads.tsv"),
+            path_pesr_evidence: String::from("./src/tests/data/ex-support-pesr.tsv.gz"),
+            path_read_ids: String::from("./src/tests/data/ex-support.read_ids.tsv"),
+            path_bam: None,
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str("").unwrap();
+
+        super::perform_extraction(&options, &config)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(&path_output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn extract_support_of_sv() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            None,
+            &["SV1"],
+            Some("sample-1"),
+            "./src/tests/data/ex-support.expected-sv1.sam",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn extract_support_of_sample() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            None,
+            &[],
+            Some("sample-1"),
+            "./src/tests/data/ex-support.expected-sample-1.sam",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn extract_support_requires_sample() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        // The supporting reads file has reads of two samples.
+        assert!(matches!(
+            _perform_extraction_and_test(
+                &tmp_dir,
+                None,
+                &["SV1"],
+                None,
+                "./src/tests/data/ex-support.expected-sv1.sam",
+            ),
+            Err(super::Error::SampleRequired())
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn extract_support_in_regions() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            Some(vec![Interval::new("two".to_string(), 0..1_000)]),
+            &[],
+            Some("sample-1"),
+            "./src/tests/data/ex-support.expected-sample-1-two.sam",
+        )?;
+        Ok(())
+    }
+}
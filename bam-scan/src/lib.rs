@@ -0,0 +1,1337 @@
+/// bam-scan -- Scan BAM file for discordant and clipped reads
+mod mates;
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use bio::data_structures::annot_map::AnnotMap;
+use bio_types::annot::contig::Contig;
+use bio_types::genome::{AbstractInterval, Interval};
+use bio_types::strand::NoStrand;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use rust_htslib::{bam, bam::Read};
+use tempfile::tempdir;
+
+use lib_common::bam::library::{
+    coverage_bases, estimate_library_insert_size, interesting_reasons, reasons_to_tag,
+    write_qc_report, ReadGroupProperties, Reason, LIBRARY_PROPERTIES_COMMENT, REASON_TAG,
+};
+use lib_common::bam::read_id::qname_hash;
+use lib_common::bam::sort::{coordinate_order, merge_sorted, ExternalSorter};
+use lib_common::bam::{build_index, guess_bam_format, open_indexed_reader, open_writer};
+use lib_common::bed_to_annot_map;
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
+use lib_common::error::Error;
+use lib_config::{Config, CoverageSpikeAction};
+use mates::{distribute_pending, for_each_locus, MatePos, MateTracker};
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// List of regions to call.
+    regions: Option<Vec<Interval>>,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// Path to input file.
+    path_input: String,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file.
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            regions: parse_regions(matches)?,
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+/// Window with a depth above the coverage spike limit.
+#[derive(Debug, Clone, PartialEq)]
+struct Spike {
+    /// Reference ID of the window.
+    tid: i32,
+    /// The part of the window not overlapping with the previous one.
+    range: std::ops::Range<i64>,
+    /// Fraction of the records starting in the window to keep, 0 when skipping the window.
+    keep: f64,
+}
+
+/// State of the extraction in one shard.
+struct ExtractionState {
+    /// Sorter for the extracted records.
+    sorter: ExternalSorter,
+    mates: MateTracker,
+    /// Number of written records by reference ID and reason.
+    reason_counts: BTreeMap<i32, BTreeMap<Reason, usize>>,
+    /// Coverage spikes found in the shard.
+    spikes: Vec<Spike>,
+}
+
+impl ExtractionState {
+    /// Compute the depth of the window part `range` from the buffered `records` starting in it
+    /// and record it if it is a coverage spike.
+    ///
+    /// Returns the spike, if any.
+    fn check_depth<'a, I, R>(
+        &mut self,
+        region: &Interval,
+        tid: i32,
+        range: std::ops::Range<i64>,
+        records: I,
+        max_depth: f64,
+        config: &Config,
+    ) -> Option<Spike>
+    where
+        I: IntoIterator<Item = &'a R>,
+        R: Borrow<bam::Record> + 'a,
+    {
+        let bases: i64 = records
+            .into_iter()
+            .map(|record| record.borrow())
+            .filter(|record| range.contains(&record.pos()))
+            .map(coverage_bases)
+            .sum();
+
+        let depth = bases as f64 / (range.end - range.start) as f64;
+        if depth <= max_depth {
+            return None;
+        }
+        let keep = match config.coverage_spike_action {
+            CoverageSpikeAction::Skip => 0.0,
+            CoverageSpikeAction::Downsample => max_depth / depth,
+        };
+        info!(
+            "Coverage spike with depth {:.2} at {}:{}-{}, keeping {:.1}% of records",
+            depth,
+            region.contig(),
+            range.start,
+            range.end,
+            100.0 * keep
+        );
+        let spike = Spike { tid, range, keep };
+        self.spikes.push(spike.clone());
+        Some(spike)
+    }
+}
+
+/// Return whether the record or its mate overlaps with the `blocked` regions.
+fn is_blocked(blocked: &Option<AnnotMap<i32, ()>>, record: &bam::Record) -> bool {
+    if let Some(blocked) = blocked {
+        // Check record alignment.
+        let location = Contig::new(
+            record.tid(),
+            record.pos() as isize,
+            record.cigar_len(),
+            NoStrand::Unknown,
+        );
+        if blocked.find(&location).next().is_some() {
+            return true;
+        }
+        // Check mate alignment.
+        if record.is_paired() && record.mtid() != -1 {
+            let location = Contig::new(record.mtid(), record.mpos() as isize, 1, NoStrand::Unknown);
+            if blocked.find(&location).next().is_some() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Return whether the record starts in one of the `spikes` and is not kept by downsampling.
+fn is_dropped(spikes: &[Spike], record: &bam::Record) -> bool {
+    for spike in spikes.iter().rev() {
+        if spike.tid != record.tid() || spike.range.end <= record.pos() {
+            break;
+        }
+        if spike.range.start <= record.pos() {
+            return qname_hash(record.qname()) as f64 >= spike.keep * u64::MAX as f64;
+        }
+    }
+    false
+}
+
+/// Consecutive windows of a region that are scanned together.
+#[derive(Debug, Clone)]
+struct Segment {
+    /// The region that the segment is part of.
+    region: Interval,
+    /// Reference ID of the region.
+    tid: i32,
+    /// Numbers of the windows of the region in the segment.
+    windows: std::ops::Range<i64>,
+    /// Number of windows in the whole region.
+    window_count: i64,
+}
+
+impl Segment {
+    /// Return the range of the window `window_no`, including the overlap with the previous one.
+    fn window(
+        &self,
+        window_no: i64,
+        window_size: i64,
+        window_overlap: i64,
+    ) -> std::ops::Range<i64> {
+        let region_start = self.region.range().start as i64;
+        let overlap = if window_no == 0 { 0 } else { window_overlap };
+        (region_start + window_no * window_size - overlap)
+            ..(region_start + (window_no + 1) * window_size)
+    }
+}
+
+/// Result of scanning one shard.
+struct ShardResult {
+    /// Number of written records by reference ID and reason.
+    reason_counts: BTreeMap<i32, BTreeMap<Reason, usize>>,
+    /// Coverage spikes found in the shard.
+    spikes: Vec<Spike>,
+}
+
+/// Extract reads from the buffer in the current window.
+///
+/// The `window` is the part of the current window not overlapping with the previous one.
+fn extract_reads_from_current_window<'a, I, R>(
+    state: &mut ExtractionState,
+    records: I,
+    window: &std::ops::Range<i64>,
+    config: &Config,
+    lib_properties: &ReadGroupProperties,
+    pass: i32,
+    blocked: &Option<AnnotMap<i32, ()>>,
+) -> Result<usize, Error>
+where
+    I: IntoIterator<Item = &'a R>,
+    R: Borrow<bam::Record> + 'a,
+{
+    let mut skipped = 0;
+    for record in records {
+        let record: &bam::Record = record.borrow();
+        let pos = record.pos();
+        if pos > window.end {
+            break;
+        }
+
+        if is_dropped(&state.spikes, record) || is_blocked(blocked, record) {
+            skipped += 1;
+            continue;
+        }
+
+        let mut reasons = interesting_reasons(record, &lib_properties, &config);
+        let interesting = !reasons.is_empty();
+        if interesting || state.mates.contains(&record.qname()) {
+            debug!(
+                "Writing {}/{}",
+                str::from_utf8(&record.qname()).unwrap(),
+                record.is_first_in_template()
+            );
+            if interesting && pass == 1 {
+                let mate = if record.is_paired() && record.mtid() >= 0 {
+                    (record.mtid(), record.mpos())
+                } else {
+                    (record.tid(), record.pos())
+                };
+                state.mates.insert(&record.qname(), mate)?;
+            } else if !interesting {
+                reasons.push(Reason::MateOfInteresting);
+            }
+
+            // Count each record only once, in the second pass of the window it starts in.
+            if pass == 2 && pos >= window.start {
+                let counts = state.reason_counts.entry(record.tid()).or_default();
+                for reason in &reasons {
+                    *counts.entry(*reason).or_insert(0) += 1;
+                }
+            }
+
+            let mut record = bam::Record::clone(record);
+            let tag = reasons_to_tag(&reasons);
+            record.push_aux(REASON_TAG, &bam::record::Aux::String(tag.as_bytes()));
+            state.sorter.push(record)?;
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Split `regions` into segments of at most `chunk_size` bases.
+///
+/// The chunk size is rounded up to a multiple of `window_size` such that the windows of the
+/// segments are the same as the windows of the whole region.
+fn split_regions(
+    regions: &[Interval],
+    header_view: &bam::HeaderView,
+    chunk_size: i64,
+    window_size: i64,
+) -> Result<Vec<Segment>, Error> {
+    let chunk_windows = std::cmp::max(1, (chunk_size + window_size - 1) / window_size);
+
+    let mut result = Vec::new();
+    for region in regions {
+        let tid = header_view
+            .tid(region.contig().as_bytes())
+            .ok_or(Error::InvalidRegion())? as i32;
+        let target_len = (region.range().end - region.range().start) as i64;
+        let window_count = (target_len + window_size - 1) / window_size;
+        let mut window_no = 0;
+        while window_no < window_count {
+            let end = std::cmp::min(window_no + chunk_windows, window_count);
+            result.push(Segment {
+                region: region.clone(),
+                tid,
+                windows: window_no..end,
+                window_count,
+            });
+            window_no = end;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Scan the segments of one shard with its own reader and mate tracker.
+///
+/// Records are written sorted and deduplicated to `path_output`, and the names pending at the end
+/// are written sorted by mate position to `path_pending` if given.
+#[allow(clippy::too_many_arguments)]
+fn scan_shard(
+    options: &Options,
+    config: &Config,
+    lib_properties: &ReadGroupProperties,
+    header: &bam::Header,
+    shard: &[Segment],
+    path_output: &str,
+    path_pending: Option<&Path>,
+    blocked: &Option<AnnotMap<i32, ()>>,
+    progress_bar: &Option<ProgressBar>,
+) -> Result<ShardResult, Error> {
+    let window_size = config.sliding_window_size;
+    let window_overlap = config.sliding_window_margin
+        + lib_properties.max_normal_isize()
+        + lib_properties.max_rlen();
+
+    let mut reader = open_indexed_reader(&options.path_input, config)?;
+    if config.htslib_io_threads > 0 {
+        reader.set_threads(config.htslib_io_threads)?;
+    }
+    let mut buffer = bam::RecordBuffer::new(reader, true);
+
+    // Windows with a depth above `max_depth` are coverage spikes, if the coverage is known.
+    let max_depth = lib_properties
+        .coverage
+        .map(|coverage| coverage * config.coverage_spike_factor)
+        .filter(|max_depth| *max_depth > 0.0);
+
+    let mut state = ExtractionState {
+        sorter: ExternalSorter::new(
+            header,
+            coordinate_order,
+            true,
+            config.scan_sort_max_in_memory,
+        ),
+        mates: MateTracker::new(config.mate_tracking_max_in_memory),
+        reason_counts: BTreeMap::new(),
+        spikes: Vec::new(),
+    };
+
+    for (segment_no, segment) in shard.iter().enumerate() {
+        let region = &segment.region;
+        if options.verbosity > 0 {
+            info!(
+                "Starting to scan {:?}, windows {}-{}",
+                region,
+                segment.windows.start + 1,
+                segment.windows.end
+            );
+        }
+
+        let mut skipped = 0;
+        for window_no in segment.windows.clone() {
+            let window = segment.window(window_no, window_size, window_overlap);
+            let (window_start, window_end) = (window.start, window.end);
+            let window_own_start = region.range().start as i64 + window_no * window_size;
+            let own_end = std::cmp::min(window_end, region.range().end as i64);
+            if options.verbosity > 0 {
+                info!(
+                    "Scanning window #{}/{}: {}:{}-{}",
+                    window_no + 1,
+                    segment.window_count,
+                    &region.contig(),
+                    window_start,
+                    window_end
+                );
+            }
+            // Count the depth of the buffered window.  The first shard window also counts the
+            // previous window as its records reach into the overlap.
+            if let Some(max_depth) = max_depth.filter(|_| segment_no == 0 && window_no > 0) {
+                let range = (window_own_start - window_size)..window_own_start;
+                buffer.fetch(
+                    region.contig().as_bytes(),
+                    std::cmp::min(range.start, window_start) as u64,
+                    range.end as u64,
+                )?;
+                state.check_depth(region, segment.tid, range, buffer.iter(), max_depth, config);
+            }
+            buffer.fetch(
+                region.contig().as_bytes(),
+                window_start as u64,
+                window_end as u64,
+            )?;
+            let spike = max_depth.and_then(|max_depth| {
+                state.check_depth(
+                    region,
+                    segment.tid,
+                    window_own_start..own_end,
+                    buffer.iter(),
+                    max_depth,
+                    config,
+                )
+            });
+
+            state.mates.advance(segment.tid, window_start, window_end)?;
+            // Records of downsampled spikes are dropped by name while extracting.
+            if !matches!(spike, Some(spike) if spike.keep <= 0.0) {
+                for pass in 1..=2 {
+                    skipped += extract_reads_from_current_window(
+                        &mut state,
+                        buffer.iter(),
+                        &(window_own_start..window_end),
+                        &config,
+                        &lib_properties,
+                        pass,
+                        &blocked,
+                    )?;
+                }
+            }
+
+            if let Some(prog_bar) = progress_bar {
+                prog_bar.inc(((own_end - window_own_start) / 1_000) as u64);
+            }
+        }
+
+        info!("Skipped {} BAM records on {:?}", skipped, region);
+    }
+
+    {
+        let mut writer = open_writer(path_output, header, config)?;
+        if config.htslib_io_threads > 0 {
+            writer.set_threads(config.htslib_io_threads)?;
+        }
+        state.sorter.finish(&mut writer)?;
+    }
+    if let Some(path_pending) = path_pending {
+        state.mates.write_pending(path_pending)?;
+    }
+
+    Ok(ShardResult {
+        reason_counts: state.reason_counts,
+        spikes: state.spikes,
+    })
+}
+
+/// Write the records of reads from earlier shards whose mates lie in the windows of `segment`.
+///
+/// The names in `path_seeds` are sorted by mate position.  As with the `MateTracker` of a serial
+/// scan, a name is active in the windows of the segment that contain its mate position, and all
+/// records of the name starting in these windows are written, e.g., also secondary and
+/// supplementary alignments.  The records of each locus of mate positions are fetched from the
+/// index and written sorted to `path_output`, unless they are dropped in the shard's `spikes`,
+/// blocked, or interesting (and thus already written by the scan).  Returns the number of written
+/// records by reference ID, counting each record only in the window it starts in.
+#[allow(clippy::too_many_arguments)]
+fn rescue_mates(
+    options: &Options,
+    config: &Config,
+    lib_properties: &ReadGroupProperties,
+    header: &bam::Header,
+    segment: &Segment,
+    path_seeds: &Path,
+    path_output: &str,
+    spikes: &[Spike],
+    blocked: &Option<AnnotMap<i32, ()>>,
+) -> Result<BTreeMap<i32, usize>, Error> {
+    let window_size = config.sliding_window_size;
+    let window_overlap = config.sliding_window_margin
+        + lib_properties.max_normal_isize()
+        + lib_properties.max_rlen();
+    let region_start = segment.region.range().start as i64;
+    let window = |window_no: i64| segment.window(window_no, window_size, window_overlap);
+    // Windows skipped as coverage spikes are not scanned for mates.
+    let is_skipped = |window_no: i64| {
+        let own_start = region_start + window_no * window_size;
+        spikes.iter().any(|spike| {
+            spike.tid == segment.tid && spike.range.start == own_start && spike.keep <= 0.0
+        })
+    };
+    // The windows of the segment whose range contains the mate position, the first one being
+    // the first window ending at or after it.
+    let active_windows = |mate_pos: i64| -> Vec<i64> {
+        let first = std::cmp::max(
+            segment.windows.start,
+            (mate_pos - region_start + window_size - 1) / window_size - 1,
+        );
+        (first..segment.windows.end)
+            .take_while(|window_no| window(*window_no).start <= mate_pos)
+            .filter(|window_no| !is_skipped(*window_no))
+            .collect()
+    };
+
+    let mut reader = open_indexed_reader(&options.path_input, config)?;
+    let mut sorter = ExternalSorter::new(
+        header,
+        coordinate_order,
+        true,
+        config.scan_sort_max_in_memory,
+    );
+    let mut counts = BTreeMap::new();
+
+    for_each_locus(
+        path_seeds,
+        config.sliding_window_size,
+        |tid, _, names: &HashSet<(Vec<u8>, i64)>| {
+            let mut active: HashMap<&[u8], Vec<i64>> = HashMap::new();
+            for (qname, mate_pos) in names {
+                active
+                    .entry(qname.as_slice())
+                    .or_default()
+                    .extend(active_windows(*mate_pos));
+            }
+            let windows = active.values().flatten();
+            let (first, last) = match (windows.clone().min(), windows.max()) {
+                (Some(first), Some(last)) => (*first, *last),
+                _ => return Ok(()),
+            };
+
+            reader.fetch(
+                tid as u32,
+                window(first).start as u64,
+                window(last).end as u64,
+            )?;
+            for record in reader.records() {
+                let mut record = record?;
+                let pos = record.pos();
+                let windows = match active.get(record.qname()) {
+                    Some(windows) => windows,
+                    None => continue,
+                };
+                if !windows
+                    .iter()
+                    .any(|window_no| window(*window_no).contains(&pos))
+                    || is_dropped(spikes, &record)
+                    || is_blocked(blocked, &record)
+                    || !interesting_reasons(&record, lib_properties, config).is_empty()
+                {
+                    continue;
+                }
+                if windows.contains(&((pos - region_start) / window_size)) {
+                    *counts.entry(tid).or_insert(0) += 1;
+                }
+                let tag = reasons_to_tag(&[Reason::MateOfInteresting]);
+                record.push_aux(REASON_TAG, &bam::record::Aux::String(tag.as_bytes()));
+                sorter.push(record)?;
+            }
+            Ok(())
+        },
+    )?;
+
+    let mut writer = open_writer(path_output, header, config)?;
+    if config.htslib_io_threads > 0 {
+        writer.set_threads(config.htslib_io_threads)?;
+    }
+    sorter.finish(&mut writer)?;
+    Ok(counts)
+}
+
+/// Extract reads from the path to the BAM file in the options (path_input) to the output BAM file.
+fn extract_reads(
+    options: &Options,
+    config: &Config,
+    lib_properties: &ReadGroupProperties,
+) -> Result<(), Error> {
+    // We will fetch records from window of size buffer_length into a bam::RecordBuffer and shift
+    // the window with a span of of window_overlap.  This will ensure that all records with a
+    // normal insert size are within the same window.
+    //
+    // We will then iterate over this window twice.  First, we capture the read names of all
+    // reads showing a PE/SR signal.  We then iterate a second time.  We collect the reads
+    // indicating a PE/SR signal or that were already present in the output file.  The second
+    // iteration is needed to capture pairs where the second read shows the split read signal.
+    //
+    // With more than one worker thread, the regions are split into shards of consecutive windows
+    // that are scanned in parallel.  Each shard writes its records and the names of reads with
+    // mates downstream of the shard, sorted by mate position, to temporary files.  The names are
+    // then distributed to the later shards that the mates lie in, and the mates are fetched from
+    // the index by locus and written to further temporary files.  All temporary files are then
+    // merged.
+    //
+    // Records are sorted by coordinate with bounded memory, and the copies of records written in
+    // both passes and in overlapping windows are removed.
+    //
+    // If the expected coverage is known, the depth of each window is counted from the buffered
+    // records.  Windows with a depth above `coverage_spike_factor` times the expected coverage are
+    // skipped or downsampled (by read name) and written to `<output>.spikes.bed`.
+    info!("Starting to scan BAM file...");
+    let reader = open_indexed_reader(&options.path_input, config)?;
+    let mut header = bam::Header::new();
+    for header_line in std::str::from_utf8(reader.header().as_bytes())
+        .unwrap()
+        .split('\n')
+    {
+        if !header_line.is_empty() {
+            if header_line.starts_with("@HD") {
+                header.push_record(&bam::header::HeaderRecord::new(
+                    b"HD\tVN:1.6\tSO:coordinate",
+                ));
+            } else if header_line.starts_with("@CO") {
+                // The library properties of earlier runs are replaced below.
+                if !header_line[4..].starts_with(LIBRARY_PROPERTIES_COMMENT) {
+                    header.push_comment(header_line[4..].as_bytes());
+                }
+            } else {
+                header.push_record(&bam::header::HeaderRecord::new(header_line[1..].as_bytes()));
+            }
+        }
+    }
+    // Pass on the library properties of each read group to `bam-collect-pesr`.
+    lib_properties.write_to_header(&mut header);
+    drop(reader);
+
+    let header_view = bam::HeaderView::from_header(&header);
+    let target_count = header_view.target_count() as usize;
+
+    let contigs: HashMap<String, i32> = header_view
+        .target_names()
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| (str::from_utf8(s).unwrap().to_string(), i as i32))
+        .collect();
+
+    let blocked = if let Some(blocked_regions_bed) = &config.blocked_regions_bed {
+        Some(bed_to_annot_map(blocked_regions_bed, &contigs)?)
+    } else {
+        None
+    };
+
+    let regions = if let Some(regions) = &options.regions {
+        regions.clone()
+    } else {
+        (0..target_count)
+            .map(|tid| {
+                Interval::new(
+                    str::from_utf8(header_view.target_names()[tid])
+                        .unwrap()
+                        .to_string(),
+                    0..(header_view.target_len(tid as u32).unwrap() as u64),
+                )
+            })
+            .collect()
+    };
+
+    match lib_properties.coverage {
+        Some(coverage) if config.coverage_spike_factor > 0.0 => info!(
+            "Treating windows with depth above {:.2} as coverage spikes",
+            coverage * config.coverage_spike_factor
+        ),
+        None if config.coverage_spike_factor > 0.0 => {
+            warn!("Coverage spike guard disabled as the expected coverage is unknown")
+        }
+        _ => info!("Coverage spike guard disabled"),
+    }
+
+    // Split regions into shards, a single shard with all segments when running serially.
+    let window_size = config.sliding_window_size;
+    let segments = split_regions(&regions, &header_view, config.scan_chunk_size, window_size)?;
+    let worker_threads = std::cmp::max(1, config.worker_threads);
+    let shards: Vec<Vec<Segment>> = if worker_threads > 1 {
+        segments.into_iter().map(|segment| vec![segment]).collect()
+    } else {
+        vec![segments]
+    };
+    info!(
+        "Scanning {} regions in {} shards with {} worker(s)",
+        regions.len(),
+        shards.len(),
+        worker_threads
+    );
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(worker_threads)
+        .build()?;
+
+    let tmp_dir = tempdir()?;
+
+    let progress_bar = if options.verbosity == 0 {
+        let total: u64 = regions
+            .iter()
+            .map(|r| (r.range().end - r.range().start) / 1_000)
+            .sum();
+        let prog_bar = ProgressBar::new(total);
+        prog_bar.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "scanning {msg:.green.bold} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
+            {pos:>7}/{len:7} Kbp {elapsed}/{eta}",
+                )
+                .progress_chars("=>-"),
+        );
+        prog_bar.set_message(&options.path_input);
+        Some(prog_bar)
+    } else {
+        None
+    };
+
+    // Scan the shards, writing sorted records and pending names to temporary files unless there
+    // is only one shard.
+    let tmp_path = |name: String| tmp_dir.path().join(name).to_str().unwrap().to_string();
+    let (paths_output, paths_pending): (Vec<String>, Vec<Option<PathBuf>>) = if shards.len() > 1 {
+        (0..shards.len())
+            .map(|shard_no| {
+                (
+                    tmp_path(format!("shard-{}.bam", shard_no)),
+                    Some(tmp_dir.path().join(format!("pending-{}.tsv", shard_no))),
+                )
+            })
+            .unzip()
+    } else {
+        (vec![options.path_output.clone()], vec![None])
+    };
+    let mut results = pool.install(|| {
+        shards
+            .par_iter()
+            .zip(paths_output.par_iter())
+            .zip(paths_pending.par_iter())
+            .map(|((shard, path_output), path_pending)| {
+                scan_shard(
+                    options,
+                    config,
+                    lib_properties,
+                    &header,
+                    shard,
+                    path_output,
+                    path_pending.as_deref(),
+                    &blocked,
+                    &progress_bar,
+                )
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+    if let Some(prog_bar) = progress_bar {
+        prog_bar.finish();
+    }
+
+    if shards.len() > 1 {
+        // Distribute the pending names to the later shards whose windows their mates lie in and
+        // write the records of these names found there.  Like in the serial scan, names with mates
+        // in the overlap of a shard's first window are active in that window as well.
+        info!("Collecting reads with mates in other shards...");
+        let window_overlap = config.sliding_window_margin
+            + lib_properties.max_normal_isize()
+            + lib_properties.max_rlen();
+        let ranges: Vec<(MatePos, MatePos)> = shards
+            .iter()
+            .map(|shard| {
+                let first = shard.first().unwrap();
+                let last = shard.last().unwrap();
+                let lower = (
+                    first.tid,
+                    first
+                        .window(first.windows.start, window_size, window_overlap)
+                        .start,
+                );
+                let upper = (
+                    last.tid,
+                    last.window(last.windows.end - 1, window_size, window_overlap)
+                        .end,
+                );
+                (lower, upper)
+            })
+            .collect();
+        let paths_pending: Vec<PathBuf> = paths_pending.into_iter().flatten().collect();
+        let paths_seeds: Vec<PathBuf> = (0..shards.len())
+            .map(|shard_no| tmp_dir.path().join(format!("seeds-{}.tsv", shard_no)))
+            .collect();
+        distribute_pending(&paths_pending, &ranges, &paths_seeds)?;
+
+        let paths_rescue: Vec<String> = (0..shards.len())
+            .map(|shard_no| tmp_path(format!("rescue-{}.bam", shard_no)))
+            .collect();
+        let rescue_counts = pool.install(|| {
+            results
+                .par_iter()
+                .zip(shards.par_iter())
+                .zip(paths_seeds.par_iter())
+                .zip(paths_rescue.par_iter())
+                .map(|(((result, shard), path_seeds), path_rescue)| {
+                    // There is one segment per shard when scanning in parallel.
+                    rescue_mates(
+                        options,
+                        config,
+                        lib_properties,
+                        &header,
+                        &shard[0],
+                        path_seeds,
+                        path_rescue,
+                        &result.spikes,
+                        &blocked,
+                    )
+                })
+                .collect::<Result<Vec<_>, Error>>()
+        })?;
+        for (result, counts) in results.iter_mut().zip(rescue_counts) {
+            for (tid, count) in counts {
+                *result
+                    .reason_counts
+                    .entry(tid)
+                    .or_default()
+                    .entry(Reason::MateOfInteresting)
+                    .or_insert(0) += count;
+            }
+        }
+
+        info!("Merging output of {} shards...", shards.len());
+        let mut writer = open_writer(&options.path_output, &header, config)?;
+        if config.htslib_io_threads > 0 {
+            writer.set_threads(config.htslib_io_threads)?;
+        }
+        // The shard outputs overlap at the shard borders and with the rescued mates, so merge and
+        // deduplicate them.
+        let paths_merge: Vec<String> = paths_output.into_iter().chain(paths_rescue).collect();
+        merge_sorted(&paths_merge, &mut writer, coordinate_order, true)?;
+    }
+
+    // Build index for sorted BAM/CRAM output.
+    let format = guess_bam_format(&options.path_output);
+    if options.path_output != "-" && !matches!(format, bam::Format::SAM) {
+        info!("Building index for {}", &options.path_output);
+        build_index(
+            &options.path_output,
+            &header_view,
+            std::cmp::max(1, config.htslib_io_threads),
+        )?;
+    }
+
+    let mut reason_counts: BTreeMap<i32, BTreeMap<Reason, usize>> = BTreeMap::new();
+    let mut spikes = Vec::new();
+    for result in results {
+        spikes.extend(result.spikes);
+        for (tid, counts) in result.reason_counts {
+            let total = reason_counts.entry(tid).or_default();
+            for (reason, count) in counts {
+                *total.entry(reason).or_insert(0) += count;
+            }
+        }
+    }
+
+    info!(
+        "Wrote {} records only because their mate was interesting",
+        reason_counts
+            .values()
+            .filter_map(|counts| counts.get(&Reason::MateOfInteresting))
+            .sum::<usize>()
+    );
+    // Write out per-contig reason summary next to the output file.
+    if options.path_output != "-" && options.path_output != "/dev/stdout" {
+        write_reasons_report(&options.path_output, &header_view, &reason_counts)?;
+        write_spikes_bed(&options.path_output, &header_view, &spikes)?;
+    }
+    info!("Done scanning BAM file...");
+    Ok(())
+}
+
+/// Write the number of written records by contig and reason to `<prefix>.reasons.json`.
+fn write_reasons_report(
+    prefix: &str,
+    header_view: &bam::HeaderView,
+    reason_counts: &BTreeMap<i32, BTreeMap<Reason, usize>>,
+) -> Result<(), Error> {
+    let mut report: BTreeMap<String, BTreeMap<&str, usize>> = BTreeMap::new();
+    for (tid, counts) in reason_counts {
+        let contig = str::from_utf8(header_view.tid2name(*tid as u32))?.to_string();
+        report.insert(
+            contig,
+            Reason::ALL
+                .iter()
+                .map(|reason| (reason.name(), *counts.get(reason).unwrap_or(&0)))
+                .collect(),
+        );
+    }
+
+    let path = format!("{}.reasons.json", prefix);
+    info!("Writing reasons report to {}", &path);
+    let mut file = File::create(&path)?;
+    file.write_all(serde_json::to_string_pretty(&report)?.as_bytes())?;
+    file.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/// Write the merged intervals of the coverage spikes to `<prefix>.spikes.bed`.
+///
+/// The file can be used as further blocked regions in vcf-annotate.
+fn write_spikes_bed(
+    prefix: &str,
+    header_view: &bam::HeaderView,
+    spikes: &[Spike],
+) -> Result<(), Error> {
+    // Shards may report the same window, so sort and merge overlapping and adjacent windows.
+    let mut intervals: Vec<(i32, i64, i64)> = spikes
+        .iter()
+        .map(|spike| (spike.tid, spike.range.start, spike.range.end))
+        .collect();
+    intervals.sort_unstable();
+    let mut merged: Vec<(i32, i64, i64)> = Vec::new();
+    for (tid, start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if last.0 == tid && last.2 >= start => last.2 = std::cmp::max(last.2, end),
+            _ => merged.push((tid, start, end)),
+        }
+    }
+
+    let path = format!("{}.spikes.bed", prefix);
+    info!(
+        "Writing {} coverage spike intervals to {}",
+        merged.len(),
+        &path
+    );
+    let mut file = File::create(&path)?;
+    for (tid, start, end) in merged {
+        writeln!(
+            file,
+            "{}\t{}\t{}",
+            str::from_utf8(header_view.tid2name(tid as u32))?,
+            start,
+            end
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Build the `bam-scan` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bam-scan")
+        .about("Scan BAM file for discordant and clipped reads")
+        .args(&[
+            Arg::from_usage("<input> 'input file to read from'"),
+            Arg::from_usage("<output> 'output file to write to'"),
+        ])
+}
+
+/// Run the `bam-scan` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output file must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    info!("Starting maelstrom bam-scan");
+    info!("options: {:?}", &options);
+
+    // Parse further settings from configuration file.
+    let config = load_config(&matches)?;
+    info!("options: {:?}", &config);
+
+    // Estimate the library insert size.
+    let lib_properties = estimate_library_insert_size(&options.path_input, &config)?;
+    // Write out library QC report next to the output file.
+    if options.path_output != "-" && options.path_output != "/dev/stdout" {
+        write_qc_report(&options.path_output, &lib_properties)?;
+    }
+    // Run the extraction algorithm.
+    extract_reads(&options, &config, &lib_properties)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interval;
+    use lib_common::bam::library::{example_read_group_properties, LibraryProperties};
+    use pretty_assertions::assert_eq;
+    use rust_htslib::{bam, bam::Read};
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that runs `extract_reads()` and compares the result.
+    fn _extract_reads_and_test(
+        tmp_dir: &TempDir,
+        path_input: &str,
+        path_expected: &str,
+        regions: &Option<Vec<Interval>>,
+        config_text: &str,
+    ) -> Result<(), super::Error> {
+        let path_output = String::from(tmp_dir.path().join("out.sam").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: regions.clone(),
+            path_config: None,
+            path_input: String::from(path_input),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str(config_text).unwrap();
+        let library_properties = super::ReadGroupProperties::uniform(LibraryProperties {
+            max_rlen: 100,
+            median_isize: 300.0,
+            std_dev_isize: 10.0,
+            max_normal_isize: 330,
+            ..Default::default()
+        });
+
+        super::extract_reads(&options, &config, &library_properties)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(&path_output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_hard_clipping_leading() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-hard-leading.sorted.bam",
+            "./src/tests/data/ex-pe-hard-leading.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_hard_clipping_trailing() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-hard-trailing.sorted.bam",
+            "./src/tests/data/ex-pe-hard-trailing.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_soft_clipping_negative() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-soft-neg.sorted.bam",
+            "./src/tests/data/ex-pe-soft-neg.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+    #[test]
+    fn identify_pairs_by_soft_clipping_positive() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-soft-pos.sorted.bam",
+            "./src/tests/data/ex-pe-soft-pos.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_different_tid() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-tid.sorted.bam",
+            "./src/tests/data/ex-pe-tid.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+    #[test]
+    fn identify_pairs_by_different_tid_with_blocked() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-tid.sorted.bam",
+            "./src/tests/data/ex-pe-tid.expected-blocked.sam",
+            &None,
+            "blocked_regions_bed = \"./src/tests/data/ex-blocked.bed\"",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_large_tlen() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-tlen.sorted.bam",
+            "./src/tests/data/ex-pe-tlen.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_by_orientation() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-orient.sorted.bam",
+            "./src/tests/data/ex-pe-orient.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_one_end_anchored_pairs() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-anchored.sorted.bam",
+            "./src/tests/data/ex-pe-anchored.expected.sam",
+            &None,
+            "extract_one_end_anchored = true",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_one_end_anchored_pairs_disabled() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-anchored.sorted.bam",
+            "./src/tests/data/ex-pe-anchored.expected-disabled.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_mates_with_secondary_alignments() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-secondary.sorted.bam",
+            "./src/tests/data/ex-pe-secondary.expected.sam",
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_pairs_in_parallel_shards() -> Result<(), super::Error> {
+        // One shard per window, the output must be the same as for the serial scan.
+        let config_text = "worker_threads = 4\nscan_chunk_size = 1000";
+        for name in &[
+            "hard-leading",
+            "hard-trailing",
+            "soft-neg",
+            "soft-pos",
+            "tid",
+            "tlen",
+            "orient",
+            "secondary",
+        ] {
+            let tmp_dir = TempDir::new("tests")?;
+            _extract_reads_and_test(
+                &tmp_dir,
+                &format!("./src/tests/data/ex-pe-{}.sorted.bam", name),
+                &format!("./src/tests/data/ex-pe-{}.expected.sam", name),
+                &None,
+                config_text,
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_sorted_indexed_bam() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_output = String::from(tmp_dir.path().join("out.bam").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: None,
+            path_config: None,
+            path_input: String::from("./src/tests/data/ex-pe-hard-trailing.sorted.bam"),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str("").unwrap();
+        let library_properties = super::ReadGroupProperties::uniform(LibraryProperties {
+            max_rlen: 100,
+            median_isize: 300.0,
+            std_dev_isize: 10.0,
+            max_normal_isize: 330,
+            ..Default::default()
+        });
+
+        super::extract_reads(&options, &config, &library_properties)?;
+
+        let mut reader = bam::IndexedReader::from_path(&path_output)?;
+        reader.fetch(1, 0, 1_000_000)?;
+        let positions: Vec<i64> = reader.records().map(|r| r.unwrap().pos()).collect();
+        assert_eq!(positions, vec![100_099]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pass_library_properties_in_header() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_output = String::from(tmp_dir.path().join("out.bam").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: None,
+            path_config: None,
+            path_input: String::from("./src/tests/data/ex-pe-tid.sorted.bam"),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str("").unwrap();
+        let library_properties = example_read_group_properties();
+
+        super::extract_reads(&options, &config, &library_properties)?;
+
+        // `bam-collect-pesr` loads the properties from the header in the same way.
+        let reader = bam::Reader::from_path(&path_output)?;
+        assert_eq!(
+            super::ReadGroupProperties::from_header(reader.header().as_bytes())?,
+            Some(library_properties)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_reasons_report_by_contig() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _extract_reads_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-pe-tid.sorted.bam",
+            "./src/tests/data/ex-pe-tid.expected.sam",
+            &None,
+            "",
+        )?;
+
+        let path_report = tmp_dir.path().join("out.sam.reasons.json");
+        let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(path_report)?)?;
+        assert_eq!(report["one"]["inter_chromosomal"], 1);
+        assert_eq!(report["one"]["mate_of_interesting"], 0);
+        assert_eq!(report["two"]["inter_chromosomal"], 1);
+
+        Ok(())
+    }
+
+    /// Helper that runs `extract_reads()` on the coverage spike example with known coverage and
+    /// compares the result and the spikes BED file.
+    fn _extract_spike_reads_and_test(
+        path_expected: &str,
+        expected_bed: &str,
+        config_text: &str,
+    ) -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_output = String::from(tmp_dir.path().join("out.sam").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: None,
+            path_config: None,
+            path_input: String::from("./src/tests/data/ex-pe-spike.sorted.bam"),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str(config_text).unwrap();
+        let mut library_properties = super::ReadGroupProperties::uniform(LibraryProperties {
+            max_rlen: 100,
+            median_isize: 300.0,
+            std_dev_isize: 10.0,
+            max_normal_isize: 330,
+            ..Default::default()
+        });
+        library_properties.coverage = Some(0.01);
+
+        super::extract_reads(&options, &config, &library_properties)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(&path_output).unwrap()
+        );
+        assert_eq!(
+            expected_bed,
+            fs::read_to_string(format!("{}.spikes.bed", &path_output))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn downsample_coverage_spike() -> Result<(), super::Error> {
+        _extract_spike_reads_and_test(
+            "./src/tests/data/ex-pe-spike.expected-downsample.sam",
+            "one\t10000\t20000\n",
+            "coverage_spike_factor = 20.0",
+        )
+    }
+
+    #[test]
+    fn skip_coverage_spike() -> Result<(), super::Error> {
+        _extract_spike_reads_and_test(
+            "./src/tests/data/ex-pe-spike.expected-skip.sam",
+            "one\t10000\t20000\n",
+            "coverage_spike_factor = 20.0\ncoverage_spike_action = \"skip\"",
+        )
+    }
+
+    #[test]
+    fn coverage_spike_guard_disabled() -> Result<(), super::Error> {
+        _extract_spike_reads_and_test("./src/tests/data/ex-pe-spike.expected.sam", "", "")
+    }
+
+    #[test]
+    fn invalid_coverage_spike_action() {
+        assert!(toml::from_str::<super::Config>("coverage_spike_action = \"drop\"").is_err());
+    }
+
+    #[test]
+    fn coverage_spike_in_parallel_shards() -> Result<(), super::Error> {
+        for action in &["skip", "downsample"] {
+            _extract_spike_reads_and_test(
+                &format!("./src/tests/data/ex-pe-spike.expected-{}.sam", action),
+                "one\t10000\t20000\n",
+                &format!(
+                    "worker_threads = 4\nscan_chunk_size = 1000\ncoverage_spike_factor = 20.0\n\
+                     coverage_spike_action = \"{}\"",
+                    action
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,516 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use tempfile::{tempdir, TempDir};
+
+use lib_common::error::Error;
+
+/// Position of a mate as reference ID and 0-based position.
+pub type MatePos = (i32, i64);
+
+/// Maximal number of runs kept open before they are merged into one.
+const MAX_OPEN_RUNS: usize = 64;
+
+/// Sorted run of read names with mate positions on disk, spilled or pending from a shard.
+struct SpillRun {
+    /// Reader for the remaining entries.
+    reader: BufReader<File>,
+    /// The next entry of the run, if any.
+    next: Option<(MatePos, Vec<u8>)>,
+}
+
+impl SpillRun {
+    /// Open the run at `path` and read its first entry.
+    fn open(path: &Path) -> Result<Self, Error> {
+        let mut result = SpillRun {
+            reader: BufReader::new(File::open(path)?),
+            next: None,
+        };
+        result.advance()?;
+        Ok(result)
+    }
+
+    /// Read the next entry into `self.next`.
+    fn advance(&mut self) -> Result<(), Error> {
+        let mut line = Vec::new();
+        self.next = if self.reader.read_until(b'\n', &mut line)? == 0 {
+            None
+        } else {
+            let line = std::str::from_utf8(&line)?.trim_end();
+            let arr: Vec<&str> = line.splitn(3, '\t').collect();
+            Some((
+                (arr[0].parse()?, arr[1].parse()?),
+                arr[2].as_bytes().to_vec(),
+            ))
+        };
+        Ok(())
+    }
+}
+
+/// Write one entry of a run to `writer`.
+fn write_entry<W: Write>(writer: &mut W, mate: MatePos, qname: &[u8]) -> Result<(), Error> {
+    writer.write_all(format!("{}\t{}\t", mate.0, mate.1).as_bytes())?;
+    writer.write_all(qname)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Merge the entries of `runs` by mate position and name, passing each to `f`.
+fn merge_runs<F>(mut runs: Vec<SpillRun>, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(usize, MatePos, &[u8]) -> Result<(), Error>,
+{
+    let mut heap = BinaryHeap::new();
+    for (run_no, run) in runs.iter_mut().enumerate() {
+        if let Some((mate, qname)) = run.next.take() {
+            heap.push(Reverse((mate, qname, run_no)));
+        }
+    }
+    while let Some(Reverse((mate, qname, run_no))) = heap.pop() {
+        f(run_no, mate, &qname)?;
+        runs[run_no].advance()?;
+        if let Some((mate, qname)) = runs[run_no].next.take() {
+            heap.push(Reverse((mate, qname, run_no)));
+        }
+    }
+    Ok(())
+}
+
+/// Distribute the names pending at the end of each shard to the later shards that their mates
+/// lie in.
+///
+/// The names pending at the end of shard `i`, sorted by mate position, are read from
+/// `paths_pending[i]`.  The names from the previous shards with mates in `ranges[j]` are written
+/// sorted by mate position to `paths_seeds[j]`.  The ranges must be in coordinate order, as the
+/// shards are.  All files are streamed, so the memory use does not depend on the number of names.
+pub fn distribute_pending<P: AsRef<Path>, Q: AsRef<Path>>(
+    paths_pending: &[P],
+    ranges: &[(MatePos, MatePos)],
+    paths_seeds: &[Q],
+) -> Result<(), Error> {
+    let runs = paths_pending
+        .iter()
+        .map(|path| SpillRun::open(path.as_ref()))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut writers = paths_seeds
+        .iter()
+        .map(|path| Ok(BufWriter::new(File::create(path)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    // The first shard whose range does not end before the current mate position.
+    let mut first = 0;
+    merge_runs(runs, |shard_no, mate, qname| {
+        while first < ranges.len() && ranges[first].1 < mate {
+            first += 1;
+        }
+        let mut later = first;
+        while later < ranges.len() && ranges[later].0 <= mate {
+            if later > shard_no {
+                write_entry(&mut writers[later], mate, qname)?;
+            }
+            later += 1;
+        }
+        Ok(())
+    })?;
+
+    for writer in &mut writers {
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Pass the names in the run at `path`, sorted by mate position, to `f` grouped by locus.
+///
+/// Consecutive entries on the same reference with mate positions at most `max_gap` apart form a
+/// locus.  `f` is called with the reference ID, the range of the mate positions, and the names
+/// with their mate positions.  Only the names of one locus are kept in memory.
+pub fn for_each_locus<F>(path: &Path, max_gap: i64, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(i32, std::ops::Range<i64>, &HashSet<(Vec<u8>, i64)>) -> Result<(), Error>,
+{
+    let mut run = SpillRun::open(path)?;
+    let mut names = HashSet::new();
+    let mut locus: Option<(i32, i64, i64)> = None;
+    while let Some(((tid, pos), qname)) = run.next.take() {
+        run.advance()?;
+        locus = match locus {
+            Some((locus_tid, start, end)) if locus_tid == tid && pos - end <= max_gap => {
+                Some((tid, start, pos))
+            }
+            Some((locus_tid, start, end)) => {
+                f(locus_tid, start..(end + 1), &names)?;
+                names.clear();
+                Some((tid, pos, pos))
+            }
+            None => Some((tid, pos, pos)),
+        };
+        names.insert((qname, pos));
+    }
+    if let Some((locus_tid, start, end)) = locus {
+        f(locus_tid, start..(end + 1), &names)?;
+    }
+    Ok(())
+}
+
+/// Exact tracking of the read names whose mates are to be written out.
+///
+/// A name is kept in memory only while the scan is in the window of the mate.  Names with mates
+/// further downstream are deferred and spilled to sorted runs on disk when more than
+/// `max_in_memory` are deferred.  When more than `MAX_OPEN_RUNS` runs are open, they are merged
+/// into one.  The BAM file must be scanned in coordinate order.
+pub struct MateTracker {
+    /// Maximal number of deferred names to keep in memory.
+    max_in_memory: usize,
+    /// Names of reads with mates in the current window, with the mate position.
+    active: HashMap<Vec<u8>, MatePos>,
+    /// Names of reads with mates downstream of the current window, by mate position.
+    deferred: BTreeMap<MatePos, Vec<Vec<u8>>>,
+    /// Number of names in `deferred`.
+    deferred_count: usize,
+    /// Runs of deferred names spilled to disk.
+    runs: Vec<SpillRun>,
+    /// Maximal number of open runs.
+    max_runs: usize,
+    /// Temporary directory for the spilled runs, created on first spill.
+    tmp_dir: Option<TempDir>,
+    /// Number of runs spilled so far, for naming the run files.
+    spill_count: usize,
+    /// Reference ID of the current window.
+    tid: i32,
+    /// Start of the current window.
+    window_start: i64,
+    /// End of the current window.
+    window_end: i64,
+}
+
+impl MateTracker {
+    /// Create new tracker that keeps at most `max_in_memory` deferred names in memory.
+    pub fn new(max_in_memory: usize) -> Self {
+        MateTracker {
+            max_in_memory,
+            active: HashMap::new(),
+            deferred: BTreeMap::new(),
+            deferred_count: 0,
+            runs: Vec::new(),
+            max_runs: MAX_OPEN_RUNS,
+            tmp_dir: None,
+            spill_count: 0,
+            tid: -1,
+            window_start: 0,
+            window_end: 0,
+        }
+    }
+
+    /// Return whether `qname` is tracked in the current window.
+    pub fn contains(&self, qname: &[u8]) -> bool {
+        self.active.contains_key(qname)
+    }
+
+    /// Track `qname` whose mate is at `mate`.
+    ///
+    /// Names with mates upstream of the current window are ignored as the mate has been passed.
+    pub fn insert(&mut self, qname: &[u8], mate: MatePos) -> Result<(), Error> {
+        if mate.0 == self.tid && mate.1 <= self.window_end {
+            if !self.active.contains_key(qname) {
+                self.active.insert(qname.to_vec(), mate);
+            }
+        } else if mate > (self.tid, self.window_end) {
+            self.deferred
+                .entry(mate)
+                .or_insert_with(Vec::new)
+                .push(qname.to_vec());
+            self.deferred_count += 1;
+            if self.deferred_count > self.max_in_memory {
+                self.spill()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move to the window `window_start..window_end` on reference `tid`.
+    ///
+    /// Names with mates upstream of the window are dropped and deferred names with mates in the
+    /// window become active.
+    pub fn advance(&mut self, tid: i32, window_start: i64, window_end: i64) -> Result<(), Error> {
+        self.tid = tid;
+        self.window_start = window_start;
+        self.window_end = window_end;
+
+        self.active
+            .retain(|_, mate| mate.0 == tid && mate.1 >= window_start);
+
+        let upper = (tid, window_end);
+        let rest = self.deferred.split_off(&(tid, window_end + 1));
+        let due = std::mem::replace(&mut self.deferred, rest);
+        for (mate, qnames) in due {
+            self.deferred_count -= qnames.len();
+            for qname in qnames {
+                self.activate(qname, mate);
+            }
+        }
+        for i in 0..self.runs.len() {
+            while let Some((mate, _)) = &self.runs[i].next {
+                if *mate > upper {
+                    break;
+                }
+                let (mate, qname) = self.runs[i].next.take().unwrap();
+                self.runs[i].advance()?;
+                self.activate(qname, mate);
+            }
+        }
+        self.runs.retain(|run| run.next.is_some());
+
+        Ok(())
+    }
+
+    /// Consume the tracker and write all names still tracked, sorted by mate position, to `path`.
+    ///
+    /// These are the names whose mates lie in the current window or downstream of it.
+    pub fn write_pending(mut self, path: &Path) -> Result<(), Error> {
+        for (qname, mate) in std::mem::take(&mut self.active) {
+            self.deferred
+                .entry(mate)
+                .or_insert_with(Vec::new)
+                .push(qname);
+        }
+        self.spill()?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        merge_runs(std::mem::take(&mut self.runs), |_, mate, qname| {
+            write_entry(&mut writer, mate, qname)
+        })?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Make `qname` active unless its mate is upstream of the current window.
+    fn activate(&mut self, qname: Vec<u8>, mate: MatePos) {
+        if mate >= (self.tid, self.window_start) {
+            self.active.entry(qname).or_insert(mate);
+        }
+    }
+
+    /// Return the path for a new run in the temporary directory.
+    fn new_run_path(&mut self) -> Result<PathBuf, Error> {
+        if self.tmp_dir.is_none() {
+            self.tmp_dir = Some(tempdir()?);
+        }
+        let path = self
+            .tmp_dir
+            .as_ref()
+            .unwrap()
+            .path()
+            .join(format!("run-{}.tsv", self.spill_count));
+        self.spill_count += 1;
+        Ok(path)
+    }
+
+    /// Add `run` to the open runs, merging all open runs into one if there are too many.
+    ///
+    /// This bounds the number of open files and the cost of `advance()`.
+    fn push_run(&mut self, run: SpillRun) -> Result<(), Error> {
+        self.runs.push(run);
+        if self.runs.len() > self.max_runs {
+            let path = self.new_run_path()?;
+            let mut writer = BufWriter::new(File::create(&path)?);
+            merge_runs(std::mem::take(&mut self.runs), |_, mate, qname| {
+                write_entry(&mut writer, mate, qname)
+            })?;
+            writer.flush()?;
+            self.runs.push(SpillRun::open(&path)?);
+        }
+        Ok(())
+    }
+
+    /// Write the deferred names to a new sorted run on disk.
+    fn spill(&mut self) -> Result<(), Error> {
+        let path = self.new_run_path()?;
+        {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            for (mate, qnames) in &self.deferred {
+                for qname in qnames {
+                    write_entry(&mut writer, *mate, qname)?;
+                }
+            }
+            writer.flush()?;
+        }
+        self.deferred.clear();
+        self.deferred_count = 0;
+        self.push_run(SpillRun::open(&path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MateTracker;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_mate_tracker_in_window() -> Result<(), super::Error> {
+        let mut tracker = MateTracker::new(10);
+        tracker.advance(0, 0, 1_000)?;
+        tracker.insert(b"read-1", (0, 500))?;
+        assert_eq!(tracker.contains(b"read-1"), true);
+        assert_eq!(tracker.contains(b"read-2"), false);
+
+        tracker.advance(0, 400, 2_000)?;
+        assert_eq!(tracker.contains(b"read-1"), true);
+        tracker.advance(0, 1_400, 3_000)?;
+        assert_eq!(tracker.contains(b"read-1"), false);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mate_tracker_deferred() -> Result<(), super::Error> {
+        let mut tracker = MateTracker::new(10);
+        tracker.advance(0, 0, 1_000)?;
+        tracker.insert(b"read-1", (0, 5_000))?;
+        tracker.insert(b"read-2", (1, 500))?;
+        assert_eq!(tracker.contains(b"read-1"), false);
+        assert_eq!(tracker.contains(b"read-2"), false);
+
+        tracker.advance(0, 4_000, 6_000)?;
+        assert_eq!(tracker.contains(b"read-1"), true);
+        assert_eq!(tracker.contains(b"read-2"), false);
+        tracker.advance(1, 0, 1_000)?;
+        assert_eq!(tracker.contains(b"read-1"), false);
+        assert_eq!(tracker.contains(b"read-2"), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mate_tracker_spilled() -> Result<(), super::Error> {
+        let mut tracker = MateTracker::new(1);
+        tracker.advance(0, 0, 1_000)?;
+        tracker.insert(b"read-1", (0, 5_000))?;
+        tracker.insert(b"read-2", (1, 500))?;
+        tracker.insert(b"read-3", (1, 700))?;
+        tracker.insert(b"read-4", (2, 100))?;
+
+        tracker.advance(0, 4_000, 6_000)?;
+        assert_eq!(tracker.contains(b"read-1"), true);
+        tracker.advance(1, 0, 1_000)?;
+        assert_eq!(tracker.contains(b"read-1"), false);
+        assert_eq!(tracker.contains(b"read-2"), true);
+        assert_eq!(tracker.contains(b"read-3"), true);
+        assert_eq!(tracker.contains(b"read-4"), false);
+        tracker.advance(2, 0, 1_000)?;
+        assert_eq!(tracker.contains(b"read-4"), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mate_tracker_merges_runs() -> Result<(), super::Error> {
+        let mut tracker = MateTracker::new(1);
+        tracker.max_runs = 2;
+        tracker.advance(0, 0, 1_000)?;
+        for i in 0..8 {
+            tracker.insert(format!("read-{}", i).as_bytes(), (1, 100 * (8 - i)))?;
+            assert!(tracker.runs.len() <= 2);
+        }
+
+        tracker.advance(1, 0, 450)?;
+        for i in 0..8 {
+            let qname = format!("read-{}", i);
+            assert_eq!(tracker.contains(qname.as_bytes()), i >= 4, "{}", qname);
+        }
+        tracker.advance(1, 400, 1_000)?;
+        for i in 0..8 {
+            let qname = format!("read-{}", i);
+            assert_eq!(tracker.contains(qname.as_bytes()), i <= 4, "{}", qname);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mate_tracker_write_pending() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path = tmp_dir.path().join("pending.tsv");
+        let mut tracker = MateTracker::new(1);
+        tracker.advance(0, 0, 1_000)?;
+        tracker.insert(b"read-1", (0, 500))?;
+        tracker.insert(b"read-2", (1, 500))?;
+        tracker.insert(b"read-3", (0, 5_000))?;
+        tracker.write_pending(&path)?;
+
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "0\t500\tread-1\n0\t5000\tread-3\n1\t500\tread-2\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_distribute_pending() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path = |name: &str| tmp_dir.path().join(name);
+        fs::write(
+            path("pending-0.tsv"),
+            "0\t500\tread-1\n0\t1500\tread-2\n0\t2500\tread-3\n",
+        )?;
+        fs::write(path("pending-1.tsv"), "0\t1950\tread-4\n0\t2500\tread-5\n")?;
+        fs::write(path("pending-2.tsv"), "")?;
+        let paths_seeds = vec![
+            path("seeds-0.tsv"),
+            path("seeds-1.tsv"),
+            path("seeds-2.tsv"),
+        ];
+        super::distribute_pending(
+            &[
+                path("pending-0.tsv"),
+                path("pending-1.tsv"),
+                path("pending-2.tsv"),
+            ],
+            &[
+                ((0, 0), (0, 1_000)),
+                ((0, 900), (0, 2_000)),
+                ((0, 1_900), (0, 3_000)),
+            ],
+            &paths_seeds,
+        )?;
+
+        assert_eq!(fs::read_to_string(&paths_seeds[0])?, "");
+        assert_eq!(fs::read_to_string(&paths_seeds[1])?, "0\t1500\tread-2\n");
+        assert_eq!(
+            fs::read_to_string(&paths_seeds[2])?,
+            "0\t1950\tread-4\n0\t2500\tread-3\n0\t2500\tread-5\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_each_locus() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path = tmp_dir.path().join("seeds.tsv");
+        fs::write(
+            &path,
+            "0\t500\tread-1\n0\t600\tread-2\n0\t5000\tread-3\n1\t5000\tread-4\n",
+        )?;
+        let mut loci = Vec::new();
+        super::for_each_locus(&path, 1_000, |tid, range, names| {
+            let mut names: Vec<(Vec<u8>, i64)> = names.iter().cloned().collect();
+            names.sort();
+            loci.push((tid, range, names));
+            Ok(())
+        })?;
+
+        assert_eq!(
+            loci,
+            vec![
+                (
+                    0,
+                    500..601,
+                    vec![(b"read-1".to_vec(), 500), (b"read-2".to_vec(), 600)]
+                ),
+                (0, 5_000..5_001, vec![(b"read-3".to_vec(), 5_000)]),
+                (1, 5_000..5_001, vec![(b"read-4".to_vec(), 5_000)]),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+/// bam-unique -- Remove duplicate rows from BAM files (as created by scanbam)
+use std::collections::HashSet;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+use rust_htslib::{bam, bam::Read};
+
+use lib_common::bam::read_id::{sidecar_path, ReadIds, READ_ID_TAG};
+use lib_common::bam::sort::{queryname_order, ExternalSorter};
+use lib_common::bam::{open_reader, open_writer};
+use lib_common::cli::{check_output_exists, load_config, required_value};
+use lib_common::error::Error;
+use lib_config::Config;
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// Path to input file.
+    path_input: String,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file.
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct RecordIdentifier {
+    first: bool,
+    tid: i32,
+    pos: i64,
+}
+
+impl RecordIdentifier {
+    fn from_record(record: &bam::Record) -> Self {
+        Self {
+            first: record.is_first_in_template(),
+            tid: record.tid(),
+            pos: record.pos(),
+        }
+    }
+}
+
+/// Write unique reads from chunk into the writer, tagged with the read ID of their name.
+fn write_unique(
+    chunk: &[bam::Record],
+    writer: &mut bam::Writer,
+    read_ids: &mut ReadIds,
+) -> Result<(), Error> {
+    let mut seen = HashSet::new();
+    let read_id = read_ids.of_name(chunk[0].qname())?.to_string();
+
+    for record in chunk.iter() {
+        let record_id = RecordIdentifier::from_record(record);
+        if !seen.contains(&record_id) {
+            let mut record = record.clone();
+            record.push_aux(READ_ID_TAG, &bam::record::Aux::String(read_id.as_bytes()));
+            writer.write(&record)?;
+            seen.insert(record_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Return whether the `@HD` line of the header declares the records grouped by query name.
+///
+/// The declaration is trusted, records are not checked to be grouped as this would require
+/// keeping all names seen so far.
+fn is_grouped_by_name(header: &[u8]) -> bool {
+    match String::from_utf8_lossy(header)
+        .lines()
+        .find(|line| line.starts_with("@HD"))
+    {
+        Some(line) => line
+            .split('\t')
+            .any(|token| token == "SO:queryname" || token == "GO:query"),
+        None => false,
+    }
+}
+
+/// Header for collated output, with the `@HD` line marking the records as grouped by query name.
+fn collated_header(header: &bam::HeaderView) -> bam::Header {
+    let mut result = bam::Header::new();
+    result.push_record(&bam::header::HeaderRecord::new(
+        b"HD\tVN:1.6\tSO:unsorted\tGO:query",
+    ));
+    for line in String::from_utf8_lossy(header.as_bytes()).lines() {
+        if line.is_empty() || line.starts_with("@HD") {
+            continue;
+        } else if line.starts_with("@CO") {
+            result.push_comment(line[4..].as_bytes());
+        } else {
+            result.push_record(&bam::header::HeaderRecord::new(line[1..].as_bytes()));
+        }
+    }
+    result
+}
+
+/// Write the unique records of each group of adjacent records with the same query name.
+fn write_unique_groups<I>(
+    records: I,
+    writer: &mut bam::Writer,
+    read_ids: &mut ReadIds,
+) -> Result<(), Error>
+where
+    I: Iterator<Item = Result<bam::Record, Error>>,
+{
+    let mut chunk: Vec<bam::Record> = Vec::new();
+    for record in records {
+        let record = record?;
+        if !chunk.is_empty() && record.qname() != chunk[0].qname() {
+            write_unique(&chunk, writer, read_ids)?;
+            chunk.clear();
+        }
+        chunk.push(record);
+    }
+    if !chunk.is_empty() {
+        write_unique(&chunk, writer, read_ids)?;
+    }
+
+    Ok(())
+}
+
+/// Main entry point after parsing command line and loading options.
+///
+/// Input that is not grouped by query name according to its `@HD` line is collated, with at
+/// most `config.collate_max_in_memory` records in memory, and streamed from the sorted runs.
+/// The names of the assigned read IDs are written to a sidecar next to the output.
+fn perform_filtration(options: &Options, config: &Config) -> Result<(), Error> {
+    info!("Starting to scan BAM file...");
+
+    let mut reader = open_reader(&options.path_input, config)?;
+    if config.htslib_io_threads > 0 {
+        reader.set_threads(config.htslib_io_threads)?;
+    }
+
+    let mut read_ids = ReadIds::new(config.read_ids_max_in_memory);
+    if is_grouped_by_name(reader.header().as_bytes()) {
+        let header = bam::Header::from_template(reader.header());
+        let mut writer = open_writer(&options.path_output, &header, config)?;
+        if config.htslib_io_threads > 0 {
+            writer.set_threads(config.htslib_io_threads)?;
+        }
+        write_unique_groups(
+            reader.records().map(|record| record.map_err(Error::from)),
+            &mut writer,
+            &mut read_ids,
+        )?;
+    } else {
+        info!("Input is not grouped by query name, collating...");
+        let header = collated_header(reader.header());
+        let mut sorter = ExternalSorter::new(
+            &header,
+            queryname_order,
+            false,
+            config.collate_max_in_memory,
+        );
+        for record in reader.records() {
+            sorter.push(record?)?;
+        }
+
+        let mut writer = open_writer(&options.path_output, &header, config)?;
+        if config.htslib_io_threads > 0 {
+            writer.set_threads(config.htslib_io_threads)?;
+        }
+        write_unique_groups(sorter.into_records()?, &mut writer, &mut read_ids)?;
+    }
+
+    if options.path_output != "-" {
+        read_ids.write_sidecar(&sidecar_path(&options.path_output), &options.path_input)?;
+    }
+
+    info!("Done scanning BAM file...");
+    Ok(())
+}
+
+/// Build the `bam-unique` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bam-unique")
+        .about("Remove duplicate rows from BAM files (as created by bam-scan)")
+        .args(&[
+            Arg::from_usage("<input> 'input file to read from'"),
+            Arg::from_usage("<output> 'output file to write to'"),
+        ])
+}
+
+/// Run the `bam-unique` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output file and its read ID sidecar must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    if options.path_output != "-" {
+        check_output_exists(&sidecar_path(&options.path_output), options.overwrite)?;
+    }
+    info!("Starting maelstrom bam-unique");
+    info!("options: {:?}", &options);
+
+    // Parse further settings from configuration file.
+    let config = load_config(&matches)?;
+    info!("options: {:?}", &config);
+
+    // Perform the block-wise filtration.
+    perform_filtration(&options, &config)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that runs `perform_filtration()` and compares the result.
+    fn _perform_filtration_and_test(
+        tmp_dir: &TempDir,
+        path_input: &str,
+        path_expected: &str,
+        config_text: &str,
+    ) -> Result<(), super::Error> {
+        let path_output = String::from(tmp_dir.path().join("out.sam").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            path_config: None,
+            path_input: String::from(path_input),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str(config_text).unwrap();
+
+        super::perform_filtration(&options, &config)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(&path_output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn perform_filtration_one_pair() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_filtration_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-duplicates.bam",
+            "./src/tests/data/ex-duplicates.expected.sam",
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn perform_filtration_two_pairs() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_filtration_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-duplicates2.bam",
+            "./src/tests/data/ex-duplicates2.expected.sam",
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn perform_filtration_coordinate_sorted() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_filtration_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-duplicates-coord.bam",
+            "./src/tests/data/ex-duplicates-coord.expected.sam",
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn perform_filtration_coordinate_sorted_spilled() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_filtration_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-duplicates-coord.bam",
+            "./src/tests/data/ex-duplicates-coord.expected.sam",
+            "collate_max_in_memory = 2",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_grouped_by_name() {
+        assert!(super::is_grouped_by_name(b"@HD\tVN:1.6\tSO:queryname\n"));
+        assert!(super::is_grouped_by_name(
+            b"@HD\tVN:1.6\tSO:unsorted\tGO:query\n@SQ\tSN:one\tLN:10\n"
+        ));
+        assert!(!super::is_grouped_by_name(b"@HD\tVN:1.6\tSO:coordinate\n"));
+        assert!(!super::is_grouped_by_name(b"@SQ\tSN:one\tLN:10\n"));
+    }
+}
@@ -1,4 +1,8 @@
 pub mod library;
+pub mod read_id;
+pub mod realign;
+pub mod sort;
+pub mod split;
 
 use std::path::Path;
 
@@ -6,17 +10,88 @@ use bio_types::genome::Interval;
 use regex::Regex;
 use rust_htslib::{bam, bam::Read};
 
+use lib_config::Config;
+
 use super::error::Error;
 
 /// Return `bam::Format` for the given filename.
 pub fn guess_bam_format(filename: &str) -> bam::Format {
     if filename.ends_with(".bam") {
         bam::Format::BAM
+    } else if filename.ends_with(".cram") {
+        bam::Format::CRAM
     } else {
         bam::Format::SAM
     }
 }
 
+/// Open SAM/BAM/CRAM file (or stdin for `-`) for reading.
+///
+/// The reference from `config.path_reference_fasta` is used for decoding CRAM files.
+pub fn open_reader(path: &str, config: &Config) -> Result<bam::Reader, Error> {
+    let mut reader = if path == "-" {
+        bam::Reader::from_stdin()?
+    } else {
+        bam::Reader::from_path(path)?
+    };
+    if let Some(path_reference) = &config.path_reference_fasta {
+        reader.set_reference(path_reference)?;
+    }
+    Ok(reader)
+}
+
+/// Open indexed SAM/BAM/CRAM file for reading.
+///
+/// The reference from `config.path_reference_fasta` is used for decoding CRAM files.
+pub fn open_indexed_reader(path: &str, config: &Config) -> Result<bam::IndexedReader, Error> {
+    let mut reader = bam::IndexedReader::from_path(path)?;
+    if let Some(path_reference) = &config.path_reference_fasta {
+        reader.set_reference(path_reference)?;
+    }
+    Ok(reader)
+}
+
+/// Open SAM/BAM/CRAM file for writing, the format is guessed from the file name.
+///
+/// Writing CRAM requires `config.path_reference_fasta` to be set.
+pub fn open_writer(
+    path: &str,
+    header: &bam::Header,
+    config: &Config,
+) -> Result<bam::Writer, Error> {
+    let format = guess_bam_format(path);
+    let is_cram = matches!(format, bam::Format::CRAM);
+    if is_cram && config.path_reference_fasta.is_none() {
+        return Err(Error::ReferenceRequired());
+    }
+    let mut writer = bam::Writer::from_path(path, header, format)?;
+    if let (true, Some(path_reference)) = (is_cram, &config.path_reference_fasta) {
+        writer.set_reference(path_reference)?;
+    }
+    Ok(writer)
+}
+
+/// Build the index for the coordinate-sorted BAM/CRAM file at `path`.
+///
+/// CRAM files get a `.crai` index.  BAM files get a `.bai` index unless a contig is too long for
+/// it, in which case a `.csi` index is built.
+pub fn build_index(path: &str, header: &bam::HeaderView, threads: usize) -> Result<(), Error> {
+    let max_len = (0..header.target_count())
+        .filter_map(|tid| header.target_len(tid))
+        .max()
+        .unwrap_or(0);
+    let idx_type = if matches!(guess_bam_format(path), bam::Format::CRAM) {
+        // htslib builds a `.crai` index for CRAM files when called with `min_shift` 0.
+        bam::index::Type::BAI
+    } else if max_len >= 1 << 29 {
+        bam::index::Type::CSI(14)
+    } else {
+        bam::index::Type::BAI
+    };
+    bam::index::build(path, None, idx_type, threads as u32)?;
+    Ok(())
+}
+
 /// Generate list of all contigs from BAM header.
 pub fn build_chroms_bam(
     header: &bam::HeaderView,
@@ -106,6 +181,58 @@ mod tests {
     fn test_guess_bam_format() {
         assert_matches!(guess_bam_format("ex.sam"), bam::Format::SAM);
         assert_matches!(guess_bam_format("ex.bam"), bam::Format::BAM);
+        assert_matches!(guess_bam_format("ex.cram"), bam::Format::CRAM);
         assert_matches!(guess_bam_format("ex.xxx"), bam::Format::SAM);
     }
+
+    #[test]
+    fn test_open_writer_cram_requires_reference() {
+        let config: Config = toml::from_str("").unwrap();
+        let header = bam::Header::new();
+        assert_matches!(
+            open_writer("ex.cram", &header, &config).err(),
+            Some(Error::ReferenceRequired())
+        );
+    }
+
+    #[test]
+    fn test_cram_round_trip() -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path_cram = tmp_dir.path().join("out.cram");
+        let path_cram = path_cram.to_str().unwrap();
+        let config: Config =
+            toml::from_str("path_reference_fasta = \"./src/tests/data/ex-clipped.fa\"")?;
+
+        let mut reader = open_reader("./src/tests/data/ex-clipped.sam", &config)?;
+        let header = bam::Header::from_template(reader.header());
+        let mut records = reader.records().collect::<Result<Vec<_>, _>>()?;
+        records.sort_by_key(|record| record.pos());
+        {
+            let mut writer = open_writer(path_cram, &header, &config)?;
+            for record in &records {
+                writer.write(record)?;
+            }
+        }
+
+        let mut reader = open_reader(path_cram, &config)?;
+        let read_back = reader.records().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(read_back.len(), records.len());
+        for (expected, record) in records.iter().zip(&read_back) {
+            assert_eq!(record.qname(), expected.qname());
+            assert_eq!(record.pos(), expected.pos());
+            assert_eq!(record.cigar().to_string(), expected.cigar().to_string());
+            assert_eq!(record.seq().as_bytes(), expected.seq().as_bytes());
+        }
+
+        build_index(path_cram, reader.header(), 1)?;
+        assert!(Path::new(&format!("{}.crai", path_cram)).exists());
+        let mut reader = open_indexed_reader(path_cram, &config)?;
+        reader.fetch(0, 250, 750)?;
+        let qnames = reader
+            .records()
+            .map(|record| Ok(record?.qname().to_vec()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        assert_eq!(qnames, vec![b"unplaced".to_vec(), b"inversion".to_vec()]);
+        Ok(())
+    }
 }
@@ -1,12 +1,23 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+
+use super::super::bed_to_annot_map;
 use super::super::error::Error;
+use super::{open_indexed_reader, open_reader};
 
+use bio_types::annot::contig::Contig;
+use bio_types::strand::NoStrand;
 use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rust_htslib::{bam, bam::Read};
+use serde::Serialize;
 
 use lib_config::Config;
 
 /// Library properties.
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct LibraryProperties {
     /// Maximal read length.
     pub max_rlen: i64,
@@ -16,52 +27,250 @@ pub struct LibraryProperties {
     pub std_dev_isize: f64,
     /// Maximal normal insert size
     pub max_normal_isize: i64,
+    /// Histogram of insert sizes in the sample (insert size to count)
+    pub isize_histogram: BTreeMap<i64, usize>,
+    /// Histogram of read lengths in the sample (read length to count)
+    pub rlen_histogram: BTreeMap<i64, usize>,
 }
 
-/// Estimate the library insert size.
+impl LibraryProperties {
+    /// One-line summary for logging, without the histograms.
+    pub fn summary(&self) -> String {
+        format!(
+            "max_rlen={}, median_isize={}, std_dev_isize={:.2}, max_normal_isize={}",
+            self.max_rlen, self.median_isize, self.std_dev_isize, self.max_normal_isize
+        )
+    }
+}
+
+/// Leading token of the `@CO` header lines with library properties.
+pub const LIBRARY_PROPERTIES_COMMENT: &str = "maelstrom-library-properties";
+
+/// Version of the library properties header lines.
 ///
-/// Current main limitation: PE read, no artifact filter.
-pub fn estimate_library_insert_size(
-    path_input: &str,
-    config: &Config,
-) -> Result<LibraryProperties, Error> {
-    info!(
-        "reading {} records to estimate insert size...",
-        config.lib_estimation_sample_size
-    );
+/// Readers reject newer versions and ignore unknown keys, so keys may be added without bumping.
+pub const LIBRARY_PROPERTIES_VERSION: u32 = 1;
 
-    let mut reader = bam::Reader::from_path(&path_input)?;
-    let mut insert_sizes = Vec::new();
-    for r in reader.records() {
-        let record = r?;
-        if record.is_paired()
-            && record.is_proper_pair()
-            && record.insert_size() > 0
-            && record.tid() == record.mtid()
-            && !record.is_unmapped()
-            && !record.is_mate_unmapped()
-            && !record.is_secondary()
-            && !record.is_supplementary()
-            && !record.is_quality_check_failed()
-            && !record.is_duplicate()
-        {
-            insert_sizes.push(record.insert_size());
-            if insert_sizes.len() > config.lib_estimation_sample_size {
-                break;
+/// Read group name of the fallback library properties in the header.
+const FALLBACK_READ_GROUP: &str = "*";
+
+/// Library properties of all read groups in a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadGroupProperties {
+    /// Library properties by read group ID.
+    pub by_read_group: HashMap<String, LibraryProperties>,
+    /// Library properties for records without a (known) read group.
+    pub fallback: LibraryProperties,
+    /// Expected coverage of the file, if estimated.
+    pub coverage: Option<f64>,
+}
+
+impl ReadGroupProperties {
+    /// Use the same library properties for all records.
+    pub fn uniform(lib_properties: LibraryProperties) -> Self {
+        Self {
+            by_read_group: HashMap::new(),
+            fallback: lib_properties,
+            coverage: None,
+        }
+    }
+
+    /// Return library properties for the given read group ID.
+    pub fn get(&self, read_group: Option<&str>) -> &LibraryProperties {
+        read_group
+            .and_then(|rg| self.by_read_group.get(rg))
+            .unwrap_or(&self.fallback)
+    }
+
+    /// Return library properties for the read group of the given record.
+    pub fn for_record(&self, record: &bam::Record) -> &LibraryProperties {
+        self.get(read_group_of(record))
+    }
+
+    /// Largest maximal normal insert size over all read groups.
+    pub fn max_normal_isize(&self) -> i64 {
+        self.by_read_group
+            .values()
+            .map(|p| p.max_normal_isize)
+            .fold(self.fallback.max_normal_isize, std::cmp::max)
+    }
+
+    /// Largest maximal read length over all read groups.
+    pub fn max_rlen(&self) -> i64 {
+        self.by_read_group
+            .values()
+            .map(|p| p.max_rlen)
+            .fold(self.fallback.max_rlen, std::cmp::max)
+    }
+
+    /// Add one `@CO` line with the library properties of each read group to `header`.
+    ///
+    /// The lines are `maelstrom-library-properties` followed by tab-separated `key:value` pairs,
+    /// the fallback properties have the read group `*`.  The histograms are not written.
+    pub fn write_to_header(&self, header: &mut bam::Header) {
+        let mut read_groups: Vec<(&str, &LibraryProperties)> = self
+            .by_read_group
+            .iter()
+            .map(|(read_group, lib_properties)| (read_group.as_str(), lib_properties))
+            .collect();
+        read_groups.sort_by_key(|(read_group, _)| *read_group);
+        read_groups.push((FALLBACK_READ_GROUP, &self.fallback));
+
+        for (read_group, lib_properties) in read_groups {
+            header.push_comment(
+                format!(
+                    "{}\tversion:{}\tread_group:{}\tmax_rlen:{}\tmedian_isize:{}\t\
+                     std_dev_isize:{}\tmax_normal_isize:{}",
+                    LIBRARY_PROPERTIES_COMMENT,
+                    LIBRARY_PROPERTIES_VERSION,
+                    read_group,
+                    lib_properties.max_rlen,
+                    lib_properties.median_isize,
+                    lib_properties.std_dev_isize,
+                    lib_properties.max_normal_isize,
+                )
+                .as_bytes(),
+            );
+        }
+    }
+
+    /// Load the library properties written by `write_to_header()` from the header text.
+    ///
+    /// Returns `None` if the header has no library properties.  Without fallback properties,
+    /// records without a known read group are judged by the widest library.
+    pub fn from_header(header: &[u8]) -> Result<Option<Self>, Error> {
+        let mut by_read_group = HashMap::new();
+        let mut fallback = None;
+        for line in String::from_utf8_lossy(header).lines() {
+            if !line.starts_with("@CO\t") {
+                continue;
+            }
+            if let Some((read_group, lib_properties)) = parse_library_properties(&line[4..])? {
+                if read_group == FALLBACK_READ_GROUP {
+                    fallback = Some(lib_properties);
+                } else {
+                    by_read_group.insert(read_group, lib_properties);
+                }
             }
         }
+
+        let fallback = fallback.or_else(|| {
+            by_read_group
+                .values()
+                .max_by_key(|p| p.max_normal_isize)
+                .cloned()
+        });
+        Ok(fallback.map(|fallback| Self {
+            by_read_group,
+            fallback,
+            coverage: None,
+        }))
     }
+}
 
-    if insert_sizes.is_empty() {
-        panic!("Found no reads in input file!");
-    } else if insert_sizes.len() < config.lib_estimation_sample_size {
-        warn!(
-            "Only found {} records instead of {}",
-            insert_sizes.len(),
-            config.lib_estimation_sample_size
-        );
+/// Parse the read group and library properties from a header comment.
+///
+/// Returns `None` if the comment does not contain library properties.
+fn parse_library_properties(comment: &str) -> Result<Option<(String, LibraryProperties)>, Error> {
+    let mut tokens = comment.split('\t');
+    if tokens.next() != Some(LIBRARY_PROPERTIES_COMMENT) {
+        return Ok(None);
+    }
+    let mut values = HashMap::new();
+    for token in tokens {
+        match token.find(':') {
+            Some(pos) => values.insert(&token[..pos], &token[(pos + 1)..]),
+            None => return Err(Error::InvalidLibraryProperties(comment.to_string())),
+        };
+    }
+    let value = |key: &str| {
+        values
+            .get(key)
+            .ok_or_else(|| Error::InvalidLibraryProperties(comment.to_string()))
+    };
+
+    let version: u32 = value("version")?.parse()?;
+    if version > LIBRARY_PROPERTIES_VERSION {
+        return Err(Error::UnsupportedLibraryPropertiesVersion(version));
+    }
+    Ok(Some((
+        value("read_group")?.to_string(),
+        LibraryProperties {
+            max_rlen: value("max_rlen")?.parse()?,
+            median_isize: value("median_isize")?.parse()?,
+            std_dev_isize: value("std_dev_isize")?.parse()?,
+            max_normal_isize: value("max_normal_isize")?.parse()?,
+            ..Default::default()
+        },
+    )))
+}
+
+/// Return the read group ID from the `RG` tag of the record, if any.
+pub fn read_group_of(record: &bam::Record) -> Option<&str> {
+    match record.aux(b"RG") {
+        Some(bam::record::Aux::String(rg)) => std::str::from_utf8(rg).ok(),
+        _ => None,
+    }
+}
+
+/// Insert sizes and read lengths sampled from the records of one library.
+#[derive(Debug, Default)]
+struct LibrarySample {
+    insert_sizes: Vec<i64>,
+    read_lengths: Vec<i64>,
+}
+
+impl LibrarySample {
+    fn len(&self) -> usize {
+        self.insert_sizes.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.insert_sizes.is_empty()
     }
 
+    fn push(&mut self, record: &bam::Record) {
+        self.insert_sizes.push(record.insert_size());
+        self.read_lengths.push(read_length(record));
+    }
+}
+
+/// Length of the read, including hard-clipped bases.
+fn read_length(record: &bam::Record) -> i64 {
+    let hard_clipped: u32 = record
+        .cigar()
+        .iter()
+        .map(|op| match op {
+            bam::record::Cigar::HardClip(len) => *len,
+            _ => 0,
+        })
+        .sum();
+    record.seq_len() as i64 + hard_clipped as i64
+}
+
+/// Number of reference bases covered by the record when estimating coverage.
+///
+/// Unmapped, secondary, and supplementary records do not count.
+pub fn coverage_bases(record: &bam::Record) -> i64 {
+    if record.is_unmapped() || record.is_secondary() || record.is_supplementary() {
+        0
+    } else {
+        record.cigar().end_pos() - record.pos()
+    }
+}
+
+/// Build histogram from the given values.
+fn histogram(values: &[i64]) -> BTreeMap<i64, usize> {
+    let mut result = BTreeMap::new();
+    for value in values {
+        *result.entry(*value).or_insert(0) += 1;
+    }
+    result
+}
+
+/// Compute library properties from a sample of insert sizes and read lengths.
+fn compute_properties(sample: &LibrarySample, config: &Config) -> LibraryProperties {
+    let mut insert_sizes = sample.insert_sizes.clone();
     insert_sizes.sort();
     let median: f64 = insert_sizes[insert_sizes.len() / 2] as f64;
     let delta: f64 = config.library_cutoff_deviation * config.library_cutoff_sd_mult * median;
@@ -75,8 +284,8 @@ pub fn estimate_library_insert_size(
 
     let mut count = 0;
     let mut variance: f64 = 0.0;
-    for i in insert_sizes {
-        let i = i as f64;
+    for i in &insert_sizes {
+        let i = *i as f64;
         if i >= cutoff_min && i <= cutoff_max {
             variance += (i - median) * (i - median);
             count += 1;
@@ -85,16 +294,393 @@ pub fn estimate_library_insert_size(
     let std_dev = (variance / (count as f64)).sqrt();
     let max_normal = median + config.lib_estimation_sd_mult * std_dev;
 
-    let result = LibraryProperties {
-        max_rlen: 150, // TODO: fixme
+    LibraryProperties {
+        max_rlen: sample.read_lengths.iter().cloned().max().unwrap_or(0),
         median_isize: median,
         std_dev_isize: std_dev,
         max_normal_isize: max_normal.ceil() as i64,
+        isize_histogram: histogram(&insert_sizes),
+        rlen_histogram: histogram(&sample.read_lengths),
+    }
+}
+
+/// Return whether the record is used for estimating the library properties.
+fn is_sampled(record: &bam::Record) -> bool {
+    record.is_paired()
+        && record.is_proper_pair()
+        && record.insert_size() > 0
+        && record.tid() == record.mtid()
+        && !record.is_unmapped()
+        && !record.is_mate_unmapped()
+        && !record.is_secondary()
+        && !record.is_supplementary()
+        && !record.is_quality_check_failed()
+        && !record.is_duplicate()
+}
+
+/// Collects the per-read group samples for library estimation.
+struct Sampler {
+    /// Number of records to sample per read group.
+    sample_size: usize,
+    /// Read group IDs from the header.
+    read_groups: Vec<String>,
+    /// Sample by read group, records without read group use the empty string.
+    samples: HashMap<String, LibrarySample>,
+    /// Sample over all read groups.
+    all_samples: LibrarySample,
+}
+
+impl Sampler {
+    fn new(sample_size: usize, read_groups: Vec<String>) -> Self {
+        Self {
+            sample_size,
+            read_groups,
+            samples: HashMap::new(),
+            all_samples: LibrarySample::default(),
+        }
+    }
+
+    /// Number of records sampled for the read group of the given record.
+    fn sampled(&self, record: &bam::Record) -> usize {
+        self.samples
+            .get(read_group_of(record).unwrap_or(""))
+            .map(|sample| sample.len())
+            .unwrap_or(0)
+    }
+
+    /// Add record to the sample of its read group unless that one is full already.
+    fn push(&mut self, record: &bam::Record) {
+        let read_group = read_group_of(record).unwrap_or("").to_string();
+        let sample = self
+            .samples
+            .entry(read_group)
+            .or_insert_with(Default::default);
+        if sample.len() < self.sample_size {
+            sample.push(record);
+            self.all_samples.push(record);
+        }
+    }
+
+    /// Return whether the samples of all read groups are full.
+    fn is_done(&self) -> bool {
+        if self.read_groups.is_empty() {
+            self.all_samples.len() >= self.sample_size
+        } else {
+            self.read_groups.iter().all(|rg| {
+                self.samples
+                    .get(rg)
+                    .map(|sample| sample.len() >= self.sample_size)
+                    .unwrap_or(false)
+            })
+        }
+    }
+
+    /// Compute the library properties from the samples.
+    fn finish(self, config: &Config, coverage: Option<f64>) -> Result<ReadGroupProperties, Error> {
+        if self.all_samples.is_empty() {
+            return Err(Error::NoReadsForLibraryEstimation());
+        }
+
+        let mut by_read_group = HashMap::new();
+        for (read_group, sample) in self.samples {
+            if sample.len() < self.sample_size {
+                warn!(
+                    "Only found {} records instead of {} for read group {:?}",
+                    sample.len(),
+                    self.sample_size,
+                    &read_group
+                );
+            }
+            if !read_group.is_empty() {
+                let lib_properties = compute_properties(&sample, config);
+                info!(
+                    "library properties for read group {}: {}",
+                    &read_group,
+                    lib_properties.summary()
+                );
+                by_read_group.insert(read_group, lib_properties);
+            }
+        }
+
+        let result = ReadGroupProperties {
+            by_read_group,
+            fallback: compute_properties(&self.all_samples, config),
+            coverage,
+        };
+        info!(
+            "library properties (all read groups): {}",
+            result.fallback.summary()
+        );
+        if let Some(coverage) = coverage {
+            info!("expected coverage: {:.2}", coverage);
+        }
+        Ok(result)
+    }
+}
+
+/// Estimate the library insert size for each read group.
+///
+/// Up to `config.lib_estimation_sample_size` proper pairs of each read group are used.  The
+/// fallback properties are computed from the pairs of all read groups together.  If the input
+/// file is indexed and `config.lib_estimation_random_regions` is non-zero, the records are drawn
+/// from random regions on the autosomes, otherwise or if the random regions contain no suitable
+/// records, they are read from the start of the file.
+///
+/// Current main limitation: PE read, no artifact filter.
+pub fn estimate_library_insert_size(
+    path_input: &str,
+    config: &Config,
+) -> Result<ReadGroupProperties, Error> {
+    info!(
+        "reading {} records per read group to estimate insert size...",
+        config.lib_estimation_sample_size
+    );
+
+    if config.lib_estimation_random_regions > 0 {
+        match open_indexed_reader(path_input, config) {
+            Ok(reader) => match sample_random_regions(reader, config)? {
+                Some(result) => return Ok(result),
+                None => {
+                    warn!("found no reads in random regions, reading from start of file instead")
+                }
+            },
+            Err(e) => warn!(
+                "could not open index ({}), reading from start of file instead",
+                e
+            ),
+        }
+    }
+    sample_from_start(path_input, config)
+}
+
+/// Sample the records for library estimation from the start of the file.
+///
+/// The expected coverage is estimated from all records read, over the lengths of the contigs
+/// passed and the part of the last contig up to the last record.
+fn sample_from_start(path_input: &str, config: &Config) -> Result<ReadGroupProperties, Error> {
+    let mut reader = open_reader(path_input, config)?;
+    let header = reader.header().clone();
+    let mut sampler = Sampler::new(
+        config.lib_estimation_sample_size,
+        read_groups_from_header(reader.header().as_bytes()),
+    );
+    // Stop reading eventually if some read groups have no or only few records.
+    let max_candidates =
+        10 * config.lib_estimation_sample_size * std::cmp::max(1, sampler.read_groups.len());
+
+    let mut candidates = 0;
+    let mut covered_bases = 0;
+    let mut passed_len = 0;
+    // Reference ID and position of the last record on a contig.
+    let mut last: Option<(i32, i64)> = None;
+    for r in reader.records() {
+        let record = r?;
+        if record.tid() >= 0 {
+            if let Some((tid, _)) = last.filter(|(tid, _)| *tid != record.tid()) {
+                passed_len += header.target_len(tid as u32).unwrap_or(0);
+            }
+            last = Some((record.tid(), record.pos()));
+            covered_bases += coverage_bases(&record);
+        }
+        if is_sampled(&record) {
+            candidates += 1;
+            sampler.push(&record);
+            if sampler.is_done() || candidates >= max_candidates {
+                break;
+            }
+        }
+    }
+
+    let seen_len = passed_len + last.map(|(_, pos)| pos as u64 + 1).unwrap_or(0);
+    let coverage = if seen_len > 0 {
+        Some(covered_bases as f64 / seen_len as f64)
+    } else {
+        None
+    };
+    sampler.finish(config, coverage)
+}
+
+/// Return whether the contig name looks like a (human) autosome, e.g., `1` or `chr22`.
+fn is_autosome(name: &str) -> bool {
+    let name = name.strip_prefix("chr").unwrap_or(name);
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Sample the records for library estimation from random regions on the autosomes.
+///
+/// Regions overlapping with `config.blocked_regions_bed` are skipped.  If no contig looks like an
+/// autosome then all contigs are used.  The expected coverage is estimated from all records
+/// starting in the drawn regions.  Returns `None` if no records could be sampled, e.g., for sparse
+/// input or if all contigs are shorter than `config.lib_estimation_region_length`.
+fn sample_random_regions(
+    mut reader: bam::IndexedReader,
+    config: &Config,
+) -> Result<Option<ReadGroupProperties>, Error> {
+    let header = reader.header().clone();
+    let mut sampler = Sampler::new(
+        config.lib_estimation_sample_size,
+        read_groups_from_header(header.as_bytes()),
+    );
+
+    // Collect contigs to draw from, weighted by their length.
+    let contigs: Vec<(u32, u64)> = (0..header.target_count())
+        .map(|tid| (tid, header.target_len(tid).unwrap_or(0)))
+        .filter(|(_, len)| *len > config.lib_estimation_region_length)
+        .collect();
+    let autosomes: Vec<(u32, u64)> = contigs
+        .iter()
+        .cloned()
+        .filter(|(tid, _)| is_autosome(&String::from_utf8_lossy(header.tid2name(*tid))))
+        .collect();
+    let contigs = if autosomes.is_empty() {
+        contigs
+    } else {
+        autosomes
+    };
+    let total_len: u64 = contigs.iter().map(|(_, len)| *len).sum();
+    if contigs.is_empty() {
+        return Ok(None);
+    }
+
+    let blocked = if let Some(blocked_regions_bed) = &config.blocked_regions_bed {
+        let contig_map: HashMap<String, i32> = header
+            .target_names()
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| (String::from_utf8_lossy(s).to_string(), i as i32))
+            .collect();
+        Some(bed_to_annot_map(blocked_regions_bed, &contig_map)?)
+    } else {
+        None
+    };
+
+    // Limit records per read group and region so the sample is spread over the genome.
+    let per_region = std::cmp::max(
+        1,
+        (config.lib_estimation_sample_size + config.lib_estimation_random_regions - 1)
+            / config.lib_estimation_random_regions,
+    );
+
+    let mut rng = StdRng::seed_from_u64(config.lib_estimation_seed);
+    let mut drawn = 0;
+    let mut covered_bases = 0;
+    // Give up eventually if most of the genome is blocked.
+    for _ in 0..(10 * config.lib_estimation_random_regions) {
+        if drawn >= config.lib_estimation_random_regions || sampler.is_done() {
+            break;
+        }
+
+        // Draw contig (weighted by length) and start position.
+        let mut offset = rng.gen_range(0, total_len);
+        let mut tid_len = contigs[0];
+        for (tid, len) in &contigs {
+            if offset < *len {
+                tid_len = (*tid, *len);
+                break;
+            }
+            offset -= len;
+        }
+        let (tid, len) = tid_len;
+        let start = rng.gen_range(0, len - config.lib_estimation_region_length);
+        let end = start + config.lib_estimation_region_length;
+
+        if let Some(blocked) = &blocked {
+            let location = Contig::new(
+                tid as i32,
+                start as isize,
+                config.lib_estimation_region_length as usize,
+                NoStrand::Unknown,
+            );
+            if blocked.find(&location).next().is_some() {
+                continue;
+            }
+        }
+        drawn += 1;
+
+        reader.fetch(tid, start, end)?;
+        let mut taken: HashMap<String, usize> = HashMap::new();
+        for r in reader.records() {
+            let record = r?;
+            // Only consider records starting in the region to not count any twice.
+            if (record.pos() as u64) < start {
+                continue;
+            }
+            covered_bases += coverage_bases(&record);
+            if !is_sampled(&record) || record.mapq() < config.lib_estimation_min_mapq {
+                continue;
+            }
+            let count = taken
+                .entry(read_group_of(&record).unwrap_or("").to_string())
+                .or_insert(0);
+            if *count < per_region && sampler.sampled(&record) < config.lib_estimation_sample_size {
+                *count += 1;
+                sampler.push(&record);
+            }
+        }
+    }
+    info!("sampled records from {} random regions", drawn);
+    if sampler.all_samples.is_empty() {
+        return Ok(None);
+    }
+
+    let coverage = if drawn > 0 {
+        Some(covered_bases as f64 / (drawn as u64 * config.lib_estimation_region_length) as f64)
+    } else {
+        None
     };
+    sampler.finish(config, coverage).map(Some)
+}
+
+/// Return IDs of the @RG lines in the given BAM header text.
+pub fn read_groups_from_header(header: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(header)
+        .lines()
+        .filter(|line| line.starts_with("@RG"))
+        .filter_map(|line| {
+            line.split('\t')
+                .find(|token| token.starts_with("ID:"))
+                .map(|token| token[3..].to_string())
+        })
+        .collect()
+}
 
-    info!("library properties: {:?}", &result);
+/// Write library QC report to `<prefix>.library.json` and `<prefix>.library.tsv`.
+///
+/// The TSV file has one row per histogram bin with the columns `read_group`, `metric` (one of
+/// `insert_size` and `read_length`), `value`, and `count`.  The properties over all read groups
+/// use `*` as the read group.
+pub fn write_qc_report(prefix: &str, lib_properties: &ReadGroupProperties) -> Result<(), Error> {
+    let mut read_groups: Vec<(&str, &LibraryProperties)> = lib_properties
+        .by_read_group
+        .iter()
+        .map(|(rg, p)| (rg.as_str(), p))
+        .collect();
+    read_groups.sort_by(|a, b| a.0.cmp(b.0));
 
-    Ok(result)
+    let json = serde_json::json!({
+        "read_groups": read_groups.iter().cloned().collect::<BTreeMap<_, _>>(),
+        "all": &lib_properties.fallback,
+        "coverage": lib_properties.coverage,
+    });
+    let mut file = File::create(format!("{}.library.json", prefix))?;
+    file.write_all(serde_json::to_string_pretty(&json)?.as_bytes())?;
+    file.write_all(b"\n")?;
+
+    let mut file = File::create(format!("{}.library.tsv", prefix))?;
+    file.write_all(b"read_group\tmetric\tvalue\tcount\n")?;
+    read_groups.push(("*", &lib_properties.fallback));
+    for (read_group, p) in read_groups {
+        for (metric, histogram) in &[
+            ("insert_size", &p.isize_histogram),
+            ("read_length", &p.rlen_histogram),
+        ] {
+            for (value, count) in histogram.iter() {
+                writeln!(file, "{}\t{}\t{}\t{}", read_group, metric, value, count)?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
 /// Return if split read clipped on left side.
@@ -127,33 +713,470 @@ pub fn is_split_read_right(
                 || cigar.trailing_softclips() >= config.min_clipped_bases))
 }
 
-/// Return if pair is discordant.
-pub fn is_discordant_pair(record: &bam::Record, lib_properties: &LibraryProperties) -> bool {
-    record.is_paired() && (record.tid() >= 0 && record.mtid() >= 0 && record.tid() != record.mtid())
-        || record.insert_size().abs() > lib_properties.max_normal_isize
-        || record.is_reverse() == record.is_mate_reverse()
+/// Return if pair is discordant, based on the library properties of the record's read group.
+pub fn is_discordant_pair(record: &bam::Record, lib_properties: &ReadGroupProperties) -> bool {
+    let max_normal_isize = lib_properties.for_record(record).max_normal_isize;
+    record.is_paired()
+        && !record.is_mate_unmapped()
+        && ((record.tid() >= 0 && record.mtid() >= 0 && record.tid() != record.mtid())
+            || record.insert_size().abs() > max_normal_isize
+            || record.is_reverse() == record.is_mate_reverse())
 }
 
-/// Determine whether the record shows PE or SR signal.
-pub fn is_interesting(
+/// Name of the aux tag with the reasons for extracting a record, as written by `bam-scan`.
+pub const REASON_TAG: &[u8] = b"xR";
+
+/// Reason for a record showing PE or SR signal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reason {
+    /// Clipped on the left side.
+    LeftClip,
+    /// Clipped on the right side.
+    RightClip,
+    /// Hard-clipped by at least `min_clipped_bases`.
+    HardClip,
+    /// Soft-clipped by at least `min_clipped_bases`.
+    SoftClip,
+    /// Mate aligns to a different chromosome.
+    InterChromosomal,
+    /// Insert size larger than the maximal normal one.
+    LargeTlen,
+    /// Read and mate have the same orientation.
+    WrongOrientation,
+    /// Exactly one read of the pair is mapped.
+    OneEndAnchored,
+    /// Not showing signal itself but the mate does.
+    MateOfInteresting,
+}
+
+impl Reason {
+    /// All reasons, in the order of their codes in the tag.
+    pub const ALL: [Reason; 9] = [
+        Reason::LeftClip,
+        Reason::RightClip,
+        Reason::HardClip,
+        Reason::SoftClip,
+        Reason::InterChromosomal,
+        Reason::LargeTlen,
+        Reason::WrongOrientation,
+        Reason::OneEndAnchored,
+        Reason::MateOfInteresting,
+    ];
+
+    /// Character code of the reason in the tag.
+    pub fn code(self) -> char {
+        match self {
+            Reason::LeftClip => 'L',
+            Reason::RightClip => 'R',
+            Reason::HardClip => 'H',
+            Reason::SoftClip => 'S',
+            Reason::InterChromosomal => 'C',
+            Reason::LargeTlen => 'T',
+            Reason::WrongOrientation => 'O',
+            Reason::OneEndAnchored => 'A',
+            Reason::MateOfInteresting => 'M',
+        }
+    }
+
+    /// Name of the reason in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Reason::LeftClip => "left_clip",
+            Reason::RightClip => "right_clip",
+            Reason::HardClip => "hard_clip",
+            Reason::SoftClip => "soft_clip",
+            Reason::InterChromosomal => "inter_chromosomal",
+            Reason::LargeTlen => "large_tlen",
+            Reason::WrongOrientation => "wrong_orientation",
+            Reason::OneEndAnchored => "one_end_anchored",
+            Reason::MateOfInteresting => "mate_of_interesting",
+        }
+    }
+}
+
+/// Encode reasons as value of the `xR` tag.
+pub fn reasons_to_tag(reasons: &[Reason]) -> String {
+    Reason::ALL
+        .iter()
+        .filter(|reason| reasons.contains(reason))
+        .map(|reason| reason.code())
+        .collect()
+}
+
+/// Decode reasons from the value of the `xR` tag, ignoring unknown codes.
+pub fn reasons_from_tag(tag: &[u8]) -> Vec<Reason> {
+    Reason::ALL
+        .iter()
+        .filter(|reason| tag.contains(&(reason.code() as u8)))
+        .copied()
+        .collect()
+}
+
+/// Return the reasons from the `xR` tag of the record, if any.
+pub fn reasons_of(record: &bam::Record) -> Option<Vec<Reason>> {
+    match record.aux(REASON_TAG) {
+        Some(bam::record::Aux::String(tag)) => Some(reasons_from_tag(tag)),
+        _ => None,
+    }
+}
+
+/// Determine the reasons for the record showing PE or SR signal, empty if it does not.
+pub fn interesting_reasons(
     record: &bam::Record,
-    lib_properties: &LibraryProperties,
+    lib_properties: &ReadGroupProperties,
     config: &Config,
-) -> bool {
+) -> Vec<Reason> {
     // We need to extract the CIGAR information for split read analysis.
     let cigar = &record.cigar_cached().unwrap();
 
     // Early exit if not interesting at all.
     if record.is_quality_check_failed()
         || record.is_duplicate()
-        || record.is_unmapped()
-        || record.is_mate_unmapped()
         || (!config.supplementary_masked_as_secondary && record.is_secondary())
     {
-        return false;
+        return vec![];
+    }
+
+    // One-end-anchored pairs are only interesting if enabled, both the anchor and the unmapped
+    // mate are kept.
+    let mut result = Vec::new();
+    if record.is_unmapped() || record.is_mate_unmapped() {
+        if !config.extract_one_end_anchored
+            || !record.is_paired()
+            || (record.is_unmapped() && record.is_mate_unmapped())
+        {
+            return vec![];
+        }
+        result.push(Reason::OneEndAnchored);
+        if record.is_unmapped() {
+            return result;
+        }
+    }
+
+    let is_primary = !record.is_supplementary() && !record.is_secondary();
+    let left = is_split_read_left(&record, &cigar, &config);
+    let right = is_split_read_right(&record, &cigar, &config);
+    if left {
+        result.push(Reason::LeftClip);
+    }
+    if right {
+        result.push(Reason::RightClip);
+    }
+    if (left && cigar.leading_hardclips() >= config.min_clipped_bases)
+        || (right && cigar.trailing_hardclips() >= config.min_clipped_bases)
+    {
+        result.push(Reason::HardClip);
+    }
+    if is_primary
+        && ((left && cigar.leading_softclips() >= config.min_clipped_bases)
+            || (right && cigar.trailing_softclips() >= config.min_clipped_bases))
+    {
+        result.push(Reason::SoftClip);
+    }
+
+    // The discordance checks do not apply to single reads or pairs with an unmapped read.
+    if !record.is_paired() || record.is_mate_unmapped() {
+        return result;
+    }
+
+    let max_normal_isize = lib_properties.for_record(record).max_normal_isize;
+    if record.tid() >= 0 && record.mtid() >= 0 && record.tid() != record.mtid() {
+        result.push(Reason::InterChromosomal);
     }
+    if record.insert_size().abs() > max_normal_isize {
+        result.push(Reason::LargeTlen);
+    }
+    if record.is_reverse() == record.is_mate_reverse() {
+        result.push(Reason::WrongOrientation);
+    }
+
+    result
+}
+
+/// Determine whether the record shows PE or SR signal.
+pub fn is_interesting(
+    record: &bam::Record,
+    lib_properties: &ReadGroupProperties,
+    config: &Config,
+) -> bool {
+    !interesting_reasons(record, lib_properties, config).is_empty()
+}
+
+/// Library properties of the read groups `rg1` and `rg2`, with `rg1` as the fallback, for tests.
+///
+/// The values need all digits to be written to the header exactly.
+#[cfg(any(test, feature = "test-fixtures"))]
+pub fn example_read_group_properties() -> ReadGroupProperties {
+    let rg1 = LibraryProperties {
+        max_rlen: 151,
+        median_isize: 412.5,
+        std_dev_isize: 37.123_456_789,
+        max_normal_isize: 599,
+        ..Default::default()
+    };
+    let rg2 = LibraryProperties {
+        max_rlen: 101,
+        median_isize: 301.0,
+        std_dev_isize: 0.1 + 0.2,
+        max_normal_isize: 333,
+        ..Default::default()
+    };
+    let mut result = ReadGroupProperties::uniform(rg1.clone());
+    result.by_read_group.insert("rg1".to_string(), rg1);
+    result.by_read_group.insert("rg2".to_string(), rg2);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn lib_properties(max_normal_isize: i64) -> LibraryProperties {
+        LibraryProperties {
+            max_rlen: 100,
+            median_isize: 300.0,
+            std_dev_isize: 10.0,
+            max_normal_isize,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_autosome() {
+        assert!(is_autosome("1"));
+        assert!(is_autosome("chr22"));
+        assert!(!is_autosome("X"));
+        assert!(!is_autosome("chrM"));
+        assert!(!is_autosome("chr"));
+        assert!(!is_autosome("GL000220.1"));
+    }
+
+    #[test]
+    fn test_read_groups_from_header() {
+        let header = b"@HD\tVN:1.6\n@RG\tID:rg1\tSM:x\n@RG\tSM:x\tID:rg2\n@PG\tID:prog\n";
+        assert_eq!(
+            read_groups_from_header(header),
+            vec!["rg1".to_string(), "rg2".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_read_group_properties() {
+        let mut props = ReadGroupProperties::uniform(lib_properties(330));
+        props
+            .by_read_group
+            .insert("rg1".to_string(), lib_properties(500));
+
+        assert_eq!(props.get(Some("rg1")).max_normal_isize, 500);
+        assert_eq!(props.get(Some("rg2")).max_normal_isize, 330);
+        assert_eq!(props.get(None).max_normal_isize, 330);
+        assert_eq!(props.max_normal_isize(), 500);
+        assert_eq!(props.max_rlen(), 100);
+    }
+
+    #[test]
+    fn test_library_properties_header_round_trip() -> Result<(), Error> {
+        let props = example_read_group_properties();
+
+        let mut header = bam::Header::new();
+        header.push_record(&bam::header::HeaderRecord::new(b"HD\tVN:1.6"));
+        header.push_comment(b"unrelated comment");
+        props.write_to_header(&mut header);
+
+        assert_eq!(
+            ReadGroupProperties::from_header(&header.to_bytes())?,
+            Some(props)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_library_properties_from_header() -> Result<(), Error> {
+        assert_eq!(ReadGroupProperties::from_header(b"@HD\tVN:1.6\n")?, None);
 
-    is_split_read_left(&record, &cigar, &config)
-        || is_split_read_right(&record, &cigar, &config)
-        || is_discordant_pair(record, lib_properties)
+        // Unknown keys are ignored, the widest read group is used as fallback.
+        let props = ReadGroupProperties::from_header(
+            b"@CO\tmaelstrom-library-properties\tversion:1\tread_group:rg1\tmax_rlen:100\t\
+              median_isize:300\tstd_dev_isize:10\tmax_normal_isize:330\tnew_key:x\n",
+        )?
+        .unwrap();
+        assert_eq!(props.fallback, lib_properties(330));
+        assert_eq!(props.get(Some("rg1")), &lib_properties(330));
+
+        assert!(ReadGroupProperties::from_header(
+            b"@CO\tmaelstrom-library-properties\tversion:2\tread_group:rg1\n"
+        )
+        .is_err());
+        assert!(ReadGroupProperties::from_header(
+            b"@CO\tmaelstrom-library-properties\tversion:1\tread_group:rg1\n"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reasons_tag() {
+        let reasons = vec![
+            Reason::SoftClip,
+            Reason::LeftClip,
+            Reason::MateOfInteresting,
+        ];
+        assert_eq!(reasons_to_tag(&reasons), "LSM");
+        assert_eq!(
+            reasons_from_tag(b"LSM"),
+            vec![
+                Reason::LeftClip,
+                Reason::SoftClip,
+                Reason::MateOfInteresting
+            ]
+        );
+        assert_eq!(reasons_from_tag(b"TX"), vec![Reason::LargeTlen]);
+        assert_eq!(
+            reasons_from_tag(b"AM"),
+            vec![Reason::OneEndAnchored, Reason::MateOfInteresting]
+        );
+        assert_eq!(reasons_to_tag(&[]), "");
+    }
+
+    /// Single read with 100 bases mapped at 100 and the given insert size.
+    fn single_read(insert_size: i64) -> bam::Record {
+        let mut record = bam::Record::new();
+        record.set(
+            b"single",
+            Some(&bam::record::CigarString(vec![bam::record::Cigar::Match(
+                100,
+            )])),
+            &[b'A'; 100],
+            &[30; 100],
+        );
+        record.set_tid(0);
+        record.set_pos(100);
+        record.set_mtid(-1);
+        record.set_mpos(-1);
+        record.set_insert_size(insert_size);
+        record.cache_cigar();
+        record
+    }
+
+    #[test]
+    fn test_interesting_reasons_unpaired() -> Result<(), Error> {
+        let config: Config = toml::from_str("")?;
+        let props = ReadGroupProperties::uniform(lib_properties(330));
+        let mut record = single_read(1000);
+
+        // Neither the orientation nor the insert size of a single read are discordant.
+        assert_eq!(interesting_reasons(&record, &props, &config), vec![]);
+
+        record.set_flags(0x1 | 0x40);
+        record.set_mtid(0);
+        record.set_mpos(1000);
+        assert_eq!(
+            interesting_reasons(&record, &props, &config),
+            vec![Reason::LargeTlen, Reason::WrongOrientation]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_discordant_pair() {
+        let props = ReadGroupProperties::uniform(lib_properties(330));
+        let mut record = single_read(1000);
+        assert!(!is_discordant_pair(&record, &props));
+
+        // Forward-reverse pair with normal insert size.
+        record.set_flags(0x1 | 0x20 | 0x40);
+        record.set_mtid(0);
+        record.set_mpos(300);
+        record.set_insert_size(300);
+        assert!(!is_discordant_pair(&record, &props));
+
+        record.set_insert_size(1000);
+        assert!(is_discordant_pair(&record, &props));
+        record.set_flags(0x1 | 0x8 | 0x20 | 0x40);
+        assert!(!is_discordant_pair(&record, &props));
+
+        record.set_flags(0x1 | 0x40);
+        record.set_insert_size(300);
+        assert!(is_discordant_pair(&record, &props));
+    }
+
+    #[test]
+    fn test_sample_random_regions() -> Result<(), Error> {
+        let config: Config = toml::from_str(
+            "lib_estimation_sample_size = 20\n\
+             lib_estimation_random_regions = 10\n\
+             lib_estimation_region_length = 1000",
+        )?;
+        let reader = bam::IndexedReader::from_path("./src/tests/data/ex-library.bam")?;
+        let result = sample_random_regions(reader, &config)?.unwrap();
+        assert_eq!(result.fallback.median_isize, 300.0);
+        assert!(result.coverage.is_some());
+
+        // Only autosomes are drawn from, there are no records on them here.
+        let reader = bam::IndexedReader::from_path("./src/tests/data/ex-library-x.bam")?;
+        assert!(sample_random_regions(reader, &config)?.is_none());
+
+        // All contigs are shorter than the regions.
+        let config: Config = toml::from_str("lib_estimation_region_length = 20000")?;
+        let reader = bam::IndexedReader::from_path("./src/tests/data/ex-library.bam")?;
+        assert!(sample_random_regions(reader, &config)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_library_insert_size_falls_back_to_start() -> Result<(), Error> {
+        let config: Config = toml::from_str("lib_estimation_region_length = 1000")?;
+        let result = estimate_library_insert_size("./src/tests/data/ex-library-x.bam", &config)?;
+        assert_eq!(result.fallback.median_isize, 300.0);
+        assert!(result.coverage.unwrap() > 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_library_insert_size_from_start_ignores_mapq() -> Result<(), Error> {
+        let config: Config = toml::from_str(
+            "lib_estimation_random_regions = 0\n\
+             lib_estimation_min_mapq = 255",
+        )?;
+        let result = estimate_library_insert_size("./src/tests/data/ex-library.bam", &config)?;
+        assert_eq!(result.fallback.median_isize, 300.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_qc_report() -> Result<(), Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let prefix = tmp_dir.path().join("out.bam");
+        let prefix = prefix.to_str().unwrap();
+
+        let mut all = lib_properties(330);
+        all.isize_histogram.insert(300, 2);
+        all.rlen_histogram.insert(100, 4);
+        let mut rg1 = lib_properties(330);
+        rg1.isize_histogram.insert(300, 1);
+        rg1.rlen_histogram.insert(100, 2);
+        let mut props = ReadGroupProperties::uniform(all);
+        props.by_read_group.insert("rg1".to_string(), rg1);
+
+        write_qc_report(prefix, &props)?;
+
+        assert_eq!(
+            fs::read_to_string(format!("{}.library.tsv", prefix))?,
+            "read_group\tmetric\tvalue\tcount\n\
+             rg1\tinsert_size\t300\t1\n\
+             rg1\tread_length\t100\t2\n\
+             *\tinsert_size\t300\t2\n\
+             *\tread_length\t100\t4\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(format!("{}.library.json", prefix))?)?;
+        assert_eq!(json["all"]["max_normal_isize"], 330);
+        assert_eq!(json["read_groups"]["rg1"]["isize_histogram"]["300"], 1);
+        assert_eq!(json["coverage"], serde_json::Value::Null);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,466 @@
+/// Stable read identifiers derived from query names.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use rust_htslib::bam;
+use tempfile::{tempdir, TempDir};
+
+use super::super::error::Error;
+
+/// Tag with the read ID.
+///
+/// The ID is written as a decimal string as integer tags are limited to 32 bits.
+pub const READ_ID_TAG: &[u8] = b"xI";
+
+/// Hash of the read name, the same for all records of a template.
+///
+/// This is FNV-1a followed by the MurmurHash3 finalizer, such that the result does not depend on
+/// the Rust version.
+pub fn qname_hash(qname: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in qname {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// Read ID for the given query name, the upper 63 bits of its hash.
+///
+/// The ID does not depend on the input order, so evidence from several shards can be merged.
+pub fn read_id_of(qname: &[u8]) -> i64 {
+    (qname_hash(qname) >> 1) as i64
+}
+
+/// Sorted run of read IDs and names, a spilled run or a sidecar.
+struct SidecarRun {
+    /// Reader for the remaining entries.
+    reader: BufReader<File>,
+    /// The next entry of the run, if any.
+    next: Option<(i64, Vec<u8>)>,
+}
+
+impl SidecarRun {
+    /// Open the run at `path` and read its first entry, skipping header lines.
+    fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut result = SidecarRun {
+            reader: BufReader::new(File::open(path)?),
+            next: None,
+        };
+        result.advance()?;
+        Ok(result)
+    }
+
+    /// Read the next entry into `self.next`.
+    fn advance(&mut self) -> Result<(), Error> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.next = None;
+                return Ok(());
+            }
+            if !line.starts_with(b"#") {
+                break;
+            }
+        }
+        let line = if line.ends_with(b"\n") {
+            &line[..line.len() - 1]
+        } else {
+            &line[..]
+        };
+        let tab = line.iter().position(|c| *c == b'\t').ok_or_else(|| {
+            Error::InvalidReadIdSidecar(String::from_utf8_lossy(line).to_string())
+        })?;
+        self.next = Some((
+            std::str::from_utf8(&line[..tab])?.parse()?,
+            line[(tab + 1)..].to_vec(),
+        ));
+        Ok(())
+    }
+}
+
+/// Write the sidecar header naming `path_source` to `writer`.
+fn write_sidecar_header<W: Write>(writer: &mut W, path_source: &str) -> Result<(), Error> {
+    writer.write_all(format!("#source\t{}\n#read_id\tqname\n", path_source).as_bytes())?;
+    Ok(())
+}
+
+/// Write one sidecar entry to `writer`.
+fn write_sidecar_entry<W: Write>(writer: &mut W, read_id: i64, qname: &[u8]) -> Result<(), Error> {
+    writer.write_all(format!("{}\t", read_id).as_bytes())?;
+    writer.write_all(qname)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Warn about two different names with the same read ID.
+fn warn_collision(read_id: i64, name: &[u8], other: &[u8]) {
+    warn!(
+        "{} and {} both have read ID {}, keeping both in the sidecar",
+        String::from_utf8_lossy(name),
+        String::from_utf8_lossy(other),
+        read_id
+    );
+}
+
+/// Merge the runs sorted by read ID and name at `paths` into a sidecar at `path`.
+///
+/// Entries with the same read ID end up next to each other, so repeated names are written once
+/// and different names are all written, with a warning.
+fn merge_runs<P: AsRef<Path>>(paths: &[P], path: &str, path_source: &str) -> Result<(), Error> {
+    let mut runs = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for (run_no, path) in paths.iter().enumerate() {
+        let mut run = SidecarRun::open(path)?;
+        if let Some((read_id, qname)) = run.next.take() {
+            heap.push(Reverse((read_id, qname, run_no)));
+        }
+        runs.push(run);
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_sidecar_header(&mut writer, path_source)?;
+    let mut last: Option<(i64, Vec<u8>)> = None;
+    while let Some(Reverse((read_id, qname, run_no))) = heap.pop() {
+        match &last {
+            Some((last_id, last_name)) if *last_id == read_id && *last_name == qname => (),
+            _ => {
+                if let Some((last_id, last_name)) = &last {
+                    if *last_id == read_id {
+                        warn_collision(read_id, last_name, &qname);
+                    }
+                }
+                write_sidecar_entry(&mut writer, read_id, &qname)?;
+                last = Some((read_id, qname));
+            }
+        }
+        runs[run_no].advance()?;
+        if let Some((read_id, qname)) = runs[run_no].next.take() {
+            heap.push(Reverse((read_id, qname, run_no)));
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Assigns read IDs to records and keeps the names seen so far for the sidecar.
+///
+/// The names are kept by read ID in memory and spilled to sorted runs on disk when more than
+/// `max_in_memory` are kept.  Different names with the same read ID (collisions) are all kept
+/// and reported with a warning, right away when in memory, else when merging the runs.
+#[derive(Debug)]
+pub struct ReadIds {
+    /// Maximal number of names to keep in memory.
+    max_in_memory: usize,
+    /// Query names by assigned read ID, sorted, not spilled yet.
+    names: BTreeMap<i64, Vec<Vec<u8>>>,
+    /// Number of names in `names`.
+    name_count: usize,
+    /// Temporary directory for the runs, created on first spill.
+    tmp_dir: Option<TempDir>,
+    /// Paths to the sorted runs.
+    runs: Vec<PathBuf>,
+}
+
+impl ReadIds {
+    /// Create new, empty `ReadIds` that keeps at most `max_in_memory` names in memory.
+    pub fn new(max_in_memory: usize) -> Self {
+        ReadIds {
+            max_in_memory: std::cmp::max(1, max_in_memory),
+            names: BTreeMap::new(),
+            name_count: 0,
+            tmp_dir: None,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Return the read ID for the given query name.
+    pub fn of_name(&mut self, qname: &[u8]) -> Result<i64, Error> {
+        self.register(read_id_of(qname), qname)
+    }
+
+    /// Return the read ID of the record, from its `xI` tag if present, else from its name.
+    pub fn of_record(&mut self, record: &bam::Record) -> Result<i64, Error> {
+        let read_id = match record.aux(READ_ID_TAG) {
+            Some(bam::record::Aux::String(read_id)) => std::str::from_utf8(read_id)?.parse()?,
+            Some(bam::record::Aux::Integer(read_id)) => read_id,
+            _ => read_id_of(record.qname()),
+        };
+        self.register(read_id, record.qname())
+    }
+
+    /// Write the sidecar mapping the read IDs seen to their names.
+    ///
+    /// The sidecar is a TSV file sorted by read ID and name, such that `SidecarReader` can look
+    /// up names by binary search.  `path_source` is the alignment file that the names come from.
+    pub fn write_sidecar(mut self, path: &str, path_source: &str) -> Result<(), Error> {
+        if self.runs.is_empty() {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_sidecar_header(&mut writer, path_source)?;
+            self.write_names(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        } else {
+            if !self.names.is_empty() {
+                self.spill()?;
+            }
+            merge_runs(&self.runs, path, path_source)
+        }
+    }
+
+    /// Remember `qname` for `read_id`, warning if another name has the same ID.
+    fn register(&mut self, read_id: i64, qname: &[u8]) -> Result<i64, Error> {
+        let names = self.names.entry(read_id).or_insert_with(Vec::new);
+        if let Err(index) = names.binary_search_by(|name| name.as_slice().cmp(qname)) {
+            if let Some(other) = names.first() {
+                warn_collision(read_id, other, qname);
+            }
+            names.insert(index, qname.to_vec());
+            self.name_count += 1;
+            if self.name_count > self.max_in_memory {
+                self.spill()?;
+            }
+        }
+        Ok(read_id)
+    }
+
+    /// Write the names in memory sorted by read ID and name to `writer`.
+    fn write_names<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        for (read_id, names) in &self.names {
+            for qname in names {
+                write_sidecar_entry(writer, *read_id, qname)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the names in memory to a new sorted run on disk.
+    fn spill(&mut self) -> Result<(), Error> {
+        if self.tmp_dir.is_none() {
+            self.tmp_dir = Some(tempdir()?);
+        }
+        let path = self
+            .tmp_dir
+            .as_ref()
+            .unwrap()
+            .path()
+            .join(format!("run-{}.tsv", self.runs.len()));
+        {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            self.write_names(&mut writer)?;
+            writer.flush()?;
+        }
+        self.names.clear();
+        self.name_count = 0;
+        self.runs.push(path);
+        Ok(())
+    }
+}
+
+/// Path of the read ID sidecar written next to `path_output`.
+pub fn sidecar_path(path_output: &str) -> String {
+    format!("{}.read_ids.tsv", path_output)
+}
+
+/// Looks up query names in a read ID sidecar written by `ReadIds::write_sidecar`.
+pub struct SidecarReader {
+    /// Reader for the sidecar file.
+    reader: BufReader<File>,
+    /// Path to the alignment file that the names come from.
+    path_source: String,
+    /// Offset of the first line after the header.
+    data_start: u64,
+    /// Length of the file.
+    data_end: u64,
+}
+
+impl SidecarReader {
+    /// Open the sidecar at `path` and read its header.
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut path_source = None;
+        let mut data_start = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let len = reader.read_line(&mut line)?;
+            if len == 0 || !line.starts_with('#') {
+                break;
+            }
+            if line.starts_with("#source\t") {
+                path_source = Some(line["#source\t".len()..].trim_end().to_string());
+            }
+            data_start += len as u64;
+        }
+        let data_end = reader.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            reader,
+            path_source: path_source
+                .ok_or_else(|| Error::InvalidReadIdSidecar(format!("no #source in {}", path)))?,
+            data_start,
+            data_end,
+        })
+    }
+
+    /// Path to the alignment file that the names come from.
+    pub fn path_source(&self) -> &str {
+        &self.path_source
+    }
+
+    /// Return the query names of `read_id`, more than one on collisions, none if not present.
+    pub fn lookup(&mut self, read_id: i64) -> Result<Vec<Vec<u8>>, Error> {
+        // Invariant: the lines starting before `lo` have smaller IDs and the first line starting
+        // at or after `hi` does not, so the first line of `read_id`, if any, is the first line
+        // starting at or after `lo` when the search ends.
+        let (mut lo, mut hi) = (self.data_start, self.data_end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.line_from(mid)? {
+                Some((line_end, line_id, _)) if line_id < read_id => lo = line_end,
+                _ => hi = mid,
+            }
+        }
+
+        let mut result = Vec::new();
+        let mut offset = lo;
+        while let Some((line_end, line_id, qname)) = self.line_from(offset)? {
+            if line_id != read_id {
+                break;
+            }
+            result.push(qname);
+            offset = line_end;
+        }
+        Ok(result)
+    }
+
+    /// Read the first line starting at or after `offset`.
+    ///
+    /// Returns the offset after the line, its read ID, and its query name.
+    fn line_from(&mut self, offset: u64) -> Result<Option<(u64, i64, Vec<u8>)>, Error> {
+        // The header ends with a newline, so `offset - 1` is always within the file.
+        let mut buffer = Vec::new();
+        self.reader.seek(SeekFrom::Start(offset - 1))?;
+        let skipped = self.reader.read_until(b'\n', &mut buffer)?;
+        buffer.clear();
+        let len = self.reader.read_until(b'\n', &mut buffer)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        let line = if buffer.ends_with(b"\n") {
+            &buffer[..len - 1]
+        } else {
+            &buffer[..]
+        };
+        let tab = line.iter().position(|c| *c == b'\t').ok_or_else(|| {
+            Error::InvalidReadIdSidecar(String::from_utf8_lossy(line).to_string())
+        })?;
+        let line_id = std::str::from_utf8(&line[..tab])?.parse()?;
+        Ok(Some((
+            offset - 1 + (skipped + len) as u64,
+            line_id,
+            line[(tab + 1)..].to_vec(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_id_of, sidecar_path, Error, ReadIds, SidecarReader};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read_id_of() {
+        assert_eq!(read_id_of(b"pair1"), read_id_of(b"pair1"));
+        assert!(read_id_of(b"pair1") != read_id_of(b"pair2"));
+        assert!(read_id_of(b"pair1") >= 0);
+    }
+
+    /// Helper that writes a sidecar with a faked collision of "pair2" and looks up all names.
+    fn _write_collision_and_lookup(max_in_memory: usize) -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path = sidecar_path(tmp_dir.path().join("out.bam").to_str().unwrap());
+        let mut read_ids = ReadIds::new(max_in_memory);
+        let read_id = read_ids.of_name(b"pair1")?;
+        assert_eq!(read_ids.of_name(b"pair1")?, read_id);
+        read_ids.of_name(b"pair2")?;
+        read_ids.of_name(b"pair3")?;
+
+        // Fake a different name with the ID of "pair2", which is kept as well.
+        assert_eq!(
+            read_ids.register(read_id_of(b"pair2"), b"other")?,
+            read_id_of(b"pair2")
+        );
+        read_ids.of_name(b"pair2")?;
+        read_ids.write_sidecar(&path, "in.bam")?;
+
+        let mut reader = SidecarReader::from_path(&path)?;
+        assert_eq!(
+            reader.lookup(read_id_of(b"pair1"))?,
+            vec![b"pair1".to_vec()]
+        );
+        assert_eq!(
+            reader.lookup(read_id_of(b"pair2"))?,
+            vec![b"other".to_vec(), b"pair2".to_vec()]
+        );
+        assert_eq!(
+            reader.lookup(read_id_of(b"pair3"))?,
+            vec![b"pair3".to_vec()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ids_collision() -> Result<(), Error> {
+        _write_collision_and_lookup(10)
+    }
+
+    #[test]
+    fn test_read_ids_collision_spilled() -> Result<(), Error> {
+        _write_collision_and_lookup(1)
+    }
+
+    /// Helper that writes a sidecar for 100 names and looks them up.
+    fn _write_and_lookup(max_in_memory: usize) -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path = sidecar_path(tmp_dir.path().join("out.bam").to_str().unwrap());
+        let names: Vec<String> = (0..100).map(|i| format!("pair{}", i)).collect();
+        let mut read_ids = ReadIds::new(max_in_memory);
+        for name in names.iter().chain(names.iter()) {
+            read_ids.of_name(name.as_bytes())?;
+        }
+        read_ids.write_sidecar(&path, "in.bam")?;
+
+        let mut reader = SidecarReader::from_path(&path)?;
+        assert_eq!(reader.path_source(), "in.bam");
+        for name in &names {
+            assert_eq!(
+                reader.lookup(read_id_of(name.as_bytes()))?,
+                vec![name.as_bytes().to_vec()]
+            );
+        }
+        assert!(reader.lookup(read_id_of(b"missing"))?.is_empty());
+        assert!(reader.lookup(-1)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sidecar_lookup() -> Result<(), Error> {
+        _write_and_lookup(1_000)
+    }
+
+    #[test]
+    fn test_sidecar_lookup_spilled() -> Result<(), Error> {
+        _write_and_lookup(7)
+    }
+}
@@ -0,0 +1,285 @@
+/// Local re-alignment of soft-clipped sequence against the reference.
+use std::fs::File;
+
+use bio::alignment::pairwise::Aligner;
+use bio::alignment::AlignmentOperation;
+use bio::alphabets::dna::revcomp;
+use bio::io::fasta;
+use rust_htslib::bam;
+
+use lib_config::Config;
+
+use super::super::error::Error;
+
+/// Side of the read that a sequence is clipped on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClipSide {
+    Left,
+    Right,
+}
+
+/// Placement of a soft-clipped sequence on the reference near its breakpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipPlacement {
+    /// The side of the read that the placed sequence is clipped on.
+    pub side: ClipSide,
+    /// Position of the breakpoint, i.e., where the aligned part of the read ends at the clip.
+    pub breakpoint: i64,
+    /// Start position of the clipped sequence's alignment.
+    pub start: i64,
+    /// End position of the clipped sequence's alignment.
+    pub end: i64,
+    /// Whether the clipped sequence aligns reverse-complemented relative to the read.
+    pub is_reverse: bool,
+    /// Fraction of the clipped bases matching the reference.
+    pub identity: f64,
+}
+
+/// Score function for re-aligning soft clips.
+type ScoreFn = fn(u8, u8) -> i32;
+
+/// Score matches with 1 and mismatches with -1.
+fn score(a: u8, b: u8) -> i32 {
+    if a == b {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Re-aligns long soft clips of records against the reference around their breakpoint.
+///
+/// This finds split reads that the aligner did not write a supplementary alignment for.  Only
+/// partners on the same contig within `realign_max_distance` of the breakpoint and not
+/// overlapping with the record's alignment are found.
+pub struct ClipRealigner {
+    /// Reader for the reference sequence.
+    reader: fasta::IndexedReader<File>,
+    /// Aligner re-used for all clips.
+    aligner: Aligner<ScoreFn>,
+    /// Minimal number of soft-clipped bases to re-align.
+    min_clipped_bases: i64,
+    /// Maximal distance of the placement from the breakpoint.
+    max_distance: i64,
+    /// Minimal fraction of clipped bases that must match the reference.
+    min_identity: f64,
+}
+
+impl ClipRealigner {
+    /// Create realigner from the configuration, `None` if disabled.
+    ///
+    /// Re-alignment requires `config.path_reference_fasta` to be set.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, Error> {
+        if !config.realign_soft_clips {
+            return Ok(None);
+        }
+        let path_reference = config
+            .path_reference_fasta
+            .as_ref()
+            .ok_or(Error::ReferenceRequiredForRealignment())?;
+        Ok(Some(ClipRealigner {
+            reader: fasta::IndexedReader::from_file(path_reference)?,
+            aligner: Aligner::with_capacity(
+                config.realign_min_clipped_bases as usize,
+                2 * config.realign_max_distance as usize,
+                -5,
+                -1,
+                score as ScoreFn,
+            ),
+            min_clipped_bases: config.realign_min_clipped_bases,
+            max_distance: config.realign_max_distance,
+            min_identity: config.realign_min_identity,
+        }))
+    }
+
+    /// Place the long soft clips of `record` on `contig` (of length `contig_len`).
+    ///
+    /// Returns the placement with the highest identity if it reaches the minimal identity.
+    pub fn realign(
+        &mut self,
+        record: &bam::Record,
+        contig: &str,
+        contig_len: u64,
+    ) -> Result<Option<ClipPlacement>, Error> {
+        if record.is_unmapped() || record.is_secondary() || record.is_supplementary() {
+            return Ok(None);
+        }
+
+        let cigar = record.cigar();
+        let seq = record.seq().as_bytes();
+        let leading = cigar.leading_softclips();
+        let trailing = cigar.trailing_softclips();
+
+        let mut best: Option<ClipPlacement> = None;
+        for (side, clipped, breakpoint) in &[
+            (ClipSide::Left, leading, record.pos()),
+            (ClipSide::Right, trailing, cigar.end_pos()),
+        ] {
+            if *clipped < self.min_clipped_bases {
+                continue;
+            }
+            let clip = match side {
+                ClipSide::Left => &seq[..*clipped as usize],
+                ClipSide::Right => &seq[(seq.len() - *clipped as usize)..],
+            };
+            let aligned = record.pos()..cigar.end_pos();
+            if let Some(placement) =
+                self.place(clip, *side, *breakpoint, &aligned, contig, contig_len)?
+            {
+                if best
+                    .as_ref()
+                    .map(|best| placement.identity > best.identity)
+                    .unwrap_or(true)
+                {
+                    best = Some(placement);
+                }
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Align `clip` in both orientations against the reference around `breakpoint`.
+    ///
+    /// Placements overlapping with the `aligned` part of the record are skipped.
+    fn place(
+        &mut self,
+        clip: &[u8],
+        side: ClipSide,
+        breakpoint: i64,
+        aligned: &std::ops::Range<i64>,
+        contig: &str,
+        contig_len: u64,
+    ) -> Result<Option<ClipPlacement>, Error> {
+        let window_start = std::cmp::max(0, breakpoint - self.max_distance) as u64;
+        let window_end = std::cmp::min(contig_len, (breakpoint + self.max_distance) as u64);
+        if window_end <= window_start {
+            return Ok(None);
+        }
+        let mut reference = Vec::new();
+        self.reader.fetch(contig, window_start, window_end)?;
+        self.reader.read(&mut reference)?;
+        reference.make_ascii_uppercase();
+        let clip = clip.to_ascii_uppercase();
+
+        let mut best: Option<ClipPlacement> = None;
+        for is_reverse in &[false, true] {
+            let query = if *is_reverse {
+                revcomp(&clip[..])
+            } else {
+                clip.clone()
+            };
+            // Semi-global alignment, the clip must align completely.
+            let alignment = self.aligner.semiglobal(&query, &reference);
+            let start = window_start as i64 + alignment.ystart as i64;
+            let end = window_start as i64 + alignment.yend as i64;
+            if start < aligned.end && aligned.start < end {
+                continue;
+            }
+            let matches = alignment
+                .operations
+                .iter()
+                .filter(|op| **op == AlignmentOperation::Match)
+                .count();
+            let identity = matches as f64 / clip.len() as f64;
+            if identity >= self.min_identity
+                && best
+                    .as_ref()
+                    .map(|best| identity > best.identity)
+                    .unwrap_or(true)
+            {
+                best = Some(ClipPlacement {
+                    side,
+                    breakpoint,
+                    start,
+                    end,
+                    is_reverse: *is_reverse,
+                    identity,
+                });
+            }
+        }
+
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipPlacement, ClipRealigner, ClipSide, Error};
+    use pretty_assertions::assert_eq;
+    use rust_htslib::{bam, bam::Read};
+
+    /// Helper that re-aligns the records of the example and returns the placements by name.
+    fn _realign(config_text: &str) -> Result<Vec<(String, Option<ClipPlacement>)>, Error> {
+        let config: lib_config::Config = toml::from_str(config_text).unwrap();
+        let mut realigner = ClipRealigner::from_config(&config)?.unwrap();
+        let mut reader = bam::Reader::from_path("./src/tests/data/ex-clipped.sam")?;
+        let mut result = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            result.push((
+                String::from_utf8(record.qname().to_vec())?,
+                realigner.realign(&record, "one", 2_000)?,
+            ));
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn test_realign_soft_clips() -> Result<(), Error> {
+        let placements = _realign(
+            "realign_soft_clips = true\n\
+             path_reference_fasta = \"./src/tests/data/ex-clipped.fa\"",
+        )?;
+        assert_eq!(
+            placements,
+            vec![
+                (
+                    "deletion".to_string(),
+                    Some(ClipPlacement {
+                        side: ClipSide::Right,
+                        breakpoint: 150,
+                        start: 600,
+                        end: 650,
+                        is_reverse: false,
+                        identity: 1.0,
+                    })
+                ),
+                (
+                    "inversion".to_string(),
+                    Some(ClipPlacement {
+                        side: ClipSide::Left,
+                        breakpoint: 700,
+                        start: 1200,
+                        end: 1240,
+                        is_reverse: true,
+                        identity: 1.0,
+                    })
+                ),
+                ("unplaced".to_string(), None),
+                ("short".to_string(), None),
+                // The clip of the tandem duplication overlaps with the alignment.
+                ("duplication".to_string(), None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_realign_far_partner() -> Result<(), Error> {
+        let placements = _realign(
+            "realign_soft_clips = true\n\
+             realign_max_distance = 400\n\
+             path_reference_fasta = \"./src/tests/data/ex-clipped.fa\"",
+        )?;
+        assert_eq!(placements[0].1, None);
+        assert_eq!(placements[1].1, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_realign_requires_reference() {
+        let config: lib_config::Config = toml::from_str("realign_soft_clips = true").unwrap();
+        assert!(ClipRealigner::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+/// External sorting and merging of BAM records with bounded memory.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+
+use rust_htslib::{bam, bam::Read};
+use tempfile::{tempdir, TempDir};
+
+use super::super::error::Error;
+
+/// Comparison function for BAM records.
+pub type Compare = fn(&bam::Record, &bam::Record) -> Ordering;
+
+/// Compare records by coordinate as `samtools sort` does, unmapped records without position last.
+///
+/// Ties are broken by name, flags, and CIGAR such that only copies of the same alignment compare
+/// equal.
+pub fn coordinate_order(lhs: &bam::Record, rhs: &bam::Record) -> Ordering {
+    (
+        lhs.tid() as u32,
+        lhs.pos(),
+        lhs.is_reverse(),
+        lhs.qname(),
+        lhs.flags(),
+        lhs.raw_cigar(),
+    )
+        .cmp(&(
+            rhs.tid() as u32,
+            rhs.pos(),
+            rhs.is_reverse(),
+            rhs.qname(),
+            rhs.flags(),
+            rhs.raw_cigar(),
+        ))
+}
+
+/// Compare records by query name only, such that a stable sort groups records by query name.
+pub fn queryname_order(lhs: &bam::Record, rhs: &bam::Record) -> Ordering {
+    lhs.qname().cmp(rhs.qname())
+}
+
+/// Next record of a sorted run in the k-way merge.
+struct Head {
+    record: bam::Record,
+    run: usize,
+    compare: Compare,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed as `BinaryHeap` is a max-heap, ties resolved by run for a stable merge.
+        (self.compare)(&other.record, &self.record).then(other.run.cmp(&self.run))
+    }
+}
+
+/// Iterator over the records of sorted BAM files in merged order.
+///
+/// When `dedup` is set, only the first of records comparing equal is returned.
+pub struct MergedRecords {
+    /// Readers of the sorted runs.
+    readers: Vec<bam::Reader>,
+    /// Next record of each run that is not exhausted.
+    heap: BinaryHeap<Head>,
+    /// The order of the runs.
+    compare: Compare,
+    /// Whether to drop records comparing equal.
+    dedup: bool,
+    /// The last returned record, if deduplicating.
+    last: Option<bam::Record>,
+}
+
+impl MergedRecords {
+    /// Open the sorted BAM files at `paths` for merging.
+    pub fn open<P: AsRef<Path>>(paths: &[P], compare: Compare, dedup: bool) -> Result<Self, Error> {
+        let mut readers = Vec::new();
+        let mut heap = BinaryHeap::new();
+        for (run, path) in paths.iter().enumerate() {
+            let mut reader = bam::Reader::from_path(path)?;
+            let mut record = bam::Record::new();
+            if reader.read(&mut record)? {
+                heap.push(Head {
+                    record,
+                    run,
+                    compare,
+                });
+            }
+            readers.push(reader);
+        }
+        Ok(MergedRecords {
+            readers,
+            heap,
+            compare,
+            dedup,
+            last: None,
+        })
+    }
+
+    /// Return the next record, `None` when all runs are exhausted.
+    fn next_record(&mut self) -> Result<Option<bam::Record>, Error> {
+        while let Some(mut head) = self.heap.pop() {
+            let record = std::mem::replace(&mut head.record, bam::Record::new());
+            if self.readers[head.run].read(&mut head.record)? {
+                self.heap.push(head);
+            }
+            if self.dedup {
+                if let Some(last) = &self.last {
+                    if (self.compare)(last, &record) == Ordering::Equal {
+                        continue;
+                    }
+                }
+                self.last = Some(record.clone());
+            }
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for MergedRecords {
+    type Item = Result<bam::Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Merge the sorted BAM files at `paths` into `writer` and return the number of written records.
+///
+/// When `dedup` is set, only the first of records comparing equal is written.
+pub fn merge_sorted<P: AsRef<Path>>(
+    paths: &[P],
+    writer: &mut bam::Writer,
+    compare: Compare,
+    dedup: bool,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    for record in MergedRecords::open(paths, compare, dedup)? {
+        writer.write(&record?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Iterator over the records of an `ExternalSorter` in order.
+pub struct SortedRecords {
+    /// The records if all are in memory.
+    buffer: std::vec::IntoIter<bam::Record>,
+    /// The merged runs if records were spilled.
+    merged: Option<MergedRecords>,
+    /// Temporary directory with the runs, removed when dropped.
+    _tmp_dir: Option<TempDir>,
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<bam::Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.merged {
+            Some(merged) => merged.next(),
+            None => self.buffer.next().map(Ok),
+        }
+    }
+}
+
+/// Sorting of BAM records that spills sorted runs to disk when holding more than
+/// `max_in_memory` records.
+///
+/// The sort is stable, records comparing equal keep the order that they were pushed in.
+pub struct ExternalSorter {
+    /// Header for writing the runs.
+    header: bam::Header,
+    /// The order to sort by.
+    compare: Compare,
+    /// Whether to drop records comparing equal.
+    dedup: bool,
+    /// Maximal number of records to keep in memory.
+    max_in_memory: usize,
+    /// Records not spilled yet.
+    buffer: Vec<bam::Record>,
+    /// Temporary directory for the runs, created on first spill.
+    tmp_dir: Option<TempDir>,
+    /// Paths to the sorted runs.
+    runs: Vec<PathBuf>,
+}
+
+impl ExternalSorter {
+    /// Create new sorter for records with the given `header`.
+    pub fn new(header: &bam::Header, compare: Compare, dedup: bool, max_in_memory: usize) -> Self {
+        ExternalSorter {
+            header: header.clone(),
+            compare,
+            dedup,
+            max_in_memory: std::cmp::max(1, max_in_memory),
+            buffer: Vec::new(),
+            tmp_dir: None,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Add `record` to the sorter.
+    pub fn push(&mut self, record: bam::Record) -> Result<(), Error> {
+        self.buffer.push(record);
+        if self.buffer.len() >= self.max_in_memory {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Sort the records in memory, dropping duplicates if configured.
+    fn sort_buffer(&mut self) {
+        let compare = self.compare;
+        self.buffer.sort_by(|lhs, rhs| compare(lhs, rhs));
+        if self.dedup {
+            self.buffer
+                .dedup_by(|rhs, lhs| compare(lhs, rhs) == Ordering::Equal);
+        }
+    }
+
+    /// Write the records in memory to a new sorted run on disk.
+    fn spill(&mut self) -> Result<(), Error> {
+        if self.tmp_dir.is_none() {
+            self.tmp_dir = Some(tempdir()?);
+        }
+        let path = self
+            .tmp_dir
+            .as_ref()
+            .unwrap()
+            .path()
+            .join(format!("run-{}.bam", self.runs.len()));
+
+        self.sort_buffer();
+        {
+            let mut writer = bam::Writer::from_path(&path, &self.header, bam::Format::BAM)?;
+            for record in &self.buffer {
+                writer.write(record)?;
+            }
+        }
+        self.buffer.clear();
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Return iterator over all records in order, merging the sorted runs on disk if spilled.
+    pub fn into_records(mut self) -> Result<SortedRecords, Error> {
+        if self.runs.is_empty() {
+            self.sort_buffer();
+            Ok(SortedRecords {
+                buffer: std::mem::take(&mut self.buffer).into_iter(),
+                merged: None,
+                _tmp_dir: None,
+            })
+        } else {
+            if !self.buffer.is_empty() {
+                self.spill()?;
+            }
+            Ok(SortedRecords {
+                buffer: Vec::new().into_iter(),
+                merged: Some(MergedRecords::open(&self.runs, self.compare, self.dedup)?),
+                _tmp_dir: self.tmp_dir.take(),
+            })
+        }
+    }
+
+    /// Write all records in order to `writer` and return the number of written records.
+    pub fn finish(self, writer: &mut bam::Writer) -> Result<usize, Error> {
+        let mut count = 0;
+        for record in self.into_records()? {
+            writer.write(&record?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coordinate_order, queryname_order, Error, ExternalSorter};
+    use pretty_assertions::assert_eq;
+    use rust_htslib::{bam, bam::Read};
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that sorts the records of `path_input` and compares the result.
+    fn _sort_and_test(
+        path_input: &str,
+        path_expected: &str,
+        max_in_memory: usize,
+    ) -> Result<(), Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_output = tmp_dir.path().join("out.sam");
+
+        let mut reader = bam::Reader::from_path(path_input)?;
+        let header = bam::Header::from_template(reader.header());
+        let mut sorter = ExternalSorter::new(&header, coordinate_order, true, max_in_memory);
+        for record in reader.records() {
+            sorter.push(record?)?;
+        }
+        {
+            let mut writer = bam::Writer::from_path(&path_output, &header, bam::Format::SAM)?;
+            assert_eq!(sorter.finish(&mut writer)?, 5);
+        }
+
+        assert_eq!(
+            fs::read_to_string(path_expected)?,
+            fs::read_to_string(&path_output)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_in_memory() -> Result<(), Error> {
+        _sort_and_test(
+            "./src/tests/data/ex-unsorted.sam",
+            "./src/tests/data/ex-unsorted.expected.sam",
+            1_000,
+        )
+    }
+
+    #[test]
+    fn test_sort_spilled() -> Result<(), Error> {
+        _sort_and_test(
+            "./src/tests/data/ex-unsorted.sam",
+            "./src/tests/data/ex-unsorted.expected.sam",
+            2,
+        )
+    }
+
+    #[test]
+    fn test_sorted_records_spilled() -> Result<(), Error> {
+        let mut reader = bam::Reader::from_path("./src/tests/data/ex-unsorted.sam")?;
+        let header = bam::Header::from_template(reader.header());
+        let mut sorter = ExternalSorter::new(&header, queryname_order, false, 2);
+        for record in reader.records() {
+            sorter.push(record?)?;
+        }
+        let names = sorter
+            .into_records()?
+            .map(|record| Ok(String::from_utf8(record?.qname().to_vec())?))
+            .collect::<Result<Vec<_>, Error>>()?;
+        assert_eq!(names, vec!["r1", "r1", "r2", "r3", "r4", "r4", "r5"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+/// Alignments of split reads from the `SA` tag and the junctions between them.
+use std::ops::Range;
+
+use rust_htslib::bam;
+
+use super::super::error::Error;
+
+/// One alignment of a split read, either the record itself or from its `SA` tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitAlignment {
+    /// Contig of the alignment.
+    pub contig: String,
+    /// Start position of the alignment.
+    pub start: i64,
+    /// End position of the alignment.
+    pub end: i64,
+    /// Whether the read is aligned reverse-complemented.
+    pub is_reverse: bool,
+    /// Soft- or hard-clipped bases at the start of the alignment.
+    pub leading_clip: i64,
+    /// Soft- or hard-clipped bases at the end of the alignment.
+    pub trailing_clip: i64,
+    /// Length of the read including the clipped bases.
+    pub read_len: i64,
+}
+
+impl SplitAlignment {
+    /// Construct from the alignment of `record` on `contig`.
+    pub fn from_record(record: &bam::Record, contig: &str) -> Self {
+        let cigar = record.cigar();
+        let leading_hard = cigar.leading_hardclips();
+        let trailing_hard = cigar.trailing_hardclips();
+        SplitAlignment {
+            contig: contig.to_string(),
+            start: record.pos(),
+            end: cigar.end_pos(),
+            is_reverse: record.is_reverse(),
+            leading_clip: leading_hard + cigar.leading_softclips(),
+            trailing_clip: trailing_hard + cigar.trailing_softclips(),
+            read_len: record.seq_len() as i64 + leading_hard + trailing_hard,
+        }
+    }
+
+    /// Parse one `rname,pos,strand,CIGAR,mapQ,NM` entry of an `SA` tag.
+    pub fn from_sa_entry(entry: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidSATag(entry.to_string());
+        let arr: Vec<&str> = entry.split(',').collect();
+        if arr.len() != 6 {
+            return Err(invalid());
+        }
+        let is_reverse = match arr[2] {
+            "+" => false,
+            "-" => true,
+            _ => return Err(invalid()),
+        };
+
+        // Walk the CIGAR string, clips before the first aligned operation are leading.
+        let (mut ref_len, mut read_len, mut leading_clip, mut trailing_clip) = (0, 0, 0, 0);
+        let mut num: i64 = 0;
+        for c in arr[3].chars() {
+            if let Some(digit) = c.to_digit(10) {
+                num = num * 10 + digit as i64;
+                continue;
+            }
+            match c {
+                'M' | '=' | 'X' => {
+                    ref_len += num;
+                    read_len += num;
+                }
+                'D' | 'N' => ref_len += num,
+                'I' => read_len += num,
+                'S' | 'H' => {
+                    read_len += num;
+                    if ref_len == 0 {
+                        leading_clip += num;
+                    } else {
+                        trailing_clip += num;
+                    }
+                }
+                'P' => (),
+                _ => return Err(invalid()),
+            }
+            num = 0;
+        }
+
+        let start = arr[1].parse::<i64>().map_err(|_| invalid())? - 1;
+        Ok(SplitAlignment {
+            contig: arr[0].to_string(),
+            start,
+            end: start + ref_len,
+            is_reverse,
+            leading_clip,
+            trailing_clip,
+            read_len,
+        })
+    }
+
+    /// Range of the aligned bases in the orientation of the sequenced read.
+    pub fn read_range(&self) -> Range<i64> {
+        if self.is_reverse {
+            self.trailing_clip..(self.read_len - self.leading_clip)
+        } else {
+            self.leading_clip..(self.read_len - self.trailing_clip)
+        }
+    }
+
+    /// Position and clipped bases where the aligned bases start in the sequenced read.
+    fn read_start_junction(&self) -> (i64, i64) {
+        if self.is_reverse {
+            (self.end, self.trailing_clip)
+        } else {
+            (self.start, self.leading_clip)
+        }
+    }
+
+    /// Position and clipped bases where the aligned bases end in the sequenced read.
+    fn read_end_junction(&self) -> (i64, i64) {
+        if self.is_reverse {
+            (self.start, self.leading_clip)
+        } else {
+            (self.end, self.trailing_clip)
+        }
+    }
+}
+
+/// Parse the alignments in the `SA` tag of `record`, empty if there is none.
+pub fn sa_alignments(record: &bam::Record) -> Result<Vec<SplitAlignment>, Error> {
+    match record.aux(b"SA") {
+        Some(bam::record::Aux::String(value)) => std::str::from_utf8(value)?
+            .split(';')
+            .filter(|entry| !entry.is_empty())
+            .map(SplitAlignment::from_sa_entry)
+            .collect(),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Junction between two alignments of a split read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Junction {
+    /// Position of the breakpoint on the first alignment.
+    pub breakpoint: i64,
+    /// Bases clipped at the breakpoint on the first alignment.
+    pub clipped_bases: i64,
+    /// Position of the breakpoint on the partner alignment.
+    pub partner_breakpoint: i64,
+    /// Bases clipped at the breakpoint on the partner alignment.
+    pub partner_clipped_bases: i64,
+}
+
+/// Return the junction of `alignment` with `partner`.
+///
+/// The alignment coming first in the sequenced read is joined at its end to the start of the
+/// other one.
+pub fn junction(alignment: &SplitAlignment, partner: &SplitAlignment) -> Junction {
+    let ((breakpoint, clipped_bases), (partner_breakpoint, partner_clipped_bases)) =
+        if alignment.read_range().start <= partner.read_range().start {
+            (alignment.read_end_junction(), partner.read_start_junction())
+        } else {
+            (alignment.read_start_junction(), partner.read_end_junction())
+        };
+    Junction {
+        breakpoint,
+        clipped_bases,
+        partner_breakpoint,
+        partner_clipped_bases,
+    }
+}
+
+/// Return the first of `partners` joined to `alignment` at a clipped side.
+///
+/// With more than one alignment in the `SA` tag, only the one adjacent to the clip on the left
+/// (if `left`) or the right (if `right`) side is the partner of the clipped sequence.
+pub fn clipped_side_partner(
+    alignment: &SplitAlignment,
+    partners: Vec<SplitAlignment>,
+    left: bool,
+    right: bool,
+) -> Option<SplitAlignment> {
+    partners.into_iter().find(|partner| {
+        let breakpoint = junction(alignment, partner).breakpoint;
+        (left && breakpoint == alignment.start) || (right && breakpoint == alignment.end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clipped_side_partner, junction, Error, Junction, SplitAlignment};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_from_sa_entry() -> Result<(), Error> {
+        assert_eq!(
+            SplitAlignment::from_sa_entry("one,5001,+,60S35M2D5M,60,3")?,
+            SplitAlignment {
+                contig: String::from("one"),
+                start: 5000,
+                end: 5042,
+                is_reverse: false,
+                leading_clip: 60,
+                trailing_clip: 0,
+                read_len: 100,
+            }
+        );
+        assert_eq!(
+            SplitAlignment::from_sa_entry("two,3001,-,40M60H,0,0")?.read_range(),
+            60..100
+        );
+        assert!(SplitAlignment::from_sa_entry("one,5001,+,60S40M,60").is_err());
+        assert!(SplitAlignment::from_sa_entry("one,5001,*,60S40M,60,0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_junction() -> Result<(), Error> {
+        let primary = SplitAlignment::from_sa_entry("one,1001,+,60M40S,60,0")?;
+
+        // Deletion-like, the partner continues the read downstream.
+        let partner = SplitAlignment::from_sa_entry("one,5001,+,60S40M,60,0")?;
+        let expected = Junction {
+            breakpoint: 1060,
+            clipped_bases: 40,
+            partner_breakpoint: 5000,
+            partner_clipped_bases: 60,
+        };
+        assert_eq!(junction(&primary, &partner), expected);
+        assert_eq!(
+            junction(&partner, &primary),
+            Junction {
+                breakpoint: 5000,
+                clipped_bases: 60,
+                partner_breakpoint: 1060,
+                partner_clipped_bases: 40,
+            }
+        );
+
+        // Inversion-like, the partner is aligned reverse-complemented.
+        let partner = SplitAlignment::from_sa_entry("one,3001,-,40M60S,60,0")?;
+        assert_eq!(
+            junction(&primary, &partner),
+            Junction {
+                breakpoint: 1060,
+                clipped_bases: 40,
+                partner_breakpoint: 3040,
+                partner_clipped_bases: 60,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_clipped_side_partner() -> Result<(), Error> {
+        // Clipped on both sides, with one partner for each side.
+        let primary = SplitAlignment::from_sa_entry("one,1001,+,30S40M30S,60,0")?;
+        let left = SplitAlignment::from_sa_entry("one,3001,+,30M70S,60,0")?;
+        let right = SplitAlignment::from_sa_entry("one,5001,+,70S30M,60,0")?;
+        let partners = vec![left.clone(), right.clone()];
+
+        assert_eq!(
+            clipped_side_partner(&primary, partners.clone(), false, true),
+            Some(right)
+        );
+        assert_eq!(
+            clipped_side_partner(&primary, partners.clone(), true, false),
+            Some(left.clone())
+        );
+        assert_eq!(
+            clipped_side_partner(&primary, partners.clone(), true, true),
+            Some(left)
+        );
+        assert_eq!(
+            clipped_side_partner(&primary, partners[..1].to_vec(), false, true),
+            None
+        );
+        Ok(())
+    }
+}
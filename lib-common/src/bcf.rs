@@ -1,6 +1,7 @@
 use rust_htslib::{bcf, bcf::Read};
 
 use super::error::Error;
+use super::standardize::standardizers;
 
 #[derive(Debug)]
 pub struct FormatInfo {
@@ -57,6 +58,7 @@ pub fn build_vcf_header(template: &bcf::header::HeaderView) -> Result<bcf::Heade
         ("DEL", "Deletion"),
         ("DUP", "Duplication"),
         ("INV", "Inversion"),
+        ("INS", "Insertion"),
         ("CNV", "Copy number variant"),
     ];
     for (id, desc) in alts {
@@ -88,11 +90,19 @@ pub fn build_vcf_header(template: &bcf::header::HeaderView) -> Result<bcf::Heade
             .as_bytes(),
         );
     }
+    let format_line = |id: &str, number: &str, type_: &str, desc: &str| {
+        format!(
+            "##FORMAT=<ID={},Number={},Type={},Description={}>",
+            &id, &number, &type_, &desc
+        )
+    };
+    header.push_record(format_line("GT", "1", "String", "Genotype").as_bytes());
+    for standardizer in standardizers() {
+        for line in standardizer.header_lines() {
+            header.push_record(line.as_bytes());
+        }
+    }
     let formats = vec![
-        ("GT", "1", "String", "Genotype"),
-        ("cnmops", "1", "Integer", "Called by cnMOPS"),
-        ("delly", "1", "Integer", "Called by Delly"),
-        ("manta", "1", "Integer", "Called by Manta"),
         ("PR", "1", "Float", "Paired read evidence"),
         ("SR", "1", "Float", "Split read evidence"),
         ("RD", "1", "Float", "Read depth evidence"),
@@ -100,15 +110,11 @@ pub fn build_vcf_header(template: &bcf::header::HeaderView) -> Result<bcf::Heade
         ("VM", "1", "Integer", "SNV count within CNV region"),
         ("VR", "1", "Integer", "SNV count right of CNV region"),
         ("ROH", "1", "Integer", "Run of homozygosity"),
+        ("SRPOS", "1", "Integer", "Split read breakpoint near POS"),
+        ("SREND", "1", "Integer", "Split read breakpoint near END"),
     ];
     for (id, number, type_, desc) in formats {
-        header.push_record(
-            format!(
-                "##FORMAT=<ID={},Number={},Type={},Description={}>",
-                &id, &number, &type_, &desc
-            )
-            .as_bytes(),
-        );
+        header.push_record(format_line(id, number, type_, desc).as_bytes());
     }
 
     // Add samples.
@@ -128,6 +134,18 @@ pub fn collect_contigs(reader: &bcf::IndexedReader) -> Result<Vec<String>, Error
     Ok(result)
 }
 
+/// Return index of `sample` in `header`.
+///
+/// Files with a single sample are accepted regardless of the sample name, such that per-sample
+/// evidence files can be used with the names from the SV VCF file.
+pub fn sample_index(header: &bcf::header::HeaderView, sample: &str) -> Result<usize, Error> {
+    if header.sample_count() == 1 {
+        Ok(0)
+    } else {
+        Ok(*header.sample_to_id(sample.as_bytes())? as usize)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,139 @@
+/// Reading and writing of BGZF files through htslib.
+use std::ffi::CString;
+use std::io::{Read, Write};
+
+use rust_htslib::htslib;
+
+use super::error::Error;
+
+/// Convert `path` for passing to htslib.
+fn c_path(path: &str) -> Result<CString, Error> {
+    CString::new(path).map_err(|_| Error::InvalidPath())
+}
+
+/// Open the BGZF file at `path` with the given htslib `mode`.
+fn open(path: &str, mode: &[u8]) -> Result<*mut htslib::BGZF, Error> {
+    let inner = unsafe { htslib::bgzf_open(c_path(path)?.as_ptr(), mode.as_ptr() as *const _) };
+    if inner.is_null() {
+        Err(Error::Io {
+            source: std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("could not open BGZF file {}", path),
+            ),
+        })
+    } else {
+        Ok(inner)
+    }
+}
+
+/// Writer for BGZF files.
+pub struct Writer {
+    inner: *mut htslib::BGZF,
+}
+
+impl Writer {
+    /// Create the BGZF file at `path`.
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self {
+            inner: open(path, b"w\0")?,
+        })
+    }
+
+    /// Virtual offset of the next byte written, for building indices.
+    pub fn tell(&self) -> u64 {
+        unsafe {
+            ((*self.inner).block_address << 16 | ((*self.inner).block_offset & 0xffff) as i64)
+                as u64
+        }
+    }
+
+    /// Flush the last block, write the EOF marker, and close the file.
+    pub fn close(mut self) -> Result<(), Error> {
+        let ret = unsafe { htslib::bgzf_close(self.inner) };
+        self.inner = std::ptr::null_mut();
+        if ret < 0 {
+            Err(Error::Io {
+                source: std::io::Error::new(std::io::ErrorKind::Other, "problem closing BGZF file"),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        let ret =
+            unsafe { htslib::bgzf_write(self.inner, buf.as_ptr() as *const _, buf.len() as _) };
+        if ret < 0 {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "problem writing BGZF file",
+            ))
+        } else {
+            Ok(ret as usize)
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        if !self.inner.is_null() {
+            unsafe { htslib::bgzf_close(self.inner) };
+        }
+    }
+}
+
+/// Reader for BGZF files, also reads uncompressed files.
+pub struct Reader {
+    inner: *mut htslib::BGZF,
+}
+
+impl Reader {
+    /// Open the file at `path`.
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self {
+            inner: open(path, b"r\0")?,
+        })
+    }
+
+    /// Continue reading at the virtual offset `offset` as returned by `Writer::tell()`.
+    pub fn seek(&mut self, offset: u64) -> Result<(), Error> {
+        let ret = unsafe { htslib::bgzf_seek(self.inner, offset as i64, 0) };
+        if ret < 0 {
+            Err(Error::Io {
+                source: std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("could not seek to virtual offset {}", offset),
+                ),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        let ret =
+            unsafe { htslib::bgzf_read(self.inner, buf.as_mut_ptr() as *mut _, buf.len() as _) };
+        if ret < 0 {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "problem reading BGZF file",
+            ))
+        } else {
+            Ok(ret as usize)
+        }
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        unsafe { htslib::bgzf_close(self.inner) };
+    }
+}
@@ -0,0 +1,163 @@
+/// Shared command line plumbing for the `maelstrom` sub commands.
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+use bio_types::genome::Interval;
+use clap::{Arg, ArgMatches};
+use log::{debug, LevelFilter};
+
+use lib_config::Config;
+
+use super::error::Error;
+use super::parse_region;
+
+/// Return the arguments shared by all sub commands.
+///
+/// The arguments are marked as global so they can be given before or after the sub command.
+pub fn global_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::from_usage("-v... 'Increase verbosity'").global(true),
+        Arg::from_usage("--overwrite 'Allow overwriting of output file'").global(true),
+        Arg::from_usage("-c, --config=[FILE] 'Sets a custom config file'").global(true),
+        Arg::from_usage("-r, --regions=[REGIONS] 'comma-separated list of regions'").global(true),
+        Arg::from_usage(
+            "--reference=[FASTA] 'Reference FASTA file, e.g., for CRAM, overrides config'",
+        )
+        .global(true),
+        Arg::from_usage("--threads=[COUNT] 'Number of htslib I/O threads, overrides config'")
+            .global(true),
+        Arg::from_usage("--worker-threads=[COUNT] 'Number of worker threads, overrides config'")
+            .global(true),
+        Arg::from_usage("--log-format=[FORMAT] 'Log format, one of {text,json}, default text'")
+            .possible_values(&["text", "json"])
+            .global(true),
+    ]
+}
+
+/// Parse the comma-separated list of regions from `--regions`, if any.
+pub fn parse_regions(matches: &ArgMatches) -> Result<Option<Vec<Interval>>, Error> {
+    matches
+        .value_of("regions")
+        .map(|s| {
+            let x: Result<Vec<Interval>, Error> = s.split(',').map(|t| parse_region(&t)).collect();
+            x
+        })
+        .transpose()
+}
+
+/// Return required string argument `name` or `Error::OptionMissing`.
+pub fn required_value(matches: &ArgMatches, name: &str) -> Result<String, Error> {
+    match matches.value_of(name) {
+        Some(x) => Ok(String::from(x)),
+        None => Err(Error::OptionMissing()),
+    }
+}
+
+/// Output file must not exist yet unless `overwrite` is given.
+pub fn check_output_exists(path_output: &str, overwrite: bool) -> Result<(), Error> {
+    if path_output != "-"
+        && path_output != "/dev/stdout"
+        && Path::new(path_output).exists()
+        && !overwrite
+    {
+        Err(Error::OutputFileExists())
+    } else {
+        Ok(())
+    }
+}
+
+/// Setup logging verbosity and format from the global arguments.
+pub fn setup_logging(matches: &ArgMatches) {
+    let level = match matches.occurrences_of("v").cmp(&1) {
+        Ordering::Less => LevelFilter::Info,
+        Ordering::Equal => LevelFilter::Debug,
+        Ordering::Greater => LevelFilter::Trace,
+    };
+    let dispatch = match matches.value_of("log-format").unwrap_or("text") {
+        "json" => fern::Dispatch::new().format(|out, message, record| {
+            out.finish(format_args!(
+                "{}",
+                serde_json::json!({
+                    "time": chrono::Local::now().to_rfc3339(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "message": message.to_string(),
+                })
+            ))
+        }),
+        _ => fern::Dispatch::new().format(|out, message, record| {
+            out.finish(format_args!(
+                "{} [{}] {}",
+                chrono::Local::now().format("[%Y-%m-%d %H:%M:%S]"),
+                record.level(),
+                message
+            ))
+        }),
+    };
+    dispatch
+        .level(level)
+        .chain(std::io::stderr())
+        .apply()
+        .unwrap();
+}
+
+/// Load configuration from `--config` (or defaults) and apply the reference and thread count
+/// overrides.
+pub fn load_config(matches: &ArgMatches) -> Result<Config, Error> {
+    let mut config: Config = match matches.value_of("config") {
+        None => toml::from_str("")?,
+        Some(path_config) => {
+            debug!("Loading config file: {}", &path_config);
+            let contents = fs::read_to_string(&path_config)?;
+            toml::from_str(&contents)?
+        }
+    };
+    if let Some(path_reference) = matches.value_of("reference") {
+        config.path_reference_fasta = Some(path_reference.to_string());
+    }
+    if let Some(threads) = matches.value_of("threads") {
+        config.htslib_io_threads = threads.parse()?;
+    }
+    if let Some(worker_threads) = matches.value_of("worker-threads") {
+        config.worker_threads = worker_threads.parse()?;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_global_args() -> Result<(), Error> {
+        let matches = App::new("test").args(&global_args()).get_matches_from(vec![
+            "test",
+            "-r",
+            "1:1-100,2:5-10",
+            "--threads",
+            "4",
+            "--reference",
+            "ref.fa",
+        ]);
+
+        let regions = parse_regions(&matches)?.unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[1], Interval::new("2".to_string(), 5..10));
+
+        let config = load_config(&matches)?;
+        assert_eq!(config.htslib_io_threads, 4);
+        assert_eq!(config.path_reference_fasta, Some("ref.fa".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_output_exists() {
+        assert!(check_output_exists("-", false).is_ok());
+        assert!(check_output_exists("./src/lib.rs", true).is_ok());
+        assert!(check_output_exists("./src/lib.rs", false).is_err());
+    }
+}
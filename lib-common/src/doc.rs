@@ -25,8 +25,8 @@ pub struct MedianReadDepthInfo {
     pub on_autosomes: f64,
 }
 
-/// Load DoC from file and compute median.
-pub fn load_doc_median(path: &str) -> Result<MedianReadDepthInfo, Error> {
+/// Load DoC of the sample with index `sample_idx` from file and compute median.
+pub fn load_doc_median(path: &str, sample_idx: usize) -> Result<MedianReadDepthInfo, Error> {
     let autosomes: HashSet<String> = NAMES_AUTOSOMES.iter().map(|&s| s.to_string()).collect();
     let all_chroms: HashSet<String> = (&[NAMES_ALLOSOMES, NAMES_AUTOSOMES])
         .concat()
@@ -39,7 +39,7 @@ pub fn load_doc_median(path: &str) -> Result<MedianReadDepthInfo, Error> {
     let mut record = reader.empty_record();
     while reader.read(&mut record)? {
         rcvs_by_chrom[record.rid().unwrap() as usize]
-            .push(record.format(b"RCV").float()?[0][0].into());
+            .push(record.format(b"RCV").float()?[sample_idx][0].into());
     }
 
     let mut by_chrom: HashMap<String, f64> = HashMap::new();
@@ -15,6 +15,9 @@ pub enum Error {
     /// Incorrect cluster setting name.
     #[error("unknown cluster setting name")]
     UnknownClusterSettingName(),
+    /// Incorrect SV calling tool name.
+    #[error("unknown SV calling tool")]
+    UnknownTool(),
     /// Invalid region.
     #[error("invalid region")]
     InvalidRegion(),
@@ -24,9 +27,48 @@ pub enum Error {
     /// Invalid path given.
     #[error("invalid path")]
     InvalidPath(),
+    /// CRAM output without reference.
+    #[error("reference FASTA required for writing CRAM")]
+    ReferenceRequired(),
+    /// Soft-clip re-alignment without reference.
+    #[error("reference FASTA required for re-aligning soft clips")]
+    ReferenceRequiredForRealignment(),
+    /// Problem reading read ID sidecar.
+    #[error("invalid read ID sidecar")]
+    InvalidReadIdSidecar(String),
+    /// Several samples in the supporting reads file but none selected.
+    #[error("--sample required for supporting reads of several samples")]
+    SampleRequired(),
+    /// Problem reading supporting reads file.
+    #[error("invalid supporting reads file")]
+    InvalidSupportingReads(String),
+    /// Problem parsing library properties from the header.
+    #[error("invalid library properties in header")]
+    InvalidLibraryProperties(String),
+    /// Library properties written by a newer version.
+    #[error("unsupported version of library properties in header")]
+    UnsupportedLibraryPropertiesVersion(u32),
+    /// Evidence records not sorted as required for indexing.
+    #[error("evidence records are not sorted")]
+    UnsortedEvidence(String),
+    /// Problem reading binary evidence file or its index.
+    #[error("invalid binary evidence file")]
+    InvalidBinaryEvidence(String),
+    /// Building the tabix index failed.
+    #[error("problem building tabix index")]
+    TabixIndexError(String),
+    /// Problem parsing `SA` tag.
+    #[error("invalid SA tag")]
+    InvalidSATag(String),
+    /// No reads found for estimating the library properties.
+    #[error("found no reads for library estimation")]
+    NoReadsForLibraryEstimation(),
     /// Problem reading BED file.
     #[error("Invalid BED file")]
     InvalidBEDFile(String),
+    /// Problem reading sample sheet.
+    #[error("Invalid sample sheet")]
+    InvalidSampleSheet(String),
     /// Problem reading from CSV file.
     #[error("Problem reading from CSV file")]
     ProblemReadingCSV {
@@ -82,6 +124,18 @@ pub enum Error {
         #[from]
         source: std::num::ParseFloatError, // TODO: add experimental backtrace feature?
     },
+    /// Problem with parsing configuration.
+    #[error("problem with parsing configuration")]
+    ConfigError {
+        #[from]
+        source: toml::de::Error, // TODO: add experimental backtrace feature?
+    },
+    /// Problem with setting up worker threads.
+    #[error("problem with setting up worker threads")]
+    ThreadPoolError {
+        #[from]
+        source: rayon::ThreadPoolBuildError, // TODO: add experimental backtrace feature?
+    },
     /// Problem with parsing int.
     #[error("problem with parsing int")]
     ParseIntError {
@@ -1,9 +1,12 @@
 /// lib-common -- shared functionality
 pub mod bam;
 pub mod bcf;
+pub mod bgzf;
+pub mod cli;
 pub mod doc;
 pub mod error;
 pub mod read_evidence;
+pub mod standardize;
 pub mod stats;
 pub mod sv;
 use log::info;
@@ -21,17 +24,6 @@ use regex::Regex;
 
 use error::Error;
 
-/// Enumeration for calling algorithms.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum Algorithm {
-    /// Delly2
-    Delly,
-    /// Manta
-    Manta,
-    // cnMOPS
-    CNMOPS,
-}
-
 /// Parse string into region.
 pub fn parse_region(s: &str) -> Result<Interval, Error> {
     let re = Regex::new(r"([[:alnum:]]+):([[:digit:]]+)-([[:digit:]]+)").unwrap();
@@ -53,38 +45,53 @@ pub fn bed_to_annot_map<R>(
 where
     R: Hash + Eq + Clone + std::borrow::ToOwned<Owned = R>,
 {
-    info!("Loading BED file {}", &path);
+    beds_to_annot_map(&[path], contig_map)
+}
+
+/// Load BED files and return the union of their entries as AnnotMap
+pub fn beds_to_annot_map<R, P>(
+    paths: &[P],
+    contig_map: &HashMap<String, R>,
+) -> Result<AnnotMap<R, ()>, Error>
+where
+    R: Hash + Eq + Clone + std::borrow::ToOwned<Owned = R>,
+    P: AsRef<str>,
+{
     let mut result = AnnotMap::new();
 
-    let mut count = 0;
-    for line in io::BufReader::new(File::open(path)?).lines() {
-        let line = line?;
-        let arr: Vec<&str> = line.split('\t').collect();
-        if arr.len() < 3 {
-            return Err(error::Error::InvalidBEDFile(format!(
-                "Unexpected number of fields in {} (must have >= 3)",
-                &line
-            )));
-        }
+    for path in paths {
+        let path = path.as_ref();
+        info!("Loading BED file {}", &path);
+        let mut count = 0;
+        for line in io::BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let arr: Vec<&str> = line.split('\t').collect();
+            if arr.len() < 3 {
+                return Err(error::Error::InvalidBEDFile(format!(
+                    "Unexpected number of fields in {} (must have >= 3)",
+                    &line
+                )));
+            }
 
-        let start = arr[1].parse::<isize>()?;
-        let end = arr[2].parse::<isize>()?;
+            let start = arr[1].parse::<isize>()?;
+            let end = arr[2].parse::<isize>()?;
 
-        #[allow(clippy::or_fun_call)]
-        let rid = contig_map
-            .get(arr[0])
-            .ok_or(Error::InvalidBEDFile(format!("Unknown contig: {}", arr[0])))?;
+            #[allow(clippy::or_fun_call)]
+            let rid = contig_map
+                .get(arr[0])
+                .ok_or(Error::InvalidBEDFile(format!("Unknown contig: {}", arr[0])))?;
 
-        let loc = Contig::new(
-            rid.to_owned(),
-            start,
-            (end - start) as usize,
-            NoStrand::Unknown,
-        );
-        result.insert_at((), &loc);
-        count += 1;
+            let loc = Contig::new(
+                rid.to_owned(),
+                start,
+                (end - start) as usize,
+                NoStrand::Unknown,
+            );
+            result.insert_at((), &loc);
+            count += 1;
+        }
+        info!("=> {} entries", count);
     }
-    info!("=> {} entries", count);
 
     Ok(result)
 }
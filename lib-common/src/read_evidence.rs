@@ -1,12 +1,17 @@
+pub mod binary;
+
+use std::collections::HashSet;
+use std::ffi::CString;
 use std::io::prelude::*;
+use std::io::BufWriter;
 
-use rust_htslib::{tbx, tbx::Read};
+use rust_htslib::{htslib, tbx, tbx::Read};
 use serde::{Deserialize, Serialize};
 
-use super::error;
+use super::{bgzf, error};
 
 /// Strand.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Strand {
     Forward,
     Reverse,
@@ -21,6 +26,50 @@ pub enum Sides {
     Neither,
 }
 
+/// Orientation class of a read pair, the strands of the leftmost and the rightmost alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PairOrientation {
+    /// Forward/reverse as for normal pairs and deletions.
+    FR,
+    /// Reverse/forward as for tandem duplications.
+    RF,
+    /// Forward/forward as for one side of inversions.
+    FF,
+    /// Reverse/reverse as for the other side of inversions.
+    RR,
+}
+
+impl PairOrientation {
+    /// Classify from whether the leftmost and the rightmost alignment are reverse.
+    pub fn from_strands(leftmost_reverse: bool, rightmost_reverse: bool) -> Self {
+        match (leftmost_reverse, rightmost_reverse) {
+            (false, true) => PairOrientation::FR,
+            (true, false) => PairOrientation::RF,
+            (false, false) => PairOrientation::FF,
+            (true, true) => PairOrientation::RR,
+        }
+    }
+}
+
+/// Placement of the other part of a split read, from the `SA` tag or by re-alignment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitPartner {
+    /// Contig of the other part.
+    pub contig: String,
+    /// Start position of the other part.
+    pub start: i64,
+    /// End position of the other part.
+    pub end: i64,
+    /// Orientation of the other part.
+    pub strand: Strand,
+    /// Breakpoint position on the other part, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakpoint: Option<i64>,
+    /// Number of bases clipped at the breakpoint on the other part, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clipped_bases: Option<i64>,
+}
+
 /// Read pair/split read annotation from one read alignment.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Record {
@@ -46,6 +95,15 @@ pub enum Record {
         strand2: Option<Strand>,
         /// Template size.
         tlen: Option<i64>,
+        /// Mapping quality of this alignment, if known.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mapq: Option<u8>,
+        /// Mapping quality of the other alignment from the `MQ` tag, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mate_mapq: Option<u8>,
+        /// Orientation class of the pair, if the mate is mapped.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        orientation: Option<PairOrientation>,
     },
     /// Split read based evidence.
     SplitRead {
@@ -59,8 +117,43 @@ pub enum Record {
         start: i64,
         /// The alignment's end position.
         end: i64,
+        /// Read orientation in this contig.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strand: Option<Strand>,
         /// The side that the read has been clipped on.
         clipped_sides: Sides,
+        /// Breakpoint position from the `SA` tag or re-alignment, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        breakpoint: Option<i64>,
+        /// Number of bases clipped at the breakpoint, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        clipped_bases: Option<i64>,
+        /// Placement of the other part of the read, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        partner: Option<SplitPartner>,
+        /// Mapping quality of this alignment, if known.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mapq: Option<u8>,
+        /// Mapping quality of the mate from the `MQ` tag, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mate_mapq: Option<u8>,
+    },
+    /// One-end-anchored read pair evidence, the mate of this alignment is unmapped.
+    AnchoredRead {
+        /// Name of the read pair.
+        read_id: i64,
+        /// Whether this alignment is first in pair.
+        is_first: bool,
+        /// The alignment's contig.
+        contig: String,
+        /// The alignment's start position.
+        start: i64,
+        /// The alignment's end position.
+        end: i64,
+        /// Read orientation in this contig.
+        strand: Strand,
+        /// Sequence of the unmapped mate as stored in the BAM file, if found.
+        mate_sequence: Option<String>,
     },
 }
 
@@ -69,63 +162,227 @@ impl Record {
         match self {
             Self::PairedRead { start1, end1, .. } => *start1..*end1,
             Self::SplitRead { start, end, .. } => *start..*end,
+            Self::AnchoredRead { start, end, .. } => *start..*end,
+        }
+    }
+
+    pub fn contig(&self) -> &str {
+        match self {
+            Self::PairedRead { contig1, .. } => contig1,
+            Self::SplitRead { contig, .. } | Self::AnchoredRead { contig, .. } => contig,
+        }
+    }
+
+    pub fn read_id(&self) -> i64 {
+        match self {
+            Self::PairedRead { read_id, .. }
+            | Self::SplitRead { read_id, .. }
+            | Self::AnchoredRead { read_id, .. } => *read_id,
         }
     }
 }
 
-pub struct Writer {
-    file: Option<std::fs::File>,
+/// Tabix configuration for the evidence files: BED-style, `#` starts header lines.
+///
+/// `0x10000` is `TBX_UCSC`, i.e., 0-based half-open coordinates.
+const TBX_CONF_EVIDENCE: htslib::tbx_conf_t = htslib::tbx_conf_t {
+    preset: 0x10000,
+    sc: 1,
+    bc: 2,
+    ec: 3,
+    meta_char: b'#' as i32,
+    line_skip: 0,
+};
+
+/// Checks that records come sorted by start position with all records of a contig in one block,
+/// as required for building an index.
+#[derive(Debug, Default)]
+struct SortOrder {
+    /// Contig and start position of the previous record.
+    last: Option<(String, i64)>,
+    /// Contigs that have been completed.
+    done_contigs: HashSet<String>,
 }
 
-impl Writer {
-    pub fn from_path(path: &str) -> Result<Self, std::io::Error> {
-        let header = b"#contig\tstart\tend\tsignal\n";
-        if path == "-" {
-            std::io::stdout()
-                .lock()
-                .write_all(b"#contig\tstart\tend\tsignal\n")?;
-            Ok(Self { file: None })
-        } else {
-            let mut file = std::fs::File::create(path)?;
-            file.write_all(header)?;
-            Ok(Self { file: Some(file) })
+impl SortOrder {
+    /// Check that a record at `contig`:`start` may follow the previous one.
+    fn check(&mut self, contig: &str, start: i64) -> Result<(), error::Error> {
+        match &self.last {
+            Some((last_contig, last_start)) if last_contig == contig => {
+                if start < *last_start {
+                    return Err(error::Error::UnsortedEvidence(format!(
+                        "{}:{} after {}:{}",
+                        contig, start, last_contig, last_start
+                    )));
+                }
+            }
+            _ => {
+                if self.done_contigs.contains(contig) {
+                    return Err(error::Error::UnsortedEvidence(format!(
+                        "records of {} are not contiguous",
+                        contig
+                    )));
+                }
+                if let Some((last_contig, _)) = self.last.take() {
+                    self.done_contigs.insert(last_contig);
+                }
+            }
+        }
+        self.last = Some((contig.to_string(), start));
+        Ok(())
+    }
+}
+
+/// Where the TSV evidence records end up.
+enum Sink {
+    Stdout(std::io::Stdout),
+    Plain(BufWriter<std::fs::File>),
+    Bgzf(bgzf::Writer),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        match self {
+            Sink::Stdout(stdout) => stdout.write(buf),
+            Sink::Plain(file) => file.write(buf),
+            Sink::Bgzf(bgzf) => bgzf.write(buf),
         }
     }
 
-    pub fn write(&mut self, e: &Record) -> Result<(), std::io::Error> {
-        let mut writer: Box<dyn Write> = match &mut self.file {
-            Some(file) => Box::new(file),
-            None => Box::new(std::io::stdout()),
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        match self {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::Plain(file) => file.flush(),
+            Sink::Bgzf(bgzf) => bgzf.flush(),
+        }
+    }
+}
+
+/// Writes evidence records as TSV with the record as JSON in the last column.
+///
+/// Paths ending in `.gz` are written in BGZF format and get a tabix index when calling
+/// `finish()`.  As the index requires sorted input, the records then must come sorted by start
+/// position with all records of a contig in one block.
+pub struct TextWriter {
+    sink: Sink,
+    /// Path of the BGZF output, for building the index.
+    path_index: Option<String>,
+    /// Check of the sort order for the index.
+    sort_order: SortOrder,
+    /// Largest end position seen so far.
+    max_end: i64,
+}
+
+impl TextWriter {
+    pub fn from_path(path: &str) -> Result<Self, error::Error> {
+        let (sink, path_index) = if path == "-" {
+            (Sink::Stdout(std::io::stdout()), None)
+        } else if path.ends_with(".gz") {
+            (
+                Sink::Bgzf(bgzf::Writer::from_path(path)?),
+                Some(path.to_string()),
+            )
+        } else {
+            (
+                Sink::Plain(BufWriter::new(std::fs::File::create(path)?)),
+                None,
+            )
         };
 
-        match e {
-            Record::PairedRead {
-                contig1,
-                start1,
-                end1,
-                ..
-            } => {
-                writer.write_all(format!("{}\t{}\t{}\t", &contig1, start1, end1).as_bytes())?;
-            }
-            Record::SplitRead {
-                contig, start, end, ..
-            } => {
-                writer.write_all(format!("{}\t{}\t{}\t", &contig, start, end).as_bytes())?;
-            }
+        let mut result = Self {
+            sink,
+            path_index,
+            sort_order: SortOrder::default(),
+            max_end: 0,
+        };
+        result.sink.write_all(b"#contig\tstart\tend\tsignal\n")?;
+        Ok(result)
+    }
+
+    pub fn write(&mut self, e: &Record) -> Result<(), error::Error> {
+        let interval = e.interval();
+        if self.path_index.is_some() {
+            self.sort_order.check(e.contig(), interval.start)?;
         }
-        writer.write_all(serde_json::to_string(&e)?.as_bytes())?;
-        writer.write_all(b"\n")?;
+        self.max_end = std::cmp::max(self.max_end, interval.end);
+
+        self.sink.write_all(
+            format!("{}\t{}\t{}\t", e.contig(), interval.start, interval.end).as_bytes(),
+        )?;
+        self.sink.write_all(serde_json::to_string(&e)?.as_bytes())?;
+        self.sink.write_all(b"\n")?;
 
         Ok(())
     }
+
+    /// Flush and close the output, then build the index for BGZF output.
+    ///
+    /// Positions beyond what `.tbi` can represent lead to a `.csi` index.
+    pub fn finish(self) -> Result<(), error::Error> {
+        let Self {
+            sink,
+            path_index,
+            max_end,
+            ..
+        } = self;
+        match sink {
+            Sink::Stdout(mut stdout) => stdout.flush()?,
+            Sink::Plain(mut file) => file.flush()?,
+            Sink::Bgzf(bgzf) => bgzf.close()?,
+        }
+
+        if let Some(path) = path_index {
+            let min_shift = if max_end >= 1 << 29 { 14 } else { 0 };
+            let c_path = CString::new(path.as_str()).map_err(|_| error::Error::InvalidPath())?;
+            let ret =
+                unsafe { htslib::tbx_index_build(c_path.as_ptr(), min_shift, &TBX_CONF_EVIDENCE) };
+            if ret < 0 {
+                return Err(error::Error::TabixIndexError(path));
+            }
+        }
+        Ok(())
+    }
 }
 
-pub struct IndexedReader {
+/// Writes evidence records, in the binary format if the path ends in `binary::EXTENSION` and as
+/// TSV otherwise.
+pub enum Writer {
+    Text(TextWriter),
+    Binary(binary::Writer),
+}
+
+impl Writer {
+    pub fn from_path(path: &str) -> Result<Self, error::Error> {
+        if path.ends_with(binary::EXTENSION) {
+            Ok(Writer::Binary(binary::Writer::from_path(path)?))
+        } else {
+            Ok(Writer::Text(TextWriter::from_path(path)?))
+        }
+    }
+
+    pub fn write(&mut self, e: &Record) -> Result<(), error::Error> {
+        match self {
+            Writer::Text(writer) => writer.write(e),
+            Writer::Binary(writer) => writer.write(e),
+        }
+    }
+
+    /// Flush and close the output, then build the index if any.
+    pub fn finish(self) -> Result<(), error::Error> {
+        match self {
+            Writer::Text(writer) => writer.finish(),
+            Writer::Binary(writer) => writer.finish(),
+        }
+    }
+}
+
+/// Reads bgzipped, tabix-indexed TSV evidence files.
+pub struct TextIndexedReader {
     inner: tbx::Reader,
     buffer: Vec<u8>,
 }
 
-impl IndexedReader {
+impl TextIndexedReader {
     pub fn from_path(path: &str) -> Result<Self, error::Error> {
         Ok(Self {
             inner: tbx::Reader::from_path(&path)?,
@@ -133,6 +390,11 @@ impl IndexedReader {
         })
     }
 
+    /// Names of the contigs in the index.
+    pub fn contigs(&self) -> Vec<String> {
+        self.inner.seqnames()
+    }
+
     pub fn fetch(&mut self, contig: &str, start: u64, end: u64) -> Result<bool, error::Error> {
         match self.inner.tid(&contig) {
             Err(rust_htslib::tbx::errors::Error::UnknownSequence { .. }) => return Ok(false),
@@ -151,3 +413,103 @@ impl IndexedReader {
         }
     }
 }
+
+/// Reads indexed evidence files in either the binary or the TSV format.
+pub enum IndexedReader {
+    Text(TextIndexedReader),
+    Binary(binary::IndexedReader),
+}
+
+impl IndexedReader {
+    /// Open the evidence file at `path`, detecting its format from its content.
+    pub fn from_path(path: &str) -> Result<Self, error::Error> {
+        if binary::is_binary(path)? {
+            Ok(IndexedReader::Binary(binary::IndexedReader::from_path(
+                path,
+            )?))
+        } else {
+            Ok(IndexedReader::Text(TextIndexedReader::from_path(path)?))
+        }
+    }
+
+    /// Names of the contigs with records, in the order of the file.
+    pub fn contigs(&self) -> Vec<String> {
+        match self {
+            IndexedReader::Text(reader) => reader.contigs(),
+            IndexedReader::Binary(reader) => reader.contigs(),
+        }
+    }
+
+    /// Jump to the records overlapping `start..end` on `contig`, false if the contig is unknown.
+    pub fn fetch(&mut self, contig: &str, start: u64, end: u64) -> Result<bool, error::Error> {
+        match self {
+            IndexedReader::Text(reader) => reader.fetch(contig, start, end),
+            IndexedReader::Binary(reader) => reader.fetch(contig, start, end),
+        }
+    }
+
+    pub fn read_record(&mut self) -> Result<Option<Record>, error::Error> {
+        match self {
+            IndexedReader::Text(reader) => reader.read_record(),
+            IndexedReader::Binary(reader) => reader.read_record(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error::Error;
+    use super::{Record, Sides, Writer};
+    use matches::assert_matches;
+
+    fn split_read(contig: &str, start: i64) -> Record {
+        Record::SplitRead {
+            read_id: 1,
+            is_first: true,
+            contig: contig.to_string(),
+            start,
+            end: start + 100,
+            strand: None,
+            clipped_sides: Sides::Left,
+            breakpoint: None,
+            clipped_bases: None,
+            partner: None,
+            mapq: None,
+            mate_mapq: None,
+        }
+    }
+
+    #[test]
+    fn test_writer_rejects_unsorted() -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path = tmp_dir.path().join("out.tsv.gz");
+
+        let mut writer = Writer::from_path(path.to_str().unwrap())?;
+        writer.write(&split_read("one", 100))?;
+        assert_matches!(
+            writer.write(&split_read("one", 99)),
+            Err(Error::UnsortedEvidence(_))
+        );
+
+        let mut writer = Writer::from_path(path.to_str().unwrap())?;
+        writer.write(&split_read("one", 100))?;
+        writer.write(&split_read("two", 0))?;
+        assert_matches!(
+            writer.write(&split_read("one", 200)),
+            Err(Error::UnsortedEvidence(_))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_plain_accepts_unsorted() -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path = tmp_dir.path().join("out.tsv");
+
+        let mut writer = Writer::from_path(path.to_str().unwrap())?;
+        writer.write(&split_read("one", 100))?;
+        writer.write(&split_read("one", 99))?;
+        writer.finish()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,813 @@
+/// Compact binary format for PE/SR evidence with a region index.
+///
+/// The data file is compressed with BGZF and starts with `MAGIC`, followed by the records.  Each
+/// record is its length as `u32`, followed by its fields in little endian.  Contigs are stored as
+/// IDs into the contig table of the index at `index_path()`, which also holds the virtual offset
+/// of the first record starting in or after each window of `1 << WINDOW_SHIFT` bases.
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use super::super::{bgzf, error::Error};
+use super::{PairOrientation, Record, Sides, SortOrder, SplitPartner, Strand};
+
+/// File name extension of binary evidence files.
+pub const EXTENSION: &str = ".mev";
+
+/// Magic bytes at the start of the (uncompressed) data file, including the format version.
+const MAGIC: [u8; 4] = *b"MEV\x01";
+
+/// Magic bytes at the start of the index file, including the format version.
+const INDEX_MAGIC: [u8; 4] = *b"MEI\x01";
+
+/// Binary logarithm of the window size of the index.
+const WINDOW_SHIFT: i64 = 14;
+
+/// Record kinds.
+const KIND_PAIRED_READ: u8 = 0;
+const KIND_SPLIT_READ: u8 = 1;
+const KIND_ANCHORED_READ: u8 = 2;
+
+/// Path of the index of the binary evidence file at `path`.
+pub fn index_path(path: &str) -> String {
+    format!("{}.mei", path)
+}
+
+/// Whether the file at `path` is a binary evidence file, judging from its magic bytes.
+pub fn is_binary(path: &str) -> Result<bool, Error> {
+    let mut reader = bgzf::Reader::from_path(path)?;
+    let mut magic = [0u8; 4];
+    match reader.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Index entry of one contig.
+#[derive(Debug)]
+struct ContigIndex {
+    /// Name of the contig.
+    name: String,
+    /// Largest length of a record on the contig.
+    max_len: i64,
+    /// Virtual offset of the first record starting in or after each window.
+    windows: Vec<u64>,
+}
+
+/// Contig table with the index entries, contigs get IDs in order of first use.
+#[derive(Debug, Default)]
+struct ContigTable {
+    /// Index entries by contig ID.
+    contigs: Vec<ContigIndex>,
+    /// Contig IDs by name.
+    ids: HashMap<String, u32>,
+}
+
+impl ContigTable {
+    /// Return the ID of `name`, adding it if necessary.
+    fn id(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.contigs.len() as u32;
+        self.contigs.push(ContigIndex {
+            name: name.to_string(),
+            max_len: 0,
+            windows: Vec::new(),
+        });
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Return the name of the contig with the given `id`.
+    fn name(&self, id: u32) -> Result<&str, Error> {
+        self.contigs
+            .get(id as usize)
+            .map(|contig| contig.name.as_str())
+            .ok_or_else(|| Error::InvalidBinaryEvidence(format!("unknown contig ID {}", id)))
+    }
+
+    /// Serialize the table as the index file.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = INDEX_MAGIC.to_vec();
+        put_u32(&mut buf, self.contigs.len() as u32);
+        for contig in &self.contigs {
+            put_str(&mut buf, &contig.name);
+            put_i64(&mut buf, contig.max_len);
+            put_u32(&mut buf, contig.windows.len() as u32);
+            for offset in &contig.windows {
+                buf.extend_from_slice(&offset.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Parse the table from the index file contents.
+    fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut decoder = Decoder { data };
+        if decoder.take(INDEX_MAGIC.len())? != INDEX_MAGIC {
+            return Err(Error::InvalidBinaryEvidence(String::from(
+                "index has unknown format",
+            )));
+        }
+        let mut result = Self::default();
+        for _ in 0..decoder.u32()? {
+            let id = result.id(&decoder.string()?);
+            let contig = &mut result.contigs[id as usize];
+            contig.max_len = decoder.i64()?;
+            for _ in 0..decoder.u32()? {
+                contig.windows.push(decoder.u64()?);
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_i64(buf: &mut Vec<u8>, value: i64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Encode `strand` as a flag bit.
+fn strand_flag(strand: &Strand, bit: u8) -> u8 {
+    match strand {
+        Strand::Forward => 0,
+        Strand::Reverse => bit,
+    }
+}
+
+/// Decode a strand from a flag bit.
+fn flag_strand(flags: u8, bit: u8) -> Strand {
+    if flags & bit != 0 {
+        Strand::Reverse
+    } else {
+        Strand::Forward
+    }
+}
+
+/// Append the encoding of `record` to `buf`.
+///
+/// All kinds start with the kind, contig ID, start, end, read ID, and a byte of flags.  Optional
+/// fields are only written if present according to the flags.
+fn encode(record: &Record, table: &mut ContigTable, buf: &mut Vec<u8>) {
+    let interval = record.interval();
+    let kind = match record {
+        Record::PairedRead { .. } => KIND_PAIRED_READ,
+        Record::SplitRead { .. } => KIND_SPLIT_READ,
+        Record::AnchoredRead { .. } => KIND_ANCHORED_READ,
+    };
+    buf.push(kind);
+    put_u32(buf, table.id(record.contig()));
+    put_i64(buf, interval.start);
+    put_i64(buf, interval.end);
+    put_i64(buf, record.read_id());
+
+    match record {
+        Record::PairedRead {
+            is_first1,
+            strand1,
+            contig2,
+            start2,
+            strand2,
+            tlen,
+            mapq,
+            mate_mapq,
+            orientation,
+            ..
+        } => {
+            let mut flags = *is_first1 as u8 | strand_flag(strand1, 1 << 1);
+            if contig2.is_some() {
+                flags |= 1 << 2;
+            }
+            if start2.is_some() {
+                flags |= 1 << 3;
+            }
+            if let Some(strand2) = strand2 {
+                flags |= 1 << 4 | strand_flag(strand2, 1 << 5);
+            }
+            if tlen.is_some() {
+                flags |= 1 << 6;
+            }
+            // Paired reads have a second byte of flags.
+            let mut flags2 = 0;
+            if mapq.is_some() {
+                flags2 |= 1;
+            }
+            if mate_mapq.is_some() {
+                flags2 |= 1 << 1;
+            }
+            if let Some(orientation) = orientation {
+                let class = match orientation {
+                    PairOrientation::FR => 0,
+                    PairOrientation::RF => 1,
+                    PairOrientation::FF => 2,
+                    PairOrientation::RR => 3,
+                };
+                flags2 |= 1 << 2 | class << 3;
+            }
+            buf.push(flags);
+            buf.push(flags2);
+            if let Some(contig2) = contig2 {
+                put_u32(buf, table.id(contig2));
+            }
+            if let Some(start2) = start2 {
+                put_i64(buf, *start2);
+            }
+            if let Some(tlen) = tlen {
+                put_i64(buf, *tlen);
+            }
+            if let Some(mapq) = mapq {
+                buf.push(*mapq);
+            }
+            if let Some(mate_mapq) = mate_mapq {
+                buf.push(*mate_mapq);
+            }
+        }
+        Record::SplitRead {
+            is_first,
+            strand,
+            clipped_sides,
+            breakpoint,
+            clipped_bases,
+            partner,
+            mapq,
+            mate_mapq,
+            ..
+        } => {
+            let sides = match clipped_sides {
+                Sides::Left => 0,
+                Sides::Right => 1,
+                Sides::Both => 2,
+                Sides::Neither => 3,
+            };
+            let mut flags = *is_first as u8 | sides << 1;
+            if breakpoint.is_some() {
+                flags |= 1 << 3;
+            }
+            if let Some(partner) = partner {
+                flags |= 1 << 4 | strand_flag(&partner.strand, 1 << 5);
+            }
+            if let Some(strand) = strand {
+                flags |= 1 << 6 | strand_flag(strand, 1 << 7);
+            }
+            // Split reads have a second byte of flags.
+            let mut flags2 = 0;
+            if clipped_bases.is_some() {
+                flags2 |= 1;
+            }
+            if let Some(partner) = partner {
+                if partner.breakpoint.is_some() {
+                    flags2 |= 1 << 1;
+                }
+                if partner.clipped_bases.is_some() {
+                    flags2 |= 1 << 2;
+                }
+            }
+            if mapq.is_some() {
+                flags2 |= 1 << 3;
+            }
+            if mate_mapq.is_some() {
+                flags2 |= 1 << 4;
+            }
+            buf.push(flags);
+            buf.push(flags2);
+            if let Some(breakpoint) = breakpoint {
+                put_i64(buf, *breakpoint);
+            }
+            if let Some(clipped_bases) = clipped_bases {
+                put_i64(buf, *clipped_bases);
+            }
+            if let Some(partner) = partner {
+                put_u32(buf, table.id(&partner.contig));
+                put_i64(buf, partner.start);
+                put_i64(buf, partner.end);
+                if let Some(breakpoint) = partner.breakpoint {
+                    put_i64(buf, breakpoint);
+                }
+                if let Some(clipped_bases) = partner.clipped_bases {
+                    put_i64(buf, clipped_bases);
+                }
+            }
+            if let Some(mapq) = mapq {
+                buf.push(*mapq);
+            }
+            if let Some(mate_mapq) = mate_mapq {
+                buf.push(*mate_mapq);
+            }
+        }
+        Record::AnchoredRead {
+            is_first,
+            strand,
+            mate_sequence,
+            ..
+        } => {
+            let mut flags = *is_first as u8 | strand_flag(strand, 1 << 1);
+            if mate_sequence.is_some() {
+                flags |= 1 << 2;
+            }
+            buf.push(flags);
+            if let Some(mate_sequence) = mate_sequence {
+                put_str(buf, mate_sequence);
+            }
+        }
+    }
+}
+
+/// Reads little endian values from a byte slice.
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() < len {
+            return Err(Error::InvalidBinaryEvidence(String::from(
+                "unexpected end of data",
+            )));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+/// Decode the record in `data`, returning its contig ID and the record.
+fn decode(data: &[u8], table: &ContigTable) -> Result<(u32, Record), Error> {
+    let mut decoder = Decoder { data };
+    let kind = decoder.u8()?;
+    let contig_id = decoder.u32()?;
+    let contig = table.name(contig_id)?.to_string();
+    let start = decoder.i64()?;
+    let end = decoder.i64()?;
+    let read_id = decoder.i64()?;
+    let flags = decoder.u8()?;
+
+    let record = match kind {
+        KIND_PAIRED_READ => {
+            let flags2 = decoder.u8()?;
+            Record::PairedRead {
+                read_id,
+                is_first1: flags & 1 != 0,
+                contig1: contig,
+                start1: start,
+                end1: end,
+                strand1: flag_strand(flags, 1 << 1),
+                contig2: if flags & 1 << 2 != 0 {
+                    Some(table.name(decoder.u32()?)?.to_string())
+                } else {
+                    None
+                },
+                start2: if flags & 1 << 3 != 0 {
+                    Some(decoder.i64()?)
+                } else {
+                    None
+                },
+                strand2: if flags & 1 << 4 != 0 {
+                    Some(flag_strand(flags, 1 << 5))
+                } else {
+                    None
+                },
+                tlen: if flags & 1 << 6 != 0 {
+                    Some(decoder.i64()?)
+                } else {
+                    None
+                },
+                mapq: if flags2 & 1 != 0 {
+                    Some(decoder.u8()?)
+                } else {
+                    None
+                },
+                mate_mapq: if flags2 & 1 << 1 != 0 {
+                    Some(decoder.u8()?)
+                } else {
+                    None
+                },
+                orientation: if flags2 & 1 << 2 != 0 {
+                    Some(match (flags2 >> 3) & 3 {
+                        0 => PairOrientation::FR,
+                        1 => PairOrientation::RF,
+                        2 => PairOrientation::FF,
+                        _ => PairOrientation::RR,
+                    })
+                } else {
+                    None
+                },
+            }
+        }
+        KIND_SPLIT_READ => {
+            let flags2 = decoder.u8()?;
+            Record::SplitRead {
+                read_id,
+                is_first: flags & 1 != 0,
+                contig,
+                start,
+                end,
+                strand: if flags & 1 << 6 != 0 {
+                    Some(flag_strand(flags, 1 << 7))
+                } else {
+                    None
+                },
+                clipped_sides: match (flags >> 1) & 3 {
+                    0 => Sides::Left,
+                    1 => Sides::Right,
+                    2 => Sides::Both,
+                    _ => Sides::Neither,
+                },
+                breakpoint: if flags & 1 << 3 != 0 {
+                    Some(decoder.i64()?)
+                } else {
+                    None
+                },
+                clipped_bases: if flags2 & 1 != 0 {
+                    Some(decoder.i64()?)
+                } else {
+                    None
+                },
+                partner: if flags & 1 << 4 != 0 {
+                    Some(SplitPartner {
+                        contig: table.name(decoder.u32()?)?.to_string(),
+                        start: decoder.i64()?,
+                        end: decoder.i64()?,
+                        strand: flag_strand(flags, 1 << 5),
+                        breakpoint: if flags2 & 1 << 1 != 0 {
+                            Some(decoder.i64()?)
+                        } else {
+                            None
+                        },
+                        clipped_bases: if flags2 & 1 << 2 != 0 {
+                            Some(decoder.i64()?)
+                        } else {
+                            None
+                        },
+                    })
+                } else {
+                    None
+                },
+                mapq: if flags2 & 1 << 3 != 0 {
+                    Some(decoder.u8()?)
+                } else {
+                    None
+                },
+                mate_mapq: if flags2 & 1 << 4 != 0 {
+                    Some(decoder.u8()?)
+                } else {
+                    None
+                },
+            }
+        }
+        KIND_ANCHORED_READ => Record::AnchoredRead {
+            read_id,
+            is_first: flags & 1 != 0,
+            contig,
+            start,
+            end,
+            strand: flag_strand(flags, 1 << 1),
+            mate_sequence: if flags & 1 << 2 != 0 {
+                Some(decoder.string()?)
+            } else {
+                None
+            },
+        },
+        _ => {
+            return Err(Error::InvalidBinaryEvidence(format!(
+                "unknown record kind {}",
+                kind
+            )))
+        }
+    };
+    Ok((contig_id, record))
+}
+
+/// Writes binary evidence files and their index.
+///
+/// The records must come sorted by start position with all records of a contig in one block.
+pub struct Writer {
+    inner: bgzf::Writer,
+    /// Path of the index to write.
+    path_index: String,
+    /// Contig table with the index entries.
+    table: ContigTable,
+    /// Check of the sort order for the index.
+    sort_order: SortOrder,
+    /// Buffer for encoding records.
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        let mut inner = bgzf::Writer::from_path(path)?;
+        inner.write_all(&MAGIC)?;
+        Ok(Self {
+            inner,
+            path_index: index_path(path),
+            table: ContigTable::default(),
+            sort_order: SortOrder::default(),
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn write(&mut self, e: &Record) -> Result<(), Error> {
+        let interval = e.interval();
+        self.sort_order.check(e.contig(), interval.start)?;
+
+        let offset = self.inner.tell();
+        let id = self.table.id(e.contig());
+        let contig = &mut self.table.contigs[id as usize];
+        let window = (std::cmp::max(interval.start, 0) >> WINDOW_SHIFT) as usize;
+        while contig.windows.len() <= window {
+            contig.windows.push(offset);
+        }
+        contig.max_len = std::cmp::max(contig.max_len, interval.end - interval.start);
+
+        self.buffer.clear();
+        encode(e, &mut self.table, &mut self.buffer);
+        self.inner
+            .write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&self.buffer)?;
+        Ok(())
+    }
+
+    /// Close the data file and write the index.
+    pub fn finish(self) -> Result<(), Error> {
+        self.inner.close()?;
+        std::fs::write(&self.path_index, self.table.to_bytes())?;
+        Ok(())
+    }
+}
+
+/// Reads binary evidence files through their index.
+pub struct IndexedReader {
+    inner: bgzf::Reader,
+    /// Contig table with the index entries.
+    table: ContigTable,
+    /// Contig ID and range of the current query, `None` when done.
+    query: Option<(u32, i64, i64)>,
+    /// Buffer for decoding records.
+    buffer: Vec<u8>,
+}
+
+impl IndexedReader {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        let mut inner = bgzf::Reader::from_path(path)?;
+        let mut magic = [0u8; 4];
+        inner.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(Error::InvalidBinaryEvidence(format!(
+                "{} has unknown format",
+                path
+            )));
+        }
+        Ok(Self {
+            inner,
+            table: ContigTable::from_bytes(&std::fs::read(index_path(path))?)?,
+            query: None,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Names of the contigs with records, in the order of the file.
+    pub fn contigs(&self) -> Vec<String> {
+        let mut contigs: Vec<&ContigIndex> = self
+            .table
+            .contigs
+            .iter()
+            .filter(|contig| !contig.windows.is_empty())
+            .collect();
+        contigs.sort_by_key(|contig| contig.windows[0]);
+        contigs
+            .into_iter()
+            .map(|contig| contig.name.clone())
+            .collect()
+    }
+
+    /// Jump to the records overlapping `start..end` on `contig`, false if the contig is unknown.
+    pub fn fetch(&mut self, contig: &str, start: u64, end: u64) -> Result<bool, Error> {
+        let id = match self.table.ids.get(contig) {
+            Some(id) => *id,
+            None => return Ok(false),
+        };
+        let (start, end) = (start as i64, end as i64);
+        // Records overlapping `start` start at most `max_len` before it.
+        let contig = &self.table.contigs[id as usize];
+        let window = (std::cmp::max(start - contig.max_len, 0) >> WINDOW_SHIFT) as usize;
+        self.query = match contig.windows.get(window) {
+            Some(offset) => {
+                self.inner.seek(*offset)?;
+                Some((id, start, end))
+            }
+            None => None,
+        };
+        Ok(true)
+    }
+
+    pub fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        while let Some((id, start, end)) = self.query {
+            let mut len = [0u8; 4];
+            match self.inner.read_exact(&mut len) {
+                Ok(()) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            self.buffer.resize(u32::from_le_bytes(len) as usize, 0);
+            self.inner.read_exact(&mut self.buffer)?;
+
+            let (contig_id, record) = decode(&self.buffer, &self.table)?;
+            let interval = record.interval();
+            if contig_id != id || interval.start >= end {
+                break;
+            }
+            // Empty records are treated as having length one, as tabix does.
+            if std::cmp::max(interval.end, interval.start + 1) > start {
+                return Ok(Some(record));
+            }
+        }
+        self.query = None;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PairOrientation, Record, Sides, SplitPartner, Strand};
+    use super::{is_binary, Error, IndexedReader, Writer};
+    use matches::assert_matches;
+    use pretty_assertions::assert_eq;
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record::PairedRead {
+                read_id: 1,
+                is_first1: true,
+                contig1: String::from("one"),
+                start1: 100,
+                end1: 200,
+                strand1: Strand::Forward,
+                contig2: Some(String::from("two")),
+                start2: Some(500),
+                strand2: Some(Strand::Reverse),
+                tlen: None,
+                mapq: Some(60),
+                mate_mapq: None,
+                orientation: Some(PairOrientation::FR),
+            },
+            Record::SplitRead {
+                read_id: 2,
+                is_first: false,
+                contig: String::from("one"),
+                start: 150,
+                end: 30_000,
+                strand: Some(Strand::Reverse),
+                clipped_sides: Sides::Both,
+                breakpoint: Some(29_950),
+                clipped_bases: Some(50),
+                partner: Some(SplitPartner {
+                    contig: String::from("three"),
+                    start: 10,
+                    end: 60,
+                    strand: Strand::Reverse,
+                    breakpoint: Some(60),
+                    clipped_bases: None,
+                }),
+                mapq: Some(23),
+                mate_mapq: Some(60),
+            },
+            Record::AnchoredRead {
+                read_id: 3,
+                is_first: true,
+                contig: String::from("one"),
+                start: 40_000,
+                end: 40_100,
+                strand: Strand::Reverse,
+                mate_sequence: Some(String::from("ACGT")),
+            },
+            Record::PairedRead {
+                read_id: 1,
+                is_first1: false,
+                contig1: String::from("two"),
+                start1: 500,
+                end1: 600,
+                strand1: Strand::Reverse,
+                contig2: None,
+                start2: None,
+                strand2: None,
+                tlen: Some(-500),
+                mapq: Some(0),
+                mate_mapq: Some(37),
+                orientation: None,
+            },
+            Record::SplitRead {
+                read_id: 4,
+                is_first: true,
+                contig: String::from("two"),
+                start: 700,
+                end: 800,
+                strand: None,
+                clipped_sides: Sides::Left,
+                breakpoint: None,
+                clipped_bases: None,
+                partner: None,
+                mapq: None,
+                mate_mapq: None,
+            },
+        ]
+    }
+
+    /// Fetch the records overlapping the region as JSON.
+    fn fetch(
+        reader: &mut IndexedReader,
+        contig: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<String>, Error> {
+        let mut result = Vec::new();
+        if reader.fetch(contig, start, end)? {
+            while let Some(record) = reader.read_record()? {
+                result.push(serde_json::to_string(&record)?);
+            }
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn test_binary_round_trip() -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path = tmp_dir.path().join("out.mev");
+        let path = path.to_str().unwrap();
+        let records = records();
+
+        let mut writer = Writer::from_path(path)?;
+        for record in &records {
+            writer.write(record)?;
+        }
+        writer.finish()?;
+        assert!(is_binary(path)?);
+
+        let json: Vec<String> = records
+            .iter()
+            .map(|record| serde_json::to_string(record).unwrap())
+            .collect();
+        let mut reader = IndexedReader::from_path(path)?;
+        assert_eq!(reader.contigs(), vec!["one", "two"]);
+        assert_eq!(
+            fetch(&mut reader, "one", 0, 1_000_000)?,
+            json[0..3].to_vec()
+        );
+        assert_eq!(
+            fetch(&mut reader, "two", 0, 1_000_000)?,
+            json[3..5].to_vec()
+        );
+        // The split read starts in an earlier window but overlaps.
+        assert_eq!(
+            fetch(&mut reader, "one", 20_000, 40_000)?,
+            json[1..2].to_vec()
+        );
+        assert_eq!(fetch(&mut reader, "one", 200, 40_001)?, json[1..3].to_vec());
+        assert_eq!(fetch(&mut reader, "two", 600, 700)?, Vec::<String>::new());
+        assert_eq!(fetch(&mut reader, "three", 0, 1_000)?, Vec::<String>::new());
+        assert!(!reader.fetch("four", 0, 1_000)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_rejects_unsorted() -> Result<(), Error> {
+        let tmp_dir = tempdir::TempDir::new("tests")?;
+        let path = tmp_dir.path().join("out.mev");
+        let mut records = records();
+        records.swap(0, 2);
+
+        let mut writer = Writer::from_path(path.to_str().unwrap())?;
+        writer.write(&records[0])?;
+        assert_matches!(writer.write(&records[1]), Err(Error::UnsortedEvidence(_)));
+        Ok(())
+    }
+}
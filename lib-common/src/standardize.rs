@@ -0,0 +1,225 @@
+pub mod cnmops;
+pub mod cnvnator;
+pub mod delly;
+pub mod gcnv;
+pub mod gridss;
+pub mod lumpy;
+pub mod manta;
+pub mod sniffles;
+
+use regex::Regex;
+use rust_htslib::bcf;
+
+use super::error::Error;
+
+/// Conversion of the VCF output of one SV caller into standardized records.
+///
+/// Implementations are listed in `STANDARDIZERS` which is used for resolving the caller name on
+/// the command line, building the header of standardized VCF files, and reading the per-caller
+/// FORMAT fields back in `StandardizedRecord::from_bcf_record()`.
+pub trait Standardizer: std::fmt::Debug + Sync {
+    /// Name of the caller, also used for INFO/ALGORITHMS and the per-caller FORMAT field.
+    fn name(&self) -> &'static str;
+
+    /// Further names that the caller can be selected by.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Description of the per-caller FORMAT field.
+    fn description(&self) -> &'static str;
+
+    /// Header lines to add to the standardized VCF file.
+    fn header_lines(&self) -> Vec<String> {
+        vec![format!(
+            "##FORMAT=<ID={},Number=1,Type=Integer,Description={}>",
+            self.name(),
+            self.description()
+        )]
+    }
+
+    /// Caller-specific part of summarizing the record `src` into `dst`.
+    ///
+    /// Returns whether `dst` is to be written.
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error>;
+}
+
+/// All known standardizers, in the order of their header lines.
+static STANDARDIZERS: &[&dyn Standardizer] = &[
+    &cnmops::CNMOPS,
+    &delly::Delly,
+    &manta::Manta,
+    &gridss::Gridss,
+    &lumpy::Lumpy,
+    &sniffles::Sniffles,
+    &cnvnator::CNVnator,
+    &gcnv::GCNV,
+];
+
+/// Return all known standardizers.
+pub fn standardizers() -> &'static [&'static dyn Standardizer] {
+    STANDARDIZERS
+}
+
+/// Return standardizer with the given name or alias.
+pub fn lookup(name: &str) -> Option<&'static dyn Standardizer> {
+    STANDARDIZERS
+        .iter()
+        .find(|s| s.name() == name || s.aliases().contains(&name))
+        .copied()
+}
+
+/// Copy FORMAT/GT of the first `sample_count` samples from `src` to `dst`.
+fn copy_genotypes(
+    src: &mut bcf::Record,
+    dst: &mut bcf::Record,
+    sample_count: usize,
+) -> Result<(), Error> {
+    let mut gts: Vec<i32> = Vec::new();
+    src.format(b"GT").integer()?[..sample_count]
+        .iter()
+        .for_each(|xs| gts.extend_from_slice(xs));
+    dst.push_format_integer(b"GT", &gts)?;
+    Ok(())
+}
+
+/// For each sample, collect whether there is any called non-reference allele.
+fn called_from_genotypes(src: &mut bcf::Record, sample_count: usize) -> Vec<i32> {
+    (0..sample_count)
+        .map(|i| {
+            let ith_genotype: bcf::record::Genotype = src.genotypes().unwrap().get(i as usize);
+            let any_var: bool = ith_genotype.iter().any(|gt_allele| match gt_allele {
+                bcf::record::GenotypeAllele::Unphased(i)
+                | bcf::record::GenotypeAllele::Phased(i) => (*i != 0),
+                _ => false,
+            });
+            if any_var {
+                1
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// For each sample, collect whether the integer FORMAT field `tag` shows any support.
+fn called_from_support(
+    src: &mut bcf::Record,
+    tag: &[u8],
+    sample_count: usize,
+) -> Result<Vec<i32>, Error> {
+    Ok(src.format(tag).integer()?[..sample_count]
+        .iter()
+        .map(|xs| if xs[0] > 0 { 1 } else { 0 })
+        .collect())
+}
+
+/// Write FORMAT/GT as `0/1` for called and `0/0` for other samples.
+///
+/// This is used for callers that do not genotype their calls.
+fn push_genotypes_from_called(dst: &mut bcf::Record, called: &[i32]) -> Result<(), Error> {
+    // Unphased alleles are encoded as `(allele + 1) << 1`.
+    let gts: Vec<i32> = called
+        .iter()
+        .flat_map(|called| vec![1 << 1, (called + 1) << 1])
+        .collect();
+    dst.push_format_integer(b"GT", &gts)?;
+    Ok(())
+}
+
+/// Build breakend ALT string pointing to `chr2:end2` with the given strands.
+///
+/// This is the inverse of `parse_bnd_pos()` and `parse_bnd_strands()`.
+fn build_bnd_alt(ref_allele: &str, chr2: &str, end2: i32, strands: &str) -> String {
+    match strands {
+        "+-" => format!("{}[{}:{}[", ref_allele, chr2, end2),
+        "++" => format!("{}]{}:{}]", ref_allele, chr2, end2),
+        "-+" => format!("]{}:{}]{}", chr2, end2, ref_allele),
+        "--" => format!("[{}:{}[{}", chr2, end2, ref_allele),
+        _ => panic!("Unexpected strands: {}", &strands),
+    }
+}
+
+/// Return SV type for a breakpoint between the two positions with the given strands.
+fn sv_type_from_breakpoint(chrom: &[u8], chr2: &[u8], strands: &str) -> &'static str {
+    if chrom != chr2 {
+        "BND"
+    } else {
+        match strands {
+            "+-" => "DEL",
+            "-+" => "DUP",
+            _ => "INV",
+        }
+    }
+}
+
+/// Parse Breakend BND position string.
+fn parse_bnd_pos(alt: &str) -> Result<(&[u8], i32), Error> {
+    let re = Regex::new(r".*[\[\]](.+):(.+)[\[\]].*").unwrap();
+    let cap = re
+        .captures(alt)
+        .unwrap_or_else(|| panic!("Could not match breakend: {}", &alt));
+
+    Ok((
+        cap.get(1).unwrap().as_str().as_bytes(),
+        cap.get(2).unwrap().as_str().parse()?,
+    ))
+}
+
+/// Parse Breakend BND position string to strands.
+fn parse_bnd_strands(alt: &str) -> String {
+    if alt.ends_with('[') {
+        "+-".to_string()
+    } else if alt.ends_with(']') {
+        "++".to_string()
+    } else if alt.starts_with(']') {
+        "-+".to_string()
+    } else if alt.starts_with('[') {
+        "--".to_string()
+    } else {
+        panic!("Unexpected alt string: {}", &alt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(super::lookup("delly").map(|s| s.name()), Some("delly"));
+        assert_eq!(super::lookup("smoove").map(|s| s.name()), Some("lumpy"));
+        assert!(super::lookup("unknown").is_none());
+    }
+
+    #[test]
+    fn test_header_lines() {
+        assert_eq!(
+            super::lookup("manta").unwrap().header_lines(),
+            vec!["##FORMAT=<ID=manta,Number=1,Type=Integer,Description=Called by Manta>"]
+        );
+    }
+
+    #[test]
+    fn test_sv_type_from_breakpoint() {
+        assert_eq!(super::sv_type_from_breakpoint(b"1", b"1", "+-"), "DEL");
+        assert_eq!(super::sv_type_from_breakpoint(b"1", b"1", "-+"), "DUP");
+        assert_eq!(super::sv_type_from_breakpoint(b"1", b"1", "++"), "INV");
+        assert_eq!(super::sv_type_from_breakpoint(b"1", b"1", "--"), "INV");
+        assert_eq!(super::sv_type_from_breakpoint(b"1", b"2", "+-"), "BND");
+    }
+
+    #[test]
+    fn test_build_bnd_alt() {
+        assert_eq!(super::build_bnd_alt("N", "2", 100, "+-"), "N[2:100[");
+        assert_eq!(super::build_bnd_alt("N", "2", 100, "++"), "N]2:100]");
+        assert_eq!(super::build_bnd_alt("N", "2", 100, "-+"), "]2:100]N");
+        assert_eq!(super::build_bnd_alt("N", "2", 100, "--"), "[2:100[N");
+    }
+}
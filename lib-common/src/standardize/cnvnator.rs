@@ -0,0 +1,57 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{called_from_genotypes, copy_genotypes, Standardizer};
+
+/// Standardizer for CNVnator output.
+#[derive(Debug)]
+pub struct CNVnator;
+
+impl Standardizer for CNVnator {
+    fn name(&self) -> &'static str {
+        "cnvnator"
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by CNVnator"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        _src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        dst.set_alleles(&src.alleles())?;
+
+        // INFO/END2
+        let end2 = src.info(b"END").integer().expect("No END?").unwrap()[0];
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(
+            b"CHR2",
+            &[&dst_header.rid2name(dst.rid().expect("No REF?"))?],
+        )?;
+        // INFO/SVTYPE
+        let sv_type = String::from(std::str::from_utf8(
+            src.info(b"SVTYPE").string()?.unwrap()[0],
+        )?);
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+
+        // NB: no INFO/STRANDS
+        // INFO/SVLEN
+        dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"cnvnator"])?;
+
+        // FORMAT/GT
+        let sample_count = dst_header.sample_count() as usize;
+        copy_genotypes(src, dst, sample_count)?;
+
+        // FORMAT/cnvnator
+        dst.push_format_integer(b"cnvnator", &called_from_genotypes(src, sample_count))?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,88 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{called_from_genotypes, copy_genotypes, Standardizer};
+
+/// Standardizer for Delly2 output.
+#[derive(Debug)]
+pub struct Delly;
+
+impl Standardizer for Delly {
+    fn name(&self) -> &'static str {
+        "delly"
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by Delly"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        _src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        dst.set_alleles(&src.alleles())?;
+
+        // INFO/END2
+        let end2 = match src.info(b"END2").integer() {
+            Ok(Some(ends)) => ends[0],
+            _ => match src.info(b"END").integer() {
+                Ok(Some(ends)) => ends[0],
+                _ => panic!("Could not read END or END2"),
+            },
+        };
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(b"CHR2", &src.info(b"CHR2").string()?.unwrap())?;
+        // INFO/SVTYPE
+        let sv_type = String::from(std::str::from_utf8(
+            src.info(b"SVTYPE").string()?.unwrap()[0],
+        )?);
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+
+        // INFO/STRANDS
+        let raw_strands = std::str::from_utf8(src.info(b"CT").string()?.unwrap()[0])?;
+        let strands = match raw_strands {
+            "5to3" => "-+",
+            "3to5" => "+-",
+            "5to5" => "--",
+            "3to3" => "++",
+            "NtoN" => {
+                if sv_type == "INS" {
+                    "+-"
+                } else {
+                    panic!("improper strands")
+                }
+            }
+            _ => panic!("improper strands"),
+        };
+        dst.push_info_string(b"STRANDS", &[strands.as_bytes()])?;
+        // INFO/SVLEN
+        let svtype = std::str::from_utf8(src.info(b"SVTYPE").string()?.unwrap()[0])?;
+        if svtype == "BND" {
+            dst.push_info_integer(b"SVLEN", &[-1])?;
+        } else if svtype == "INS" {
+            // Length of inserted sequence, if known.
+            let ins_len = match src.info(b"INSLEN").integer() {
+                Ok(Some(lens)) => lens[0],
+                _ => -1,
+            };
+            dst.push_info_integer(b"SVLEN", &[ins_len])?;
+        } else {
+            dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        }
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"delly"])?;
+
+        // FORMAT/GT
+        let sample_count = dst_header.sample_count() as usize;
+        copy_genotypes(src, dst, sample_count)?;
+
+        // FORMAT/delly
+        dst.push_format_integer(b"delly", &called_from_genotypes(src, sample_count))?;
+
+        Ok(true)
+    }
+}
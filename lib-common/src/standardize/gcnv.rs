@@ -0,0 +1,84 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{push_genotypes_from_called, Standardizer};
+
+/// Standardizer for GATK gCNV output.
+///
+/// The segments VCF has the ALT alleles `<DEL>` and `<DUP>` and haploid GT values that give the
+/// allele index.  Reference segments are skipped and the SV type is taken from the allele of the
+/// first called sample.
+#[derive(Debug)]
+pub struct GCNV;
+
+impl Standardizer for GCNV {
+    fn name(&self) -> &'static str {
+        "gcnv"
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by GATK gCNV"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        _src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        let sample_count = dst_header.sample_count() as usize;
+        let alleles: Vec<Option<i32>> = (0..sample_count)
+            .map(|i| {
+                src.genotypes().unwrap().get(i as usize).iter().find_map(
+                    |gt_allele| match gt_allele {
+                        bcf::record::GenotypeAllele::Unphased(i)
+                        | bcf::record::GenotypeAllele::Phased(i)
+                            if *i != 0 =>
+                        {
+                            Some(*i)
+                        }
+                        _ => None,
+                    },
+                )
+            })
+            .collect();
+        let allele = match alleles.iter().find_map(|allele| *allele) {
+            Some(allele) => allele,
+            None => return Ok(false), // skip reference segments
+        };
+        let alt_allele = std::str::from_utf8(src.alleles()[allele as usize])?.to_owned();
+        let sv_type = alt_allele
+            .trim_matches(|c| c == '<' || c == '>')
+            .to_string();
+        let ref_allele = std::str::from_utf8(src.alleles()[0])?[0..1].to_owned();
+        dst.set_alleles(&[&ref_allele.as_bytes(), &alt_allele.as_bytes()])?;
+
+        // INFO/END2
+        let end2 = src.info(b"END").integer().expect("No END?").unwrap()[0];
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(
+            b"CHR2",
+            &[&dst_header.rid2name(dst.rid().expect("No REF?"))?],
+        )?;
+        // INFO/SVTYPE
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+
+        // NB: no INFO/STRANDS
+        // INFO/SVLEN
+        dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"gcnv"])?;
+
+        // FORMAT/GT and FORMAT/gcnv
+        let called: Vec<i32> = alleles
+            .iter()
+            .map(|a| if *a == Some(allele) { 1 } else { 0 })
+            .collect();
+        push_genotypes_from_called(dst, &called)?;
+        dst.push_format_integer(b"gcnv", &called)?;
+
+        Ok(true)
+    }
+}
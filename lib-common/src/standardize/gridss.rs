@@ -0,0 +1,92 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{
+    called_from_support, parse_bnd_pos, parse_bnd_strands, push_genotypes_from_called,
+    sv_type_from_breakpoint, Standardizer,
+};
+
+/// Standardizer for GRIDSS output.
+///
+/// GRIDSS describes all SVs as pairs of breakend records.  Only the first record of each pair is
+/// kept, for mates at the same position the one with the smaller ID than its INFO/MATEID, and the
+/// SV type is derived from the breakpoint orientation.  GRIDSS does not genotype, so
+/// samples with supporting fragments (FORMAT/VF) are written as called.
+#[derive(Debug)]
+pub struct Gridss;
+
+impl Standardizer for Gridss {
+    fn name(&self) -> &'static str {
+        "gridss"
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by GRIDSS"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        let ref_allele = std::str::from_utf8(src.alleles()[0])?[0..1].to_owned();
+        let alt_allele = std::str::from_utf8(src.alleles()[1])?.to_owned();
+        if !alt_allele.contains('[') && !alt_allele.contains(']') {
+            return Ok(false); // skip single breakends
+        }
+
+        // Skip the second record of each breakend pair.
+        let rid = src.rid().expect("No REF?");
+        let chrom = src_header.rid2name(rid)?;
+        let (chr2, end2) = parse_bnd_pos(&alt_allele)?;
+        let rid2 = src_header.name2rid(chr2)?;
+        let (here, mate) = ((rid, src.pos() + 1), (rid2, end2 as i64));
+        if mate < here {
+            return Ok(false);
+        } else if mate == here {
+            let id = src.id();
+            if let Ok(Some(mate_ids)) = src.info(b"MATEID").string() {
+                if mate_ids[0] < &id[..] {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let strands = parse_bnd_strands(&alt_allele);
+        let sv_type = sv_type_from_breakpoint(chrom, chr2, &strands);
+
+        // Set REF and ALT alleles.
+        if sv_type == "BND" {
+            dst.set_alleles(&[&ref_allele.as_bytes(), &alt_allele.as_bytes()])?;
+        } else {
+            dst.set_alleles(&[&ref_allele.as_bytes(), format!("<{}>", &sv_type).as_bytes()])?;
+        }
+
+        // INFO/END2
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(b"CHR2", &[chr2])?;
+        // INFO/SVTYPE
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+        // INFO/STRANDS
+        dst.push_info_string(b"STRANDS", &[strands.as_bytes()])?;
+        // INFO/SVLEN
+        if sv_type == "BND" {
+            dst.push_info_integer(b"SVLEN", &[-1])?;
+        } else {
+            dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        }
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"gridss"])?;
+
+        // FORMAT/GT and FORMAT/gridss
+        let sample_count = dst_header.sample_count() as usize;
+        let called = called_from_support(src, b"VF", sample_count)?;
+        push_genotypes_from_called(dst, &called)?;
+        dst.push_format_integer(b"gridss", &called)?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,101 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{
+    called_from_genotypes, called_from_support, copy_genotypes, parse_bnd_pos,
+    push_genotypes_from_called, Standardizer,
+};
+
+/// Standardizer for Lumpy (and Smoove) output.
+///
+/// The second records of BND pairs (INFO/SECONDARY) are skipped.  Un-genotyped Lumpy calls
+/// (without FORMAT/GT) use the supporting read count FORMAT/SU instead.
+#[derive(Debug)]
+pub struct Lumpy;
+
+impl Standardizer for Lumpy {
+    fn name(&self) -> &'static str {
+        "lumpy"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["smoove"]
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by Lumpy"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        if src.info(b"SECONDARY").flag()? {
+            return Ok(false);
+        }
+
+        let sv_type = std::str::from_utf8(src.info(b"SVTYPE").string()?.unwrap()[0])?.to_string();
+        if !["DEL", "DUP", "INV", "BND"].contains(&&sv_type[..]) {
+            return Ok(false);
+        }
+
+        // Extract reference and alternative allele.
+        let ref_allele = std::str::from_utf8(src.alleles()[0])?[0..1].to_owned();
+        let alt_allele = std::str::from_utf8(src.alleles()[1])?.to_owned();
+
+        // Compute value of INFO/CHR2 and END2.
+        let (chr2, end2) = match &sv_type[..] {
+            "BND" => parse_bnd_pos(&alt_allele)?,
+            _ => (
+                src_header.rid2name(src.rid().expect("No REF?"))?,
+                src.info(b"END")
+                    .integer()?
+                    .expect("Could not read INFO/END")[0],
+            ),
+        };
+
+        // Set REF and ALT alleles.
+        if sv_type == "BND" {
+            dst.set_alleles(&[&ref_allele.as_bytes(), &alt_allele.as_bytes()])?;
+        } else {
+            dst.set_alleles(&[&ref_allele.as_bytes(), format!("<{}>", &sv_type).as_bytes()])?;
+        }
+
+        // INFO/END2
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(b"CHR2", &[chr2])?;
+        // INFO/SVTYPE
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+
+        // INFO/STRANDS, given as, e.g., "+-:7", use the first (best supported) one.
+        let raw_strands = std::str::from_utf8(src.info(b"STRANDS").string()?.unwrap()[0])?;
+        let strands = raw_strands.split(':').next().unwrap().to_string();
+        dst.push_info_string(b"STRANDS", &[strands.as_bytes()])?;
+        // INFO/SVLEN
+        if sv_type == "BND" {
+            dst.push_info_integer(b"SVLEN", &[-1])?;
+        } else {
+            dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        }
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"lumpy"])?;
+
+        // FORMAT/GT and FORMAT/lumpy
+        let sample_count = dst_header.sample_count() as usize;
+        let called = if src.format(b"GT").integer().is_ok() {
+            copy_genotypes(src, dst, sample_count)?;
+            called_from_genotypes(src, sample_count)
+        } else {
+            let called = called_from_support(src, b"SU", sample_count)?;
+            push_genotypes_from_called(dst, &called)?;
+            called
+        };
+        dst.push_format_integer(b"lumpy", &called)?;
+
+        Ok(true)
+    }
+}
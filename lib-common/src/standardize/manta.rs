@@ -0,0 +1,105 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{
+    called_from_genotypes, copy_genotypes, parse_bnd_pos, parse_bnd_strands, Standardizer,
+};
+
+/// Standardizer for Manta output.
+#[derive(Debug)]
+pub struct Manta;
+
+impl Standardizer for Manta {
+    fn name(&self) -> &'static str {
+        "manta"
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by Manta"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        // TODO: skip "mated" records
+
+        // Obtain the SVTYPE and normalize.
+        let sv_type = std::str::from_utf8(src.info(b"SVTYPE").string()?.unwrap()[0])?.to_string();
+        let sv_type = match &sv_type[..] {
+            "DUP:TANDEM" => "DUP".to_string(),
+            _ => sv_type,
+        };
+
+        // Extract reference and alternative allele.
+        let ref_allele = std::str::from_utf8(src.alleles()[0])?[0..1].to_owned();
+        let alt_allele = std::str::from_utf8(src.alleles()[1])?.to_owned();
+
+        // Compute value of INFO/CHR2 and END2.
+        let (chr2, end2) = match &sv_type[..] {
+            "BND" => parse_bnd_pos(&alt_allele)?,
+            _ => (
+                src_header.rid2name(src.rid().expect("No REF?"))?,
+                src.info(b"END")
+                    .integer()?
+                    .expect("Could not read INFO/END")[0],
+            ),
+        };
+
+        // Set REF and ALT alleles.
+        if sv_type == "BND" {
+            dst.set_alleles(&[&ref_allele.as_bytes(), &alt_allele.as_bytes()])?;
+        } else {
+            dst.set_alleles(&[&ref_allele.as_bytes(), format!("<{}>", &sv_type).as_bytes()])?;
+        }
+
+        // INFO/END2
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(b"CHR2", &[chr2])?;
+        // INFO/SVTYPE
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+
+        // INFO/STRANDS
+        let strands: String = match &sv_type[..] {
+            "INV" => match src.info(b"INV3").flag()? {
+                true => "++".to_string(),
+                false => "--".to_string(),
+            },
+            "BND" => parse_bnd_strands(&alt_allele),
+            "DEL" => "+-".to_string(),
+            "DUP" => "-+".to_string(),
+            "INS" => "+-".to_string(),
+            _ => panic!("Unexpected sv_type: {}", &sv_type),
+        };
+        dst.push_info_string(b"STRANDS", &[strands.as_bytes()])?;
+        // INFO/SVLEN
+        let svtype = std::str::from_utf8(src.info(b"SVTYPE").string()?.unwrap()[0])?;
+        if svtype == "BND" {
+            dst.push_info_integer(b"SVLEN", &[-1])?;
+        } else if svtype == "INS" {
+            // Length of inserted sequence, unknown for incompletely assembled insertions.
+            let ins_len = match src.info(b"SVLEN").integer() {
+                Ok(Some(lens)) => lens[0].abs(),
+                _ => -1,
+            };
+            dst.push_info_integer(b"SVLEN", &[ins_len])?;
+        } else {
+            dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        }
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"manta"])?;
+
+        // FORMAT/GT
+        let sample_count = dst_header.sample_count() as usize;
+        copy_genotypes(src, dst, sample_count)?;
+
+        // FORMAT/manta
+        dst.push_format_integer(b"manta", &called_from_genotypes(src, sample_count))?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,104 @@
+use rust_htslib::bcf;
+
+use super::super::error::Error;
+use super::{
+    build_bnd_alt, called_from_genotypes, copy_genotypes, parse_bnd_strands, Standardizer,
+};
+
+/// Standardizer for Sniffles output.
+///
+/// Translocations (`TRA`) are written as `BND`, complex SV types are skipped.
+#[derive(Debug)]
+pub struct Sniffles;
+
+impl Standardizer for Sniffles {
+    fn name(&self) -> &'static str {
+        "sniffles"
+    }
+
+    fn description(&self) -> &'static str {
+        "Called by Sniffles"
+    }
+
+    fn summarize_record(
+        &self,
+        src: &mut bcf::Record,
+        src_header: &bcf::header::HeaderView,
+        dst: &mut bcf::Record,
+        dst_header: &bcf::header::HeaderView,
+    ) -> Result<bool, Error> {
+        let sv_type = std::str::from_utf8(src.info(b"SVTYPE").string()?.unwrap()[0])?.to_string();
+        let sv_type = match &sv_type[..] {
+            "DEL" | "DUP" | "INV" | "INS" | "BND" => sv_type,
+            "TRA" => "BND".to_string(),
+            _ => return Ok(false), // skip complex SVs
+        };
+
+        let ref_allele = std::str::from_utf8(src.alleles()[0])?[0..1].to_owned();
+        let alt_allele = std::str::from_utf8(src.alleles()[1])?.to_owned();
+
+        // Compute value of INFO/CHR2 and END2.
+        let chr2 = match src.info(b"CHR2").string()? {
+            Some(chr2) => chr2[0].to_vec(),
+            None => src_header.rid2name(src.rid().expect("No REF?"))?.to_vec(),
+        };
+        let end2 = src
+            .info(b"END")
+            .integer()?
+            .expect("Could not read INFO/END")[0];
+
+        // INFO/STRANDS, falling back to breakend notation or SV type.
+        let strands = match src.info(b"STRANDS").string()? {
+            Some(strands) => std::str::from_utf8(strands[0])?.to_string(),
+            None if alt_allele.contains('[') || alt_allele.contains(']') => {
+                parse_bnd_strands(&alt_allele)
+            }
+            None => match &sv_type[..] {
+                "DUP" => "-+".to_string(),
+                "INV" => "++".to_string(),
+                _ => "+-".to_string(),
+            },
+        };
+
+        // Set REF and ALT alleles.
+        if sv_type == "BND" {
+            let alt_allele =
+                build_bnd_alt(&ref_allele, std::str::from_utf8(&chr2)?, end2, &strands);
+            dst.set_alleles(&[&ref_allele.as_bytes(), &alt_allele.as_bytes()])?;
+        } else {
+            dst.set_alleles(&[&ref_allele.as_bytes(), format!("<{}>", &sv_type).as_bytes()])?;
+        }
+
+        // INFO/END2
+        dst.push_info_integer(b"END2", &[end2])?;
+        // INFO/CHR2
+        dst.push_info_string(b"CHR2", &[&chr2])?;
+        // INFO/SVTYPE
+        dst.push_info_string(b"SVTYPE", &[sv_type.as_bytes()])?;
+        // INFO/STRANDS
+        dst.push_info_string(b"STRANDS", &[strands.as_bytes()])?;
+        // INFO/SVLEN
+        if sv_type == "BND" {
+            dst.push_info_integer(b"SVLEN", &[-1])?;
+        } else if sv_type == "INS" {
+            let ins_len = match src.info(b"SVLEN").integer() {
+                Ok(Some(lens)) => lens[0].abs(),
+                _ => -1,
+            };
+            dst.push_info_integer(b"SVLEN", &[ins_len])?;
+        } else {
+            dst.push_info_integer(b"SVLEN", &[end2 - (src.pos() as i32)])?;
+        }
+        // INFO/ALGORITHMS
+        dst.push_info_string(b"ALGORITHMS", &[b"sniffles"])?;
+
+        // FORMAT/GT
+        let sample_count = dst_header.sample_count() as usize;
+        copy_genotypes(src, dst, sample_count)?;
+
+        // FORMAT/sniffles
+        dst.push_format_integer(b"sniffles", &called_from_genotypes(src, sample_count))?;
+
+        Ok(true)
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use rust_htslib::bcf;
 
 use super::error::Error;
+use super::standardize::standardizers;
 
 /// Representation of a structural variant record suitable for clustering.
 #[derive(Debug, Clone)]
@@ -86,11 +87,12 @@ impl StandardizedRecord {
         let sample_count = record.header().sample_count() as usize;
 
         let mut called_by: Vec<Vec<String>> = vec![vec![]; sample_count];
-        for algorithm in vec!["delly".to_string()] {
-            if let Ok(arr) = record.format(b"delly").integer() {
-                for i in 0..sample_count {
-                    if arr[i][0] != 0 {
-                        called_by[i].push(algorithm.clone());
+        for standardizer in standardizers() {
+            let algorithm = standardizer.name();
+            if let Ok(arr) = record.format(algorithm.as_bytes()).integer() {
+                for (i, values) in arr.iter().enumerate().take(sample_count) {
+                    if values[0] > 0 {
+                        called_by[i].push(algorithm.to_string());
                     }
                 }
             }
@@ -204,7 +206,7 @@ impl StandardizedRecord {
     }
 
     pub fn extended_interval(&self, delta: i64) -> std::ops::Range<i64> {
-        let mut tmp = if self.sv_type == "BND" {
+        let mut tmp = if self.sv_type == "BND" || self.sv_type == "INS" {
             self.pos..(self.pos + 1)
         } else {
             self.pos..self.end2
@@ -13,6 +13,9 @@ fn default_count_kind() -> String {
 fn default_window_length() -> usize {
     100
 }
+fn default_chunk_size() -> usize {
+    10_000_000
+}
 
 /// Configuration for bam-collect-doc.
 #[derive(Deserialize, Debug, Clone)]
@@ -29,6 +32,9 @@ pub struct DepthOfCoverageConfig {
     /// The window length,
     #[serde(default = "default_window_length")]
     pub window_length: usize,
+    /// Length of the chunks that contigs are split into for parallel processing.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
 }
 
 fn default_reciprocal_overlap() -> f32 {
@@ -52,6 +58,14 @@ fn default_sample_overlap() -> Option<f32> {
     Some(0.5)
 }
 
+fn default_ins_max_distance() -> i64 {
+    100
+}
+
+fn default_ins_length_similarity() -> Option<f32> {
+    Some(0.5)
+}
+
 /// Clustering configuration for vcf-cluster.
 #[derive(Deserialize, Debug, Clone)]
 pub struct ClusterSettings {
@@ -73,12 +87,34 @@ pub struct ClusterSettings {
     /// Sample overlap to require, if any.
     #[serde(default = "default_sample_overlap")]
     pub sample_overlap: Option<f32>,
+    /// Maximal distance between the positions of insertions.
+    #[serde(default = "default_ins_max_distance")]
+    pub ins_max_distance: i64,
+    /// Length similarity (shorter by longer) to require for insertions of known length, if any.
+    #[serde(default = "default_ins_length_similarity")]
+    pub ins_length_similarity: Option<f32>,
 }
 
 fn default_lib_estimation_sample_size() -> usize {
     100_000
 }
 
+fn default_lib_estimation_random_regions() -> usize {
+    1_000
+}
+
+fn default_lib_estimation_region_length() -> u64 {
+    10_000
+}
+
+fn default_lib_estimation_min_mapq() -> u8 {
+    20
+}
+
+fn default_lib_estimation_seed() -> u64 {
+    42
+}
+
 fn default_library_cutoff_deviation() -> f64 {
     0.1
 }
@@ -99,12 +135,32 @@ fn default_sliding_window_size() -> i64 {
     10_000
 }
 
-fn default_bloom_false_positive_rate() -> f32 {
-    0.0001
+fn default_mate_tracking_max_in_memory() -> usize {
+    1_000_000
+}
+
+fn default_scan_chunk_size() -> i64 {
+    50_000_000
+}
+
+fn default_scan_sort_max_in_memory() -> usize {
+    1_000_000
+}
+
+fn default_coverage_spike_factor() -> f64 {
+    0.0
+}
+
+fn default_coverage_spike_action() -> CoverageSpikeAction {
+    CoverageSpikeAction::Downsample
 }
 
-fn default_bloom_expected_read_count() -> u32 {
-    100_000_000
+fn default_collate_max_in_memory() -> usize {
+    1_000_000
+}
+
+fn default_read_ids_max_in_memory() -> usize {
+    1_000_000
 }
 
 fn default_min_clipped_bases() -> i64 {
@@ -115,10 +171,34 @@ fn default_supplementary_masked_as_secondary() -> bool {
     true
 }
 
+fn default_extract_one_end_anchored() -> bool {
+    false
+}
+
+fn default_realign_soft_clips() -> bool {
+    false
+}
+
+fn default_realign_min_clipped_bases() -> i64 {
+    30
+}
+
+fn default_realign_max_distance() -> i64 {
+    1_000
+}
+
+fn default_realign_min_identity() -> f64 {
+    0.9
+}
+
 fn default_htslib_io_threads() -> usize {
     0
 }
 
+fn default_worker_threads() -> usize {
+    1
+}
+
 fn default_stdvcf_apply_filters() -> bool {
     true
 }
@@ -131,6 +211,8 @@ fn default_vcf_cluster_presets_per_tool_pesr() -> ClusterSettings {
         match_sv_type: true,
         sv_type_out: None,
         sample_overlap: None,
+        ins_max_distance: default_ins_max_distance(),
+        ins_length_similarity: default_ins_length_similarity(),
     }
 }
 
@@ -142,6 +224,8 @@ fn default_vcf_cluster_presets_per_tool_doc() -> ClusterSettings {
         match_sv_type: false,
         sv_type_out: Some("CNV".to_string()),
         sample_overlap: None,
+        ins_max_distance: default_ins_max_distance(),
+        ins_length_similarity: default_ins_length_similarity(),
     }
 }
 
@@ -151,6 +235,7 @@ fn default_collect_doc_config() -> DepthOfCoverageConfig {
         min_unclipped: default_min_unclipped(),
         window_length: default_window_length(),
         count_kind: default_count_kind(),
+        chunk_size: default_chunk_size(),
     }
 }
 
@@ -162,10 +247,22 @@ fn default_annotate_read_evidence_slack() -> i64 {
     50
 }
 
+fn default_annotate_read_evidence_min_mapq() -> u8 {
+    0
+}
+
+fn default_annotate_read_evidence_orientation() -> bool {
+    false
+}
+
 fn default_blocked_regions_bed() -> Option<String> {
     None
 }
 
+fn default_extra_blocked_regions_beds() -> Vec<String> {
+    Vec::new()
+}
+
 fn default_doc_annotation_min_bins() -> usize {
     10
 }
@@ -178,6 +275,16 @@ fn default_annotation_doc_baf_limit() -> usize {
     1_000_000
 }
 
+/// What bam-scan does with the records of coverage spikes.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverageSpikeAction {
+    /// Skip all records starting in the window.
+    Skip,
+    /// Downsample the records starting in the window (by read name) to the depth limit.
+    Downsample,
+}
+
 /// Program configuration, from config file.
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
@@ -185,6 +292,23 @@ pub struct Config {
     #[serde(default = "default_lib_estimation_sample_size")]
     pub lib_estimation_sample_size: usize,
 
+    /// Number of random regions to sample records from for library estimation, 0 to read from
+    /// the start of the file instead.
+    #[serde(default = "default_lib_estimation_random_regions")]
+    pub lib_estimation_random_regions: usize,
+
+    /// Length of the random regions for library estimation.
+    #[serde(default = "default_lib_estimation_region_length")]
+    pub lib_estimation_region_length: u64,
+
+    /// Minimal MAPQ of records drawn from the random regions for library estimation.
+    #[serde(default = "default_lib_estimation_min_mapq")]
+    pub lib_estimation_min_mapq: u8,
+
+    /// Seed for drawing the random regions for library estimation.
+    #[serde(default = "default_lib_estimation_seed")]
+    pub lib_estimation_seed: u64,
+
     /// Library deviation to use for cut-off.
     #[serde(default = "default_library_cutoff_deviation")]
     pub library_cutoff_deviation: f64,
@@ -205,13 +329,38 @@ pub struct Config {
     #[serde(default = "default_sliding_window_size")]
     pub sliding_window_size: i64,
 
-    /// Bloom filter FPR.
-    #[serde(default = "default_bloom_false_positive_rate")]
-    pub bloom_false_positive_rate: f32,
+    /// Maximal number of read names with downstream mates to keep in memory before spilling
+    /// them to disk.
+    #[serde(default = "default_mate_tracking_max_in_memory")]
+    pub mate_tracking_max_in_memory: usize,
+
+    /// Length of the chunks that contigs are split into for parallel scanning.
+    #[serde(default = "default_scan_chunk_size")]
+    pub scan_chunk_size: i64,
+
+    /// Maximal number of extracted records to keep in memory per shard before spilling sorted
+    /// runs to disk.
+    #[serde(default = "default_scan_sort_max_in_memory")]
+    pub scan_sort_max_in_memory: usize,
+
+    /// Maximal number of records to keep in memory when collating input by query name in
+    /// bam-unique before spilling sorted runs to disk.
+    #[serde(default = "default_collate_max_in_memory")]
+    pub collate_max_in_memory: usize,
+
+    /// Maximal number of read names to keep in memory when assigning read IDs in bam-unique and
+    /// bam-collect-pesr before spilling sorted runs to disk.
+    #[serde(default = "default_read_ids_max_in_memory")]
+    pub read_ids_max_in_memory: usize,
+
+    /// Windows with a depth above this multiple of the expected coverage are coverage spikes in
+    /// bam-scan, 0 (the default) to disable.
+    #[serde(default = "default_coverage_spike_factor")]
+    pub coverage_spike_factor: f64,
 
-    /// Expected number of reads; for bloom filter.
-    #[serde(default = "default_bloom_expected_read_count")]
-    pub bloom_expected_read_count: u32,
+    /// What bam-scan does with the records of coverage spikes.
+    #[serde(default = "default_coverage_spike_action")]
+    pub coverage_spike_action: CoverageSpikeAction,
 
     /// Number of clipped bases (and maybe split aligned bases) that are interesting.
     #[serde(default = "default_min_clipped_bases")]
@@ -223,10 +372,35 @@ pub struct Config {
     #[serde(default = "default_supplementary_masked_as_secondary")]
     pub supplementary_masked_as_secondary: bool,
 
+    /// Whether to extract one-end-anchored pairs, i.e., pairs with exactly one unmapped read.
+    #[serde(default = "default_extract_one_end_anchored")]
+    pub extract_one_end_anchored: bool,
+
+    /// Whether to re-align long soft clips against the reference (`path_reference_fasta`) to
+    /// place split reads without supplementary alignment.
+    #[serde(default = "default_realign_soft_clips")]
+    pub realign_soft_clips: bool,
+
+    /// Minimal number of soft-clipped bases to re-align.
+    #[serde(default = "default_realign_min_clipped_bases")]
+    pub realign_min_clipped_bases: i64,
+
+    /// Maximal distance from the breakpoint to search for the placement of soft clips.
+    #[serde(default = "default_realign_max_distance")]
+    pub realign_max_distance: i64,
+
+    /// Minimal fraction of re-aligned soft-clipped bases that must match the reference.
+    #[serde(default = "default_realign_min_identity")]
+    pub realign_min_identity: f64,
+
     /// Number of I/O threads to use.
     #[serde(default = "default_htslib_io_threads")]
     pub htslib_io_threads: usize,
 
+    /// Number of worker threads for parallel processing.
+    #[serde(default = "default_worker_threads")]
+    pub worker_threads: usize,
+
     /// Whether or not to interpret FILTER values in stdvcf.
     #[serde(default = "default_stdvcf_apply_filters")]
     pub stdvcf_apply_filters: bool,
@@ -251,10 +425,25 @@ pub struct Config {
     #[serde(default = "default_annotate_read_evidence_slack")]
     pub annotate_read_evidence_slack: i64,
 
+    /// Minimal MAPQ of PE/SR evidence to annotate, for both alignments of PE evidence.  Records
+    /// without MAPQ are not filtered.
+    #[serde(default = "default_annotate_read_evidence_min_mapq")]
+    pub annotate_read_evidence_min_mapq: u8,
+
+    /// Whether to require the pair orientation of PE evidence to match the SV type, e.g., RF for
+    /// duplications, off by default.  Records without orientation are not filtered.
+    #[serde(default = "default_annotate_read_evidence_orientation")]
+    pub annotate_read_evidence_orientation: bool,
+
     /// Optionally, a BED file with blocked regions.
     #[serde(default = "default_blocked_regions_bed")]
     pub blocked_regions_bed: Option<String>,
 
+    /// Further BED files with blocked regions for vcf-annotate, e.g., the coverage spikes from
+    /// bam-scan.
+    #[serde(default = "default_extra_blocked_regions_beds")]
+    pub extra_blocked_regions_beds: Vec<String>,
+
     /// Minimal number of bins for DoC annotation.
     #[serde(default = "default_doc_annotation_min_bins")]
     pub doc_annotation_min_bins: usize,
@@ -0,0 +1,43 @@
+/// maelstrom -- SV analysis for oceans of genomes.
+use clap::{App, AppSettings};
+use git_version::git_version;
+
+use lib_common::cli::{global_args, setup_logging};
+use lib_common::error::Error;
+
+fn main() -> Result<(), Error> {
+    // Setup command line parser with all sub commands and parse options.
+    let matches = App::new("maelstrom")
+        .version(git_version!())
+        .author("Manuel Holtgrewe <manuel.holtgrewe@bihealth.de>")
+        .about("SV analysis for oceans of genomes")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .args(&global_args())
+        .subcommand(maelstrom_bam_scan::subcommand())
+        .subcommand(maelstrom_bam_unique::subcommand())
+        .subcommand(maelstrom_bam_collect_doc::subcommand())
+        .subcommand(maelstrom_bam_collect_pesr::subcommand())
+        .subcommand(maelstrom_bam_extract_support::subcommand())
+        .subcommand(maelstrom_pesr_convert::subcommand())
+        .subcommand(maelstrom_vcf_standardize::subcommand())
+        .subcommand(maelstrom_vcf_cluster::subcommand())
+        .subcommand(maelstrom_vcf_annotate::subcommand())
+        .get_matches();
+
+    // Setup logging verbosity and format.
+    setup_logging(&matches);
+
+    // Dispatch to the sub command.
+    match matches.subcommand() {
+        ("bam-scan", Some(m)) => maelstrom_bam_scan::run(m),
+        ("bam-unique", Some(m)) => maelstrom_bam_unique::run(m),
+        ("bam-collect-doc", Some(m)) => maelstrom_bam_collect_doc::run(m),
+        ("bam-collect-pesr", Some(m)) => maelstrom_bam_collect_pesr::run(m),
+        ("bam-extract-support", Some(m)) => maelstrom_bam_extract_support::run(m),
+        ("pesr-convert", Some(m)) => maelstrom_pesr_convert::run(m),
+        ("vcf-standardize", Some(m)) => maelstrom_vcf_standardize::run(m),
+        ("vcf-cluster", Some(m)) => maelstrom_vcf_cluster::run(m),
+        ("vcf-annotate", Some(m)) => maelstrom_vcf_annotate::run(m),
+        _ => unreachable!("clap requires a sub command"),
+    }
+}
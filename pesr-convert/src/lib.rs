@@ -0,0 +1,143 @@
+/// pesr-convert -- Convert PE/SR evidence between the TSV and the binary format.
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+
+use lib_common::cli::{check_output_exists, required_value};
+use lib_common::error::Error;
+use lib_common::read_evidence;
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// Path to input file.
+    path_input: String,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file.
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+/// Main entry point after parsing command line.
+///
+/// The input format is detected from the content, the output format is chosen by the extension
+/// as in `bam-collect-pesr`.
+fn perform_conversion(options: &Options) -> Result<(), Error> {
+    let mut reader = read_evidence::IndexedReader::from_path(&options.path_input)?;
+    let mut writer = read_evidence::Writer::from_path(&options.path_output)?;
+
+    let mut count: usize = 0;
+    for contig in reader.contigs() {
+        // Read all evidence records on the contig.
+        reader.fetch(&contig, 0, 10_000_000_000)?;
+        while let Some(record) = reader.read_record()? {
+            writer.write(&record)?;
+            count += 1;
+        }
+    }
+    writer.finish()?;
+
+    info!("Converted {} records", count);
+    Ok(())
+}
+
+/// Build the `pesr-convert` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("pesr-convert")
+        .about("Convert PE/SR evidence between the TSV and the binary format")
+        .args(&[
+            Arg::from_usage("<input> 'indexed input file to read from, TSV or binary'"),
+            Arg::from_usage(
+                "<output> 'output file to write to, binary for .mev, bgzipped TSV for .gz'",
+            ),
+        ])
+}
+
+/// Run the `pesr-convert` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output file must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    info!("Starting maelstrom pesr-convert");
+    info!("options: {:?}", &options);
+
+    perform_conversion(&options)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_common::read_evidence::binary;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that runs `perform_conversion()`.
+    fn _perform_conversion(path_input: &str, path_output: &str) -> Result<(), super::Error> {
+        let options = super::Options {
+            verbosity: 1,
+            path_config: None,
+            path_input: String::from(path_input),
+            path_output: String::from(path_output),
+            overwrite: false,
+        };
+        super::perform_conversion(&options)
+    }
+
+    #[test]
+    fn convert_tsv_to_binary_and_back() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_binary = String::from(tmp_dir.path().join("out.mev").to_str().unwrap());
+        let path_tsv = String::from(tmp_dir.path().join("out.tsv").to_str().unwrap());
+
+        _perform_conversion("./src/tests/data/ex-pesr.tsv.gz", &path_binary)?;
+        assert!(binary::is_binary(&path_binary)?);
+        _perform_conversion(&path_binary, &path_tsv)?;
+
+        assert_eq!(
+            fs::read_to_string("./src/tests/data/ex-pesr.tsv").unwrap(),
+            fs::read_to_string(&path_tsv).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn convert_binary_to_bgzipped_tsv() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        let path_binary = String::from(tmp_dir.path().join("out.mev").to_str().unwrap());
+        let path_bgzip = String::from(tmp_dir.path().join("out.tsv.gz").to_str().unwrap());
+        let path_tsv = String::from(tmp_dir.path().join("out.tsv").to_str().unwrap());
+
+        _perform_conversion("./src/tests/data/ex-pesr.tsv.gz", &path_binary)?;
+        _perform_conversion(&path_binary, &path_bgzip)?;
+        assert!(!binary::is_binary(&path_bgzip)?);
+        _perform_conversion(&path_bgzip, &path_tsv)?;
+
+        assert_eq!(
+            fs::read_to_string("./src/tests/data/ex-pesr.tsv").unwrap(),
+            fs::read_to_string(&path_tsv).unwrap()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1762 @@
+/// vcf-annotate -- Create annotations for VCF file with SVs.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::prelude::*;
+
+use bio::data_structures::annot_map::AnnotMap;
+use bio_types::annot::contig::Contig;
+use bio_types::annot::contig::SeqContigStranded;
+use bio_types::annot::loc::Loc;
+use bio_types::genome::{AbstractInterval, Interval};
+use bio_types::strand::NoStrand;
+use bio_types::strand::ReqStrand;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use itertools::Itertools;
+use log::{debug, info};
+use rust_htslib::{bcf, bcf::record::Numeric, bcf::Read};
+
+use lib_common::bcf::{build_vcf_header, collect_contigs, guess_bcf_format, sample_index};
+use lib_common::beds_to_annot_map;
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
+use lib_common::doc::{load_doc_median, MedianReadDepthInfo};
+use lib_common::error::Error;
+use lib_common::read_evidence;
+use lib_common::read_evidence::PairOrientation;
+use lib_common::read_evidence::Sides;
+use lib_common::read_evidence::Strand;
+use lib_common::stats::Stats;
+use lib_common::sv;
+use lib_config::Config;
+
+/// Input files for one sample of the input VCF file.
+#[derive(Debug, Clone, PartialEq)]
+struct SampleInputs {
+    /// Name of the sample in the input VCF file.
+    sample: String,
+    /// Path to input PE/SR evidence file.
+    path_pesr_evidence: Option<String>,
+    /// Path to input DoC evidence file.
+    path_doc_evidence: Option<String>,
+    /// Path to input small variant VCF.
+    path_snv_vcf: Option<String>,
+}
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// List of regions to call.
+    regions: Option<Vec<Interval>>,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// Input files for each sample to annotate for.
+    samples: Vec<SampleInputs>,
+    /// Path to output per-contig DoC file.
+    path_out_doc_summary: Option<String>,
+    /// Path to output SNV BAF info file.
+    path_out_baf_snvs: Option<String>,
+    /// Path to output file with the IDs of the reads supporting each SV.
+    path_out_supporting_reads: Option<String>,
+    /// Path to input VCF file.
+    path_input: String,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file(s).
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        let samples = if let Some(path_sample_sheet) = matches.value_of("sample-sheet") {
+            load_sample_sheet(path_sample_sheet)?
+        } else {
+            vec![SampleInputs {
+                sample: required_value(matches, "sample")?,
+                path_pesr_evidence: matches
+                    .value_of("path-pesr-evidence")
+                    .map(|s| s.to_string()),
+                path_doc_evidence: matches.value_of("path-doc-evidence").map(|s| s.to_string()),
+                path_snv_vcf: matches.value_of("path-snv-vcf").map(|s| s.to_string()),
+            }]
+        };
+
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            regions: parse_regions(matches)?,
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            samples,
+            path_out_doc_summary: matches
+                .value_of("path-out-doc-summary")
+                .map(|s| s.to_string()),
+            path_out_baf_snvs: matches.value_of("path-out-baf-snvs").map(|s| s.to_string()),
+            path_out_supporting_reads: matches
+                .value_of("path-out-supporting-reads")
+                .map(|s| s.to_string()),
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+/// Load sample sheet.
+///
+/// The sample sheet is a TSV file with the columns sample name, path to PE/SR evidence, path to
+/// DoC evidence, and path to SNV VCF.  Missing files are given as `.`, lines starting with `#` are
+/// ignored.
+fn load_sample_sheet(path: &str) -> Result<Vec<SampleInputs>, Error> {
+    info!("Loading sample sheet {}", &path);
+    let optional_path = |s: &str| {
+        if s.is_empty() || s == "." {
+            None
+        } else {
+            Some(s.to_string())
+        }
+    };
+
+    let mut result = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let arr: Vec<&str> = line.split('\t').collect();
+        if arr.len() != 4 {
+            return Err(Error::InvalidSampleSheet(format!(
+                "Unexpected number of fields in {} (must be 4)",
+                &line
+            )));
+        }
+        result.push(SampleInputs {
+            sample: arr[0].to_string(),
+            path_pesr_evidence: optional_path(arr[1]),
+            path_doc_evidence: optional_path(arr[2]),
+            path_snv_vcf: optional_path(arr[3]),
+        });
+    }
+    info!("=> {} samples", result.len());
+
+    if result.is_empty() {
+        Err(Error::InvalidSampleSheet(
+            "No samples in sample sheet".to_string(),
+        ))
+    } else {
+        Ok(result)
+    }
+}
+
+/// Load all evidence records for the given region.
+fn load_read_evidence(
+    annot_map: &mut AnnotMap<String, read_evidence::Record>,
+    interval: &Interval,
+    path_pesr_evidence: &str,
+    options: &Options,
+    blocked: &Option<AnnotMap<String, ()>>,
+) -> Result<usize, Error> {
+    let mut skipped = 0;
+
+    info!("loading evidence for {:?}...", interval);
+    let mut reader = read_evidence::IndexedReader::from_path(&path_pesr_evidence)?;
+    if !reader.fetch(
+        &interval.contig(),
+        interval.range().start,
+        interval.range().end,
+    )? {
+        return Ok(skipped);
+    }
+
+    let spinner_style = ProgressStyle::default_spinner()
+        .tick_chars(".oO@* ")
+        .template("{prefix:.bold.dim} {spinner} {msg} {elapsed}");
+    let spinner = if options.verbosity == 0 {
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_style(spinner_style);
+        spinner.enable_steady_tick(100);
+        Some(spinner)
+    } else {
+        None
+    };
+
+    let mut counter: usize = 0;
+
+    while let Some(record) = reader.read_record()? {
+        debug!("record = {:?}", &record);
+        let location = match &record {
+            read_evidence::Record::PairedRead {
+                contig1,
+                start1,
+                end1,
+                ..
+            } => Contig::new(
+                contig1.clone(),
+                *start1 as isize,
+                (end1 - start1) as usize,
+                NoStrand::Unknown,
+            ),
+            read_evidence::Record::SplitRead {
+                contig, start, end, ..
+            }
+            | read_evidence::Record::AnchoredRead {
+                contig, start, end, ..
+            } => Contig::new(
+                contig.clone(),
+                *start as isize,
+                (end - start) as usize,
+                NoStrand::Unknown,
+            ),
+        };
+        let skip = if let Some(blocked) = blocked {
+            blocked.find(&location).next().is_some()
+        } else {
+            false
+        };
+        if !skip {
+            annot_map.insert_at(record, &location);
+        } else {
+            skipped += 1;
+            debug!("skipping!");
+        }
+
+        counter += 1;
+        if counter % 1_000 == 0 {
+            if let Some(spinner) = &spinner {
+                spinner.set_message(&format!("currently at {}", &location.contig(),));
+            }
+        }
+    }
+    if let Some(spinner) = &spinner {
+        spinner.finish_and_clear();
+    }
+    info!("... done loading evidence for {:?}", &interval);
+
+    Ok(skipped)
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord)]
+struct ReadEvidenceCount {
+    sv_id: String,
+    pe_count: usize,
+    sr_count: usize,
+    /// IDs of the read pairs giving PE evidence.
+    pe_reads: BTreeSet<i64>,
+    /// IDs of the reads giving SR evidence.
+    sr_reads: BTreeSet<i64>,
+    /// Most common split read breakpoint near POS, if any.
+    sr_pos: Option<i64>,
+    /// Most common split read breakpoint near END, if any.
+    sr_end: Option<i64>,
+}
+
+/// Split read alignment found near one side of an SV.
+#[derive(Debug, Clone, PartialEq)]
+struct SplitReadHit {
+    /// Breakpoint position on the alignment, if known.
+    breakpoint: Option<i64>,
+    /// Placement of the other part of the read, if known.
+    partner: Option<read_evidence::SplitPartner>,
+}
+
+impl SplitReadHit {
+    /// Return `Some` with the partner's breakpoint if the partner supports the `query` side.
+    ///
+    /// The partner must overlap `query` and, if its breakpoint is known, be clipped on the side
+    /// given by the query's strand.
+    fn partner_in(&self, query: &SeqContigStranded) -> Option<Option<i64>> {
+        let partner = self.partner.as_ref()?;
+        let overlaps = &partner.contig == query.refid()
+            && partner.start < (query.start() + query.length() as isize) as i64
+            && partner.end > query.start() as i64;
+        let side_matches = match (query.strand(), partner.breakpoint) {
+            (_, None) => true,
+            (ReqStrand::Forward, Some(breakpoint)) => breakpoint == partner.end,
+            (ReqStrand::Reverse, Some(breakpoint)) => breakpoint == partner.start,
+        };
+        if overlaps && side_matches {
+            Some(partner.breakpoint)
+        } else {
+            None
+        }
+    }
+}
+
+/// Split read breakpoints near POS and END, by read ID and first-in-pair flag.
+type SplitReadBreakpoints = HashMap<(i64, bool), (Option<i64>, Option<i64>)>;
+
+/// Return the most common of `values`, the smallest one on ties.
+fn most_common<I: Iterator<Item = i64>>(values: I) -> Option<i64> {
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(value, count)| (*count, std::cmp::Reverse(*value)))
+        .map(|(value, _)| value)
+}
+
+/// Filters for the PE/SR evidence records of one search.
+#[derive(Debug, Clone, Default)]
+struct EvidenceFilter {
+    /// Minimal MAPQ of the alignments, records without MAPQ pass.
+    min_mapq: u8,
+    /// Required orientation class of read pairs, records without orientation pass.
+    orientation: Option<PairOrientation>,
+}
+
+impl EvidenceFilter {
+    /// Whether the alignment with `mapq` passes.
+    fn passes_mapq(&self, mapq: &Option<u8>) -> bool {
+        mapq.map_or(true, |mapq| mapq >= self.min_mapq)
+    }
+
+    /// Whether the read pair with `orientation` passes.
+    fn passes_orientation(&self, orientation: &Option<PairOrientation>) -> bool {
+        match (self.orientation, orientation) {
+            (Some(expected), Some(orientation)) => expected == *orientation,
+            _ => true,
+        }
+    }
+}
+
+/// Return PR read names and SR hits passing `filter`.
+fn fetch_read_evidence(
+    query: &SeqContigStranded,
+    read_evidence: &AnnotMap<String, read_evidence::Record>,
+    blocked: &Option<AnnotMap<String, ()>>,
+    filter: &EvidenceFilter,
+) -> (HashSet<i64>, HashMap<(i64, bool), SplitReadHit>) {
+    debug!("  fetch_read_evidence");
+    let mut prs = HashSet::new();
+    let mut srs = HashMap::new();
+
+    debug!("    finding {:?}", &query);
+    debug!("    #found = {}", read_evidence.find(query).count());
+
+    for record in read_evidence.find(query) {
+        match record.data() {
+            read_evidence::Record::PairedRead {
+                read_id,
+                strand1,
+                contig1,
+                start1,
+                contig2,
+                start2,
+                mapq,
+                mate_mapq,
+                orientation,
+                ..
+            } => {
+                debug!("    [[PR]] read_id = {}, strand1 = {:?}", &read_id, strand1);
+                if !filter.passes_mapq(mapq)
+                    || !filter.passes_mapq(mate_mapq)
+                    || !filter.passes_orientation(orientation)
+                {
+                    continue;
+                }
+                match (query.strand(), strand1) {
+                    (ReqStrand::Forward, Strand::Forward)
+                    | (ReqStrand::Reverse, Strand::Reverse) => {
+                        let skip = if let Some(blocked) = blocked {
+                            let location1 = Contig::new(
+                                contig1.clone(),
+                                *start1 as isize,
+                                1,
+                                NoStrand::Unknown,
+                            );
+                            let skip1 = blocked.find(&location1).next().is_some();
+                            let skip2 = if let Some(start2) = start2 {
+                                let location2 = Contig::new(
+                                    contig2.clone().unwrap(),
+                                    *start2 as isize,
+                                    1,
+                                    NoStrand::Unknown,
+                                );
+                                blocked.find(&location2).next().is_some()
+                            } else {
+                                false
+                            };
+                            skip1 || skip2
+                        } else {
+                            false
+                        };
+                        if !skip {
+                            prs.insert(*read_id);
+                        }
+                    }
+                    _ => (), // ignored; no strand match
+                }
+            }
+            read_evidence::Record::SplitRead {
+                read_id,
+                clipped_sides,
+                is_first,
+                contig,
+                start,
+                breakpoint,
+                partner,
+                mapq,
+                ..
+            } => {
+                debug!(
+                    "    [[SR]] read_id = {}, clipped_sides = {:?}",
+                    &read_id, clipped_sides
+                );
+                if !filter.passes_mapq(mapq) {
+                    continue;
+                }
+                match (query.strand(), clipped_sides) {
+                    (ReqStrand::Forward, Sides::Both)
+                    | (ReqStrand::Reverse, Sides::Both)
+                    | (ReqStrand::Forward, Sides::Right)
+                    | (ReqStrand::Reverse, Sides::Left) => {
+                        let skip = if let Some(blocked) = blocked {
+                            let location =
+                                Contig::new(contig.clone(), *start as isize, 1, NoStrand::Unknown);
+                            blocked.find(&location).next().is_some()
+                        } else {
+                            false
+                        };
+                        if !skip {
+                            srs.insert(
+                                (*read_id, *is_first),
+                                SplitReadHit {
+                                    breakpoint: *breakpoint,
+                                    partner: partner.clone(),
+                                },
+                            );
+                        }
+                    }
+                    _ => (), // ignored; no side match
+                }
+            }
+            read_evidence::Record::AnchoredRead { .. } => (), // not counted as PE/SR evidence
+        }
+    }
+
+    (prs, srs)
+}
+
+/// Return PE and SR evidence supporting both sides.
+///
+/// A split read supports both sides if it has been found on both sides or if the other part
+/// given by its `SA` tag or re-alignment lies on the other side.
+fn supporting_evidence(
+    left: &SeqContigStranded,
+    right: &SeqContigStranded,
+    read_evidence: &AnnotMap<String, read_evidence::Record>,
+    blocked: &Option<AnnotMap<String, ()>>,
+    filter: &EvidenceFilter,
+) -> (HashSet<i64>, SplitReadBreakpoints) {
+    let (left_prs, left_srs) = fetch_read_evidence(left, read_evidence, blocked, filter);
+    let (right_prs, right_srs) = fetch_read_evidence(right, read_evidence, blocked, filter);
+    debug!("supporting_evidence");
+    debug!("  left_prs = {:?}", &left_prs);
+    debug!("  left_srs = {:?}", &left_srs);
+    debug!("  right_prs = {:?}", &right_prs);
+    debug!("  right_srs = {:?}", &right_srs);
+    debug!("------");
+    debug!(
+        "  both prs = {:?}",
+        &left_prs
+            .intersection(&right_prs)
+            .cloned()
+            .collect::<Vec<i64>>()
+    );
+    debug!(
+        "  both prs = {:?}",
+        &left_prs.intersection(&right_prs).count()
+    );
+
+    let mut srs = SplitReadBreakpoints::new();
+    for (key, hit) in &left_srs {
+        if let Some(other) = right_srs.get(key) {
+            srs.insert(*key, (hit.breakpoint, other.breakpoint));
+        } else if let Some(partner_breakpoint) = hit.partner_in(right) {
+            srs.insert(*key, (hit.breakpoint, partner_breakpoint));
+        }
+    }
+    for (key, hit) in &right_srs {
+        if !srs.contains_key(key) {
+            if let Some(partner_breakpoint) = hit.partner_in(left) {
+                srs.insert(*key, (partner_breakpoint, hit.breakpoint));
+            }
+        }
+    }
+
+    (left_prs.intersection(&right_prs).cloned().collect(), srs)
+}
+
+/// Return SR evidence for insertions.
+///
+/// Reads spanning into an insertion are clipped at the insertion site, either on the right side
+/// (left of the site) or on the left side (right of the site).  In contrast to the other SV types
+/// the two sides are not supported by the same reads, so the union is returned.  All breakpoints
+/// are at the insertion site and are returned as the ones near POS.
+fn supporting_insertion_evidence(
+    left: &SeqContigStranded,
+    right: &SeqContigStranded,
+    read_evidence: &AnnotMap<String, read_evidence::Record>,
+    blocked: &Option<AnnotMap<String, ()>>,
+    filter: &EvidenceFilter,
+) -> SplitReadBreakpoints {
+    let (_, left_srs) = fetch_read_evidence(left, read_evidence, blocked, filter);
+    let (_, right_srs) = fetch_read_evidence(right, read_evidence, blocked, filter);
+    debug!("supporting_insertion_evidence");
+    debug!("  left_srs = {:?}", &left_srs);
+    debug!("  right_srs = {:?}", &right_srs);
+
+    left_srs
+        .into_iter()
+        .chain(right_srs.into_iter())
+        .map(|(key, hit)| (key, (hit.breakpoint, None)))
+        .collect()
+}
+
+/// Perform PE/SR annotation of SV.
+fn annotate_pesr(
+    options: &Options,
+    config: &Config,
+    read_evidence: &AnnotMap<String, read_evidence::Record>,
+    region: &Interval,
+    blocked: &Option<AnnotMap<String, ()>>,
+) -> Result<Vec<ReadEvidenceCount>, Error> {
+    fn f(a: isize, b: isize) -> isize {
+        if b > a {
+            0
+        } else {
+            a - b
+        }
+    }
+
+    let mut reader = bcf::IndexedReader::from_path(&options.path_input)?;
+    let res = reader.fetch(
+        reader.header().name2rid(region.contig().as_bytes())?,
+        region.range().start,
+        region.range().end,
+    );
+    match res {
+        Err(rust_htslib::bcf::errors::Error::Seek { .. }) => return Ok(vec![]),
+        Err(_) => res?,
+        Ok(_) => (),
+    }
+
+    let mut record = reader.empty_record();
+    let mut result = Vec::new();
+    while reader.read(&mut record)? {
+        let sv_id = std::str::from_utf8(&record.id())?.to_string();
+        debug!(">>> sv_id = {}", &sv_id);
+        let record = sv::StandardizedRecord::from_bcf_record(&mut record)?;
+        // Define a couple of shortcuts to make the matching code below more compact.
+        use ReqStrand::{Forward, Reverse};
+        let slack = config.annotate_read_evidence_slack as usize;
+        let slacki = config.annotate_read_evidence_slack as isize;
+        let delta = config.annotate_read_evidence_max_dist as usize;
+        let deltai = delta as isize;
+        let chrom = &record.chrom;
+        let chrom2 = &record.chrom2;
+        let pos = record.pos as isize;
+        let end2 = record.end2 as isize;
+        let mut pe_count = 0;
+        let mut sr_count = 0;
+        let mut pe_reads = BTreeSet::new();
+        let mut sr_reads = BTreeSet::new();
+        let mut sr_breakpoints = Vec::new();
+
+        let search_wheres = match (&record.sv_type[..], &record.strands[..]) {
+            ("DEL", _) => vec![(
+                SeqContigStranded::new(chrom.clone(), f(pos, deltai), delta + slack, Forward),
+                SeqContigStranded::new(chrom2.clone(), end2 - slacki, delta + slack, Reverse),
+                Some(PairOrientation::FR),
+            )],
+            ("DUP", _) => vec![(
+                SeqContigStranded::new(chrom.clone(), pos - slacki, delta + slack, Reverse),
+                SeqContigStranded::new(chrom2.clone(), end2 - deltai, delta + slack, Forward),
+                Some(PairOrientation::RF),
+            )],
+            ("INV", _) => vec![
+                (
+                    SeqContigStranded::new(chrom.clone(), f(pos, deltai), delta + slack, Forward),
+                    SeqContigStranded::new(chrom2.clone(), f(end2, deltai), delta + slack, Forward),
+                    Some(PairOrientation::FF),
+                ),
+                (
+                    SeqContigStranded::new(chrom.clone(), pos - slacki, delta + slack, Reverse),
+                    SeqContigStranded::new(chrom2.clone(), f(end2, deltai), delta + slack, Reverse),
+                    Some(PairOrientation::RR),
+                ),
+            ],
+            // Breakends may join contigs in either order, so the orientation is not checked.
+            ("BND", "--") | ("BND", "++") => vec![(
+                SeqContigStranded::new(chrom.clone(), pos - slacki, delta + slack, Reverse),
+                SeqContigStranded::new(chrom2.clone(), end2 - slacki, delta + slack, Reverse),
+                None,
+            )],
+            ("BND", "-+") => vec![(
+                SeqContigStranded::new(chrom.clone(), pos - slacki, delta + slack, Reverse),
+                SeqContigStranded::new(chrom2.clone(), f(end2, deltai), delta + slack, Reverse),
+                None,
+            )],
+            ("BND", "+-") => vec![(
+                SeqContigStranded::new(chrom.clone(), f(pos, deltai), delta + slack, Forward),
+                SeqContigStranded::new(chrom2.clone(), end2 - slacki, delta + slack, Reverse),
+                None,
+            )],
+            ("INS", _) => vec![], // handled below
+            _ => panic!(format!(
+                "Unknown SV/strands combination: {}/{}",
+                &record.sv_type, &record.strands
+            )),
+        };
+
+        debug!("Search where: {:?}", &search_wheres);
+
+        for (left, right, orientation) in &search_wheres {
+            debug!(">>>>> searching: {}/{}", &left, &right);
+            let filter = EvidenceFilter {
+                min_mapq: config.annotate_read_evidence_min_mapq,
+                orientation: orientation.filter(|_| config.annotate_read_evidence_orientation),
+            };
+            let (pe, sr) = supporting_evidence(left, right, read_evidence, blocked, &filter);
+            debug!(">>>>> pe = {}, sr = {}", pe.len(), sr.len());
+            pe_count += pe.len();
+            sr_count += sr.len();
+            pe_reads.extend(pe);
+            sr_reads.extend(sr.keys().map(|(read_id, _)| *read_id));
+            sr_breakpoints.extend(sr.values().cloned());
+        }
+        if record.sv_type == "INS" {
+            let sr = supporting_insertion_evidence(
+                &SeqContigStranded::new(chrom.clone(), f(pos, deltai), delta + slack, Forward),
+                &SeqContigStranded::new(chrom.clone(), pos - slacki, delta + slack, Reverse),
+                read_evidence,
+                blocked,
+                &EvidenceFilter {
+                    min_mapq: config.annotate_read_evidence_min_mapq,
+                    orientation: None,
+                },
+            );
+            sr_count += sr.len();
+            sr_reads.extend(sr.keys().map(|(read_id, _)| *read_id));
+            sr_breakpoints.extend(sr.values().cloned());
+        }
+
+        result.push(ReadEvidenceCount {
+            sv_id,
+            pe_count,
+            sr_count,
+            pe_reads,
+            sr_reads,
+            sr_pos: most_common(sr_breakpoints.iter().filter_map(|(pos, _)| *pos)),
+            sr_end: most_common(sr_breakpoints.iter().filter_map(|(_, end)| *end)),
+        });
+    }
+
+    Ok(result)
+}
+
+/// Coverage evidence information for a structural variant.
+#[derive(Debug, PartialEq, PartialOrd)]
+struct CoverageEvidence {
+    /// ID of the called SV
+    sv_id: String,
+    /// Normalized coverage
+    norm_cov: f64,
+}
+
+struct DocWindow {
+    cov: f64,
+    mapq: f64,
+}
+
+/// Perform DoC annotation of SV.
+fn annotate_doc(
+    options: &Options,
+    config: &Config,
+    region: &Interval,
+    inputs: &SampleInputs,
+    median_doc: f64,
+) -> Result<Vec<Option<CoverageEvidence>>, Error> {
+    let mut reader = bcf::IndexedReader::from_path(&options.path_input)?;
+    let res = reader.fetch(
+        reader.header().name2rid(region.contig().as_bytes())?,
+        region.range().start,
+        region.range().end,
+    );
+    match res {
+        Err(rust_htslib::bcf::errors::Error::Seek { .. }) => return Ok(vec![]),
+        Err(_) => res?,
+        Ok(_) => (),
+    }
+
+    let mut doc_reader = bcf::IndexedReader::from_path(inputs.path_doc_evidence.clone().unwrap())?;
+    let doc_idx = sample_index(doc_reader.header(), &inputs.sample)?;
+    let mut doc_record = doc_reader.empty_record();
+
+    let mut record = reader.empty_record();
+    let mut result = Vec::new();
+    while reader.read(&mut record)? {
+        let sv_id = std::str::from_utf8(&record.id())?.to_string();
+        debug!(">>> sv_id = {}", &sv_id);
+        let record = sv::StandardizedRecord::from_bcf_record(&mut record)?;
+
+        let cov_evidence =
+            if record.sv_type == "DEL" || record.sv_type == "DUP" || record.sv_type == "CNV" {
+                let annotation_doc_baf_limit = config.annotation_doc_baf_limit as i64;
+                let (start, end) = if record.end2 - record.pos > annotation_doc_baf_limit as i64 {
+                    let shift =
+                        (record.end2 - record.pos - annotation_doc_baf_limit as i64) as u64 / 2;
+                    (record.pos as u64 + shift, record.end2 as u64 - shift)
+                } else {
+                    (record.pos as u64, record.end2 as u64)
+                };
+
+                let rid: u32 = doc_reader.header().name2rid(record.chrom.as_bytes())?;
+                if doc_reader.fetch(rid, start, end).is_ok() {
+                    let mut doc_windows = Vec::new();
+                    while doc_reader.read(&mut doc_record)? {
+                        doc_windows.push(DocWindow {
+                            cov: doc_record.format(b"RCV").float()?[doc_idx][0].into(),
+                            mapq: doc_record.format(b"MQ").float()?[doc_idx][0].into(),
+                        });
+                    }
+
+                    let min_mapq = 55.0;
+                    let q0_windows = doc_windows.iter().filter(|w| w.mapq >= min_mapq).count();
+                    let do_filter = q0_windows >= config.doc_annotation_min_bins;
+                    let covs: Vec<f64> = doc_windows
+                        .iter()
+                        .filter(|w| !do_filter || w.mapq >= min_mapq)
+                        .map(|w| w.cov)
+                        .collect();
+
+                    if covs.is_empty() {
+                        None
+                    } else {
+                        Some(CoverageEvidence {
+                            sv_id,
+                            norm_cov: covs.median() / median_doc,
+                        })
+                    }
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+        result.push(cov_evidence);
+    }
+
+    Ok(result)
+}
+
+/// Het SNV frequency / count evidence
+#[derive(Debug, PartialEq, PartialOrd)]
+struct SNVEvidence {
+    /// Number of SNVs in left flanking region.
+    snvs_left: i32,
+    /// Number of SNVs within region.
+    snvs_within: i32,
+    /// Number of SNVs in right flanking region.
+    snvs_right: i32,
+}
+
+/// Perform BAF annotation of SV.
+fn annotate_snv(
+    options: &Options,
+    config: &Config,
+    region: &Interval,
+    inputs: &SampleInputs,
+    file_out_baf_snvs: &mut Option<fs::File>,
+) -> Result<Vec<Option<SNVEvidence>>, Error> {
+    let mut reader = bcf::IndexedReader::from_path(&options.path_input)?;
+    let res = reader.fetch(
+        reader.header().name2rid(region.contig().as_bytes())?,
+        region.range().start,
+        region.range().end,
+    );
+    match res {
+        Err(rust_htslib::bcf::errors::Error::Seek { .. }) => return Ok(vec![]),
+        Err(_) => res?,
+        Ok(_) => (),
+    }
+
+    let mut baf_reader = bcf::IndexedReader::from_path(inputs.path_snv_vcf.clone().unwrap())?;
+    let snv_idx = sample_index(baf_reader.header(), &inputs.sample)?;
+    let mut baf_record = baf_reader.empty_record();
+
+    let mut record = reader.empty_record();
+    let mut result = Vec::new();
+    while reader.read(&mut record)? {
+        let record = sv::StandardizedRecord::from_bcf_record(&mut record)?;
+
+        let snv_evidence =
+            if record.sv_type == "DEL" || record.sv_type == "DUP" || record.sv_type == "CNV" {
+                let rid: u32 = baf_reader.header().name2rid(record.chrom.as_bytes())?;
+                let annotation_doc_baf_limit = config.annotation_doc_baf_limit as i64;
+
+                let (start, end) = if record.end2 - record.pos > annotation_doc_baf_limit as i64 {
+                    let shift =
+                        (record.end2 - record.pos - annotation_doc_baf_limit as i64) as u64 / 2;
+                    (record.pos as u64 + shift, record.end2 as u64 - shift)
+                } else {
+                    (record.pos as u64, record.end2 as u64)
+                };
+                let length = end - start;
+
+                // Count SNVs left of CNV.
+                let mut snvs_left: i32 = 0;
+                if baf_reader.fetch(rid, start - length, start).is_ok() {
+                    while baf_reader.read(&mut baf_record)? {
+                        let genotype: bcf::record::Genotype =
+                            baf_record.genotypes().unwrap().get(snv_idx);
+                        let gt0 = genotype.get(0).unwrap().index().unwrap_or(0) as usize;
+                        let gt1 = genotype.get(1).unwrap().index().unwrap_or(0) as usize;
+                        if baf_record.alleles().len() == 2  // only biallelic SNVs
+                            && baf_record.alleles()[0].len() == 1
+                            && baf_record.alleles()[1].len() == 1
+                            && gt0 != gt1
+                        {
+                            snvs_left += 1;
+                        }
+                    }
+                }
+
+                // Count SNVs right of CNV.
+                let mut snvs_right: i32 = 0;
+                if baf_reader.fetch(rid, start - length, start).is_ok() {
+                    while baf_reader.read(&mut baf_record)? {
+                        let genotype: bcf::record::Genotype =
+                            baf_record.genotypes().unwrap().get(snv_idx);
+                        let gt0 = genotype.get(0).unwrap().index().unwrap_or(0) as usize;
+                        let gt1 = genotype.get(1).unwrap().index().unwrap_or(0) as usize;
+                        if baf_record.alleles().len() == 2  // only biallelic SNVs
+                            && baf_record.alleles()[0].len() == 1
+                            && baf_record.alleles()[1].len() == 1
+                            && gt0 != gt1
+                        {
+                            snvs_right += 1;
+                        }
+                    }
+                }
+
+                if (record.sv_type == "DEL" || record.sv_type == "DUP" || record.sv_type == "CNV")
+                    && baf_reader.fetch(rid, start, end).is_ok()
+                {
+                    let mut bafs = Vec::new();
+                    while baf_reader.read(&mut baf_record)? {
+                        let genotype: bcf::record::Genotype =
+                            baf_record.genotypes().unwrap().get(snv_idx);
+                        let gt0 = genotype.get(0).unwrap().index().unwrap_or(0) as usize;
+                        let gt1 = genotype.get(1).unwrap().index().unwrap_or(0) as usize;
+                        if baf_record.alleles().len() == 2  // only biallelic SNVs
+                            && baf_record.alleles()[0].len() == 1
+                            && baf_record.alleles()[1].len() == 1
+                            && gt0 != gt1
+                        {
+                            let a0 = baf_record.format(b"AD").integer()?[snv_idx][gt0] as f64;
+                            let a1 = baf_record.format(b"AD").integer()?[snv_idx][gt1] as f64;
+                            let baf = a1 / (a0 + a1);
+                            debug!("{} -- {} // {}", baf_record.pos(), a0, a1);
+                            bafs.push(baf);
+                        }
+                    }
+
+                    if record.sv_type == "DUP" || record.sv_type == "CNV" {
+                        if let Some(file_out_baf_snvs) = file_out_baf_snvs {
+                            file_out_baf_snvs.write_all(
+                                format!(
+                                    "{}\t{}\t{}\n",
+                                    &record.sv_id,
+                                    &inputs.sample,
+                                    bafs.iter().map(|x| format!("{:.5}", x)).join(";"),
+                                )
+                                .as_bytes(),
+                            )?;
+                        }
+                    }
+
+                    Some(SNVEvidence {
+                        snvs_left,
+                        snvs_right,
+                        snvs_within: bafs.len() as i32,
+                    })
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+        result.push(snv_evidence);
+    }
+
+    Ok(result)
+}
+
+/// Write annotated variants.
+///
+/// The evidence is given for each sample of the sample sheet, `sample_idxs` gives the index of
+/// each such sample in the input VCF.  Samples without evidence get missing values.
+#[allow(clippy::too_many_arguments)]
+fn write_annotated(
+    options: &Options,
+    _config: &Config,
+    region: &Interval,
+    sample_idxs: &[usize],
+    read_evidence: &[Option<Vec<ReadEvidenceCount>>],
+    doc_evidence: &[Option<Vec<Option<CoverageEvidence>>>],
+    baf_evidence: &[Option<Vec<Option<SNVEvidence>>>],
+    writer: &mut bcf::Writer,
+) -> Result<(), Error> {
+    let mut reader = bcf::IndexedReader::from_path(&options.path_input)?;
+    let res = reader.fetch(
+        reader.header().name2rid(region.contig().as_bytes())?,
+        region.range().start,
+        region.range().end,
+    );
+    match res {
+        Err(rust_htslib::bcf::errors::Error::Seek { .. }) => return Ok(()),
+        Err(_) => res?,
+        Ok(_) => (),
+    }
+    let sample_count = reader.header().sample_count() as usize;
+
+    let mut idx = 0;
+    let mut record = reader.empty_record();
+    while reader.read(&mut record)? {
+        writer.translate(&mut record);
+
+        if read_evidence.iter().any(|evidence| evidence.is_some()) {
+            let mut prs = vec![f32::missing(); sample_count];
+            let mut srs = vec![f32::missing(); sample_count];
+            let mut sr_poss = vec![i32::missing(); sample_count];
+            let mut sr_ends = vec![i32::missing(); sample_count];
+            let mut any_sr_pos = false;
+            let mut any_sr_end = false;
+            for (sample_idx, evidence) in sample_idxs.iter().zip(read_evidence) {
+                if let Some(evidence) = evidence {
+                    let elem = evidence.get(idx).unwrap();
+                    prs[*sample_idx] = elem.pe_count as f32;
+                    srs[*sample_idx] = elem.sr_count as f32;
+                    if let Some(sr_pos) = elem.sr_pos {
+                        sr_poss[*sample_idx] = sr_pos as i32;
+                        any_sr_pos = true;
+                    }
+                    if let Some(sr_end) = elem.sr_end {
+                        sr_ends[*sample_idx] = sr_end as i32;
+                        any_sr_end = true;
+                    }
+                }
+            }
+            record.push_format_float(b"PR", &prs)?;
+            record.push_format_float(b"SR", &srs)?;
+            if any_sr_pos {
+                record.push_format_integer(b"SRPOS", &sr_poss)?;
+            }
+            if any_sr_end {
+                record.push_format_integer(b"SREND", &sr_ends)?;
+            }
+        }
+
+        let mut rds = vec![f32::missing(); sample_count];
+        let mut any_rd = false;
+        for (sample_idx, evidence) in sample_idxs.iter().zip(doc_evidence) {
+            if let Some(evidence) = evidence {
+                if let Some(elem) = evidence.get(idx).unwrap() {
+                    rds[*sample_idx] = elem.norm_cov as f32;
+                    any_rd = true;
+                }
+            }
+        }
+        if any_rd {
+            record.push_format_float(b"RD", &rds)?;
+        }
+
+        let sv_type =
+            std::str::from_utf8(record.info(b"SVTYPE").string()?.unwrap()[0])?.to_string();
+        let length = record.info(b"SVLEN").integer()?.unwrap()[0];
+        let min_snvs = if length >= 100_000 {
+            50
+        } else {
+            5 * (length / 10_000)
+        };
+        let mut vls = vec![i32::missing(); sample_count];
+        let mut vms = vec![i32::missing(); sample_count];
+        let mut vrs = vec![i32::missing(); sample_count];
+        let mut rohs = vec![i32::missing(); sample_count];
+        let mut any_snv = false;
+        let mut any_roh = false;
+        for (sample_idx, evidence) in sample_idxs.iter().zip(baf_evidence) {
+            if let Some(evidence) = evidence {
+                if let Some(elem) = evidence.get(idx).unwrap() {
+                    vls[*sample_idx] = elem.snvs_left;
+                    vms[*sample_idx] = elem.snvs_within;
+                    vrs[*sample_idx] = elem.snvs_right;
+                    any_snv = true;
+                    if (elem.snvs_right < min_snvs || elem.snvs_left < min_snvs)
+                        && elem.snvs_within < min_snvs
+                    {
+                        rohs[*sample_idx] = 1;
+                        any_roh = true;
+                    }
+                }
+            }
+        }
+        if any_snv && sv_type == "DEL" {
+            record.push_format_integer(b"VL", &vls)?;
+            record.push_format_integer(b"VM", &vms)?;
+            record.push_format_integer(b"VR", &vrs)?;
+        }
+        if any_roh {
+            record.push_format_integer(b"ROH", &rohs)?;
+        }
+
+        writer.write(&record)?;
+
+        idx += 1;
+    }
+
+    Ok(())
+}
+
+/// Write the IDs of the reads supporting each SV of `sample`.
+fn write_supporting_reads(
+    file: &mut fs::File,
+    sample: &str,
+    counts: &[ReadEvidenceCount],
+) -> Result<(), Error> {
+    for count in counts {
+        for (kind, reads) in &[("PE", &count.pe_reads), ("SR", &count.sr_reads)] {
+            for read_id in reads.iter() {
+                file.write_all(
+                    format!("{}\t{}\t{}\t{}\n", &count.sv_id, sample, kind, read_id).as_bytes(),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Main entry point after parsing command line and loading options.
+fn perform_annotation(options: &Options, config: &Config) -> Result<(), Error> {
+    info!(
+        "Starting to annotate variants for {} sample(s)...",
+        options.samples.len()
+    );
+
+    let reader = bcf::IndexedReader::from_path(&options.path_input)?;
+    let sample_idxs = options
+        .samples
+        .iter()
+        .map(|inputs| Ok(*reader.header().sample_to_id(inputs.sample.as_bytes())? as usize))
+        .collect::<Result<Vec<usize>, Error>>()?;
+
+    // TODO: try to load median DoCs from header first...
+    let mut doc_medians = Vec::new();
+    for inputs in &options.samples {
+        doc_medians.push(if let Some(path_doc_evidence) = &inputs.path_doc_evidence {
+            info!(
+                "Computing median depth of coverage (DoC) for {}...",
+                &inputs.sample
+            );
+            let doc_reader = bcf::Reader::from_path(path_doc_evidence)?;
+            let doc_idx = sample_index(doc_reader.header(), &inputs.sample)?;
+            let doc_median = load_doc_median(path_doc_evidence, doc_idx)?;
+            info!("... median DoC is {}", doc_median.on_autosomes);
+            Some(doc_median)
+        } else {
+            None
+        });
+    }
+
+    let header = build_vcf_header(reader.header())?;
+    let guessed = guess_bcf_format(&options.path_output);
+    let mut writer = bcf::Writer::from_path(
+        &options.path_output,
+        &header,
+        guessed.uncompressed,
+        guessed.format,
+    )?;
+
+    // Write out DoC summary.
+    if let Some(path_out_doc_summary) = &options.path_out_doc_summary {
+        let summarized: Vec<(&SampleInputs, &MedianReadDepthInfo)> = options
+            .samples
+            .iter()
+            .zip(&doc_medians)
+            .filter_map(|(inputs, doc_median)| doc_median.as_ref().map(|m| (inputs, m)))
+            .collect();
+        if !summarized.is_empty() {
+            let mut file = fs::File::create(&path_out_doc_summary)?;
+            file.write_all(
+                format!(
+                    "CHROM\t{}\nautosomes\t{}\n",
+                    summarized
+                        .iter()
+                        .map(|(inputs, _)| &inputs.sample)
+                        .join("\t"),
+                    summarized.iter().map(|(_, m)| m.on_autosomes).join("\t"),
+                )
+                .as_bytes(),
+            )?;
+            let contigs: Vec<&String> = summarized
+                .iter()
+                .flat_map(|(_, m)| m.by_chrom.keys())
+                .unique()
+                .sorted()
+                .collect();
+            for contig in contigs {
+                file.write_all(
+                    format!(
+                        "{}\t{}\n",
+                        contig,
+                        summarized
+                            .iter()
+                            .map(|(_, m)| match m.by_chrom.get(contig) {
+                                Some(count) => format!("{}", count),
+                                None => ".".to_string(),
+                            })
+                            .join("\t")
+                    )
+                    .as_bytes(),
+                )?;
+            }
+        }
+    }
+
+    let contigs = collect_contigs(&reader)?;
+
+    // The blocked regions from all BED files, e.g., the coverage spikes found by bam-scan.
+    let blocked_regions_beds: Vec<&String> = config
+        .blocked_regions_bed
+        .iter()
+        .chain(config.extra_blocked_regions_beds.iter())
+        .collect();
+    let blocked = if blocked_regions_beds.is_empty() {
+        None
+    } else {
+        let contigs: HashMap<String, String> =
+            contigs.iter().map(|s| (s.clone(), s.clone())).collect();
+        Some(beds_to_annot_map(&blocked_regions_beds, &contigs)?)
+    };
+
+    let regions = if let Some(regions) = &options.regions {
+        regions.clone()
+    } else {
+        contigs
+            .iter()
+            .map(|name| Interval::new(name.clone(), 0..10_000_000_000))
+            .collect()
+    };
+
+    let mut read_evidences = Vec::new();
+    for inputs in &options.samples {
+        read_evidences.push(
+            if let Some(path_pesr_evidence) = &inputs.path_pesr_evidence {
+                info!("Loading read-based evidence for {}...", &inputs.sample);
+                let mut skipped = 0;
+                let mut read_evidence: AnnotMap<String, read_evidence::Record> = AnnotMap::new();
+                for region in &regions {
+                    debug!("region = {:?}", &region);
+                    skipped += load_read_evidence(
+                        &mut read_evidence,
+                        region,
+                        path_pesr_evidence,
+                        &options,
+                        &blocked,
+                    )?;
+                }
+                debug!("evidence: {:?}", &read_evidence);
+                info!("Skipped {} blocked evidence records", skipped);
+                Some(read_evidence)
+            } else {
+                None
+            },
+        );
+    }
+
+    let mut file_out_baf_snvs = if let Some(path_out_baf_snvs) = &options.path_out_baf_snvs {
+        let mut file = fs::File::create(&path_out_baf_snvs)?;
+        file.write_all(b"SVID\tSAMPLE\tBAFS\n")?;
+        Some(file)
+    } else {
+        None
+    };
+
+    let mut file_out_supporting_reads =
+        if let Some(path_out_supporting_reads) = &options.path_out_supporting_reads {
+            let mut file = fs::File::create(&path_out_supporting_reads)?;
+            file.write_all(b"SVID\tSAMPLE\tKIND\tREAD_ID\n")?;
+            Some(file)
+        } else {
+            None
+        };
+
+    info!("Processing regions/contigs...");
+    for region in &regions {
+        info!("Processing contig {:?}", region);
+
+        let mut read_evidence = Vec::new();
+        let mut doc_evidence = Vec::new();
+        let mut baf_evidence = Vec::new();
+        for ((inputs, pesr), doc_median) in options
+            .samples
+            .iter()
+            .zip(&read_evidences)
+            .zip(&doc_medians)
+        {
+            read_evidence.push(
+                pesr.as_ref()
+                    .map(|re| annotate_pesr(&options, &config, &re, &region, &blocked))
+                    .transpose()?,
+            );
+            if let (Some(file), Some(Some(counts))) =
+                (&mut file_out_supporting_reads, read_evidence.last())
+            {
+                write_supporting_reads(file, &inputs.sample, counts)?;
+            }
+            doc_evidence.push(
+                doc_median
+                    .as_ref()
+                    .map(|m| annotate_doc(&options, &config, &region, inputs, m.on_autosomes))
+                    .transpose()?,
+            );
+            baf_evidence.push(
+                inputs
+                    .path_snv_vcf
+                    .as_ref()
+                    .map(|_| {
+                        annotate_snv(&options, &config, &region, inputs, &mut file_out_baf_snvs)
+                    })
+                    .transpose()?,
+            );
+        }
+
+        write_annotated(
+            &options,
+            &config,
+            &region,
+            &sample_idxs,
+            &read_evidence,
+            &doc_evidence,
+            &baf_evidence,
+            &mut writer,
+        )?;
+    }
+
+    info!("Done annotating variants...");
+    Ok(())
+}
+
+/// Build the `vcf-annotate` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("vcf-annotate")
+        .about("Create annotations for VCF file with SVs.")
+        .args(&[
+            Arg::from_usage("--path-pesr-evidence=[FILE] 'Path to PE/SR evidence file'"),
+            Arg::from_usage("--path-doc-evidence=[FILE] 'Path to DoC evidence file'"),
+            Arg::from_usage("--path-snv-vcf=[FILE] 'Path to BAF evidence file'"),
+            Arg::from_usage("--path-out-doc-summary=[FILE] 'Path to output DoC summary file'"),
+            Arg::from_usage("--path-out-baf-snvs=[FILE] 'Path to output SNV file for BAF'"),
+            Arg::from_usage(
+                "--path-out-supporting-reads=[FILE] 'Path to output file with the IDs of the \
+                 reads supporting each SV'",
+            ),
+            Arg::from_usage("-s, --sample=[SAMPLE] 'Set sample to analyze'")
+                .required_unless("sample-sheet"),
+            Arg::from_usage(
+                "--sample-sheet=[TSV] 'Sample sheet with sample name, PE/SR evidence, DoC \
+                 evidence, and SNV VCF for each sample to analyze'",
+            )
+            .conflicts_with_all(&[
+                "sample",
+                "path-pesr-evidence",
+                "path-doc-evidence",
+                "path-snv-vcf",
+            ]),
+            Arg::from_usage("<input> 'input VCF file to read from'"),
+            Arg::from_usage("<output> 'output VCF file'"),
+        ])
+}
+
+/// Run the `vcf-annotate` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output files must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    if let Some(path_out_supporting_reads) = &options.path_out_supporting_reads {
+        check_output_exists(path_out_supporting_reads, options.overwrite)?;
+    }
+    info!("Starting maelstrom vcf-annotate");
+    info!("options: {:?}", &options);
+
+    // Parse further settings from configuration file.
+    let config = load_config(&matches)?;
+    info!("options: {:?}", &config);
+
+    // Perform the record annotation.
+    perform_annotation(&options, &config)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interval, SampleInputs};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that returns the inputs for the given sample from the Delly example.
+    fn _delly_inputs(sample: &str) -> SampleInputs {
+        SampleInputs {
+            sample: sample.to_string(),
+            path_pesr_evidence: Some(String::from("./src/tests/data/ex-delly-pesr.tsv.gz")),
+            path_doc_evidence: Some(String::from("./src/tests/data/ex-delly-doc.vcf.gz")),
+            path_snv_vcf: Some(String::from("./src/tests/data/ex-delly-snvs.vcf.gz")),
+        }
+    }
+
+    /// Helper that runs `perform_clustering()` and compares the result.
+    #[allow(clippy::too_many_arguments)]
+    fn _perform_annotation_and_test(
+        tmp_dir: &TempDir,
+        samples: Vec<SampleInputs>,
+        path_input: &str,
+        path_expected: &str,
+        path_expected_doc: Option<String>,
+        path_expected_snv: Option<String>,
+        path_expected_reads: Option<String>,
+        regions: &Option<Vec<Interval>>,
+        config_text: &str,
+    ) -> Result<(), super::Error> {
+        let path_output = String::from(tmp_dir.path().join("out.vcf").to_str().unwrap());
+        let path_output_doc = String::from(tmp_dir.path().join("out.doc.tsv").to_str().unwrap());
+        let path_output_snv = String::from(tmp_dir.path().join("out.snv.tsv").to_str().unwrap());
+        let path_output_reads =
+            String::from(tmp_dir.path().join("out.reads.tsv").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            regions: regions.clone(),
+            path_config: None,
+            samples,
+            path_out_doc_summary: path_expected_doc.as_ref().map(|_| path_output_doc),
+            path_out_baf_snvs: path_expected_snv.as_ref().map(|_| path_output_snv),
+            path_out_supporting_reads: path_expected_reads.as_ref().map(|_| path_output_reads),
+            path_input: path_input.to_string(),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str(config_text).unwrap();
+
+        super::perform_annotation(&options, &config)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(path_output).unwrap(),
+        );
+
+        if let Some(path_out_doc_summary) = options.path_out_doc_summary {
+            assert_eq!(
+                fs::read_to_string(path_expected_doc.unwrap()).unwrap(),
+                fs::read_to_string(path_out_doc_summary).unwrap(),
+            );
+        }
+
+        if let Some(path_out_baf_snvs) = options.path_out_baf_snvs {
+            assert_eq!(
+                fs::read_to_string(path_expected_snv.unwrap()).unwrap(),
+                fs::read_to_string(path_out_baf_snvs).unwrap(),
+            );
+        }
+
+        if let Some(path_out_supporting_reads) = options.path_out_supporting_reads {
+            assert_eq!(
+                fs::read_to_string(path_expected_reads.unwrap()).unwrap(),
+                fs::read_to_string(path_out_supporting_reads).unwrap(),
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vcf_cluster_delly2_filter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_annotation_and_test(
+            &tmp_dir,
+            vec![_delly_inputs("sample-1")],
+            "./src/tests/data/ex-delly-svs.vcf.gz",
+            "./src/tests/data/ex-delly.expected.vcf",
+            Some(String::from("./src/tests/data/ex-delly.expected.doc.tsv")),
+            Some(String::from("./src/tests/data/ex-delly.expected.snvs.tsv")),
+            Some(String::from("./src/tests/data/ex-delly.expected.reads.tsv")),
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vcf_cluster_delly2_filter_with_blocked() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_annotation_and_test(
+            &tmp_dir,
+            vec![_delly_inputs("sample-1")],
+            "./src/tests/data/ex-delly-svs.vcf.gz",
+            "./src/tests/data/ex-delly.expected-blocked.vcf",
+            Some(String::from(
+                "./src/tests/data/ex-delly.expected-blocked.doc.tsv",
+            )),
+            Some(String::from(
+                "./src/tests/data/ex-delly.expected-blocked.snvs.tsv",
+            )),
+            None,
+            &None,
+            "blocked_regions_bed = \"./src/tests/data/ex-delly-blocked.bed\"",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vcf_cluster_delly2_filter_with_extra_blocked() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_annotation_and_test(
+            &tmp_dir,
+            vec![_delly_inputs("sample-1")],
+            "./src/tests/data/ex-delly-svs.vcf.gz",
+            "./src/tests/data/ex-delly.expected-blocked.vcf",
+            Some(String::from(
+                "./src/tests/data/ex-delly.expected-blocked.doc.tsv",
+            )),
+            Some(String::from(
+                "./src/tests/data/ex-delly.expected-blocked.snvs.tsv",
+            )),
+            None,
+            &None,
+            "extra_blocked_regions_beds = [\"./src/tests/data/ex-delly-blocked.bed\"]",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_sample_sheet() -> Result<(), super::Error> {
+        let samples = super::load_sample_sheet("./src/tests/data/ex-delly-cohort.samples.tsv")?;
+        assert_eq!(
+            samples,
+            vec![
+                _delly_inputs("sample-1"),
+                SampleInputs {
+                    path_pesr_evidence: Some(String::from(
+                        "./src/tests/data/ex-delly-cohort-pesr.tsv.gz"
+                    )),
+                    .._delly_inputs("sample-2")
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_vcf_annotate_delly2_cohort() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_annotation_and_test(
+            &tmp_dir,
+            super::load_sample_sheet("./src/tests/data/ex-delly-cohort.samples.tsv")?,
+            "./src/tests/data/ex-delly-cohort-svs.vcf.gz",
+            "./src/tests/data/ex-delly-cohort.expected.vcf",
+            Some(String::from(
+                "./src/tests/data/ex-delly-cohort.expected.doc.tsv",
+            )),
+            Some(String::from(
+                "./src/tests/data/ex-delly-cohort.expected.snvs.tsv",
+            )),
+            Some(String::from(
+                "./src/tests/data/ex-delly-cohort.expected.reads.tsv",
+            )),
+            &None,
+            "",
+        )?;
+        Ok(())
+    }
+
+    /// Helper that builds a split read record on contig "one".
+    fn _split_read(
+        read_id: i64,
+        range: (i64, i64),
+        clipped_sides: super::Sides,
+        breakpoint: Option<i64>,
+        partner: Option<(&str, i64, i64, i64)>,
+    ) -> super::read_evidence::Record {
+        super::read_evidence::Record::SplitRead {
+            read_id,
+            is_first: true,
+            contig: String::from("one"),
+            start: range.0,
+            end: range.1,
+            strand: Some(super::Strand::Forward),
+            clipped_sides,
+            breakpoint,
+            clipped_bases: None,
+            partner: partner.map(|(contig, start, end, breakpoint)| {
+                super::read_evidence::SplitPartner {
+                    contig: String::from(contig),
+                    start,
+                    end,
+                    strand: super::Strand::Forward,
+                    breakpoint: Some(breakpoint),
+                    clipped_bases: None,
+                }
+            }),
+            mapq: Some(60),
+            mate_mapq: None,
+        }
+    }
+
+    /// Helper that builds the two records of a read pair on contig "one".
+    fn _paired_read(
+        read_id: i64,
+        starts: (i64, i64),
+        reverse: (bool, bool),
+        mapqs: (Option<u8>, Option<u8>),
+        with_orientation: bool,
+    ) -> Vec<super::read_evidence::Record> {
+        let strand = |is_reverse: bool| {
+            if is_reverse {
+                super::Strand::Reverse
+            } else {
+                super::Strand::Forward
+            }
+        };
+        // The orientation class as computed by `bam-collect-pesr` from the leftmost alignment.
+        let orientation = if (starts.0, reverse.0) <= (starts.1, reverse.1) {
+            super::PairOrientation::from_strands(reverse.0, reverse.1)
+        } else {
+            super::PairOrientation::from_strands(reverse.1, reverse.0)
+        };
+        let orientation = Some(orientation).filter(|_| with_orientation);
+        vec![
+            super::read_evidence::Record::PairedRead {
+                read_id,
+                is_first1: true,
+                contig1: String::from("one"),
+                start1: starts.0,
+                end1: starts.0 + 100,
+                strand1: strand(reverse.0),
+                contig2: Some(String::from("one")),
+                start2: Some(starts.1),
+                strand2: Some(strand(reverse.1)),
+                tlen: Some(starts.1 + 100 - starts.0),
+                mapq: mapqs.0,
+                mate_mapq: mapqs.1,
+                orientation,
+            },
+            super::read_evidence::Record::PairedRead {
+                read_id,
+                is_first1: false,
+                contig1: String::from("one"),
+                start1: starts.1,
+                end1: starts.1 + 100,
+                strand1: strand(reverse.1),
+                contig2: Some(String::from("one")),
+                start2: Some(starts.0),
+                strand2: Some(strand(reverse.0)),
+                tlen: None,
+                mapq: mapqs.1,
+                mate_mapq: mapqs.0,
+                orientation,
+            },
+        ]
+    }
+
+    /// Helper that builds an `AnnotMap` from `records`.
+    fn _annot_map(
+        records: Vec<super::read_evidence::Record>,
+    ) -> super::AnnotMap<String, super::read_evidence::Record> {
+        let mut read_evidence = super::AnnotMap::new();
+        for record in records {
+            let (start, end) = (record.interval().start, record.interval().end);
+            let location = super::Contig::new(
+                String::from("one"),
+                start as isize,
+                (end - start) as usize,
+                super::NoStrand::Unknown,
+            );
+            read_evidence.insert_at(record, &location);
+        }
+        read_evidence
+    }
+
+    #[test]
+    fn test_supporting_evidence_split_partners() -> Result<(), super::Error> {
+        use super::{ReqStrand, SeqContigStranded, Sides};
+
+        let records = vec![
+            // Partner on the right side, clipped on the left.
+            _split_read(
+                1,
+                (150, 250),
+                Sides::Right,
+                Some(250),
+                Some(("one", 1050, 1100, 1050)),
+            ),
+            // Found on both sides without partner information.
+            _split_read(2, (160, 250), Sides::Right, None, None),
+            _split_read(2, (1050, 1120), Sides::Left, None, None),
+            // Only found on the right side, partner on the left side.
+            _split_read(
+                3,
+                (1050, 1150),
+                Sides::Left,
+                Some(1050),
+                Some(("one", 200, 250, 250)),
+            ),
+            // Partner elsewhere or clipped on the wrong side.
+            _split_read(
+                4,
+                (150, 250),
+                Sides::Right,
+                Some(250),
+                Some(("two", 1050, 1100, 1050)),
+            ),
+            _split_read(
+                5,
+                (150, 250),
+                Sides::Right,
+                Some(250),
+                Some(("one", 1050, 1100, 1100)),
+            ),
+        ];
+        let read_evidence = _annot_map(records);
+
+        let left = SeqContigStranded::new(String::from("one"), 100, 200, ReqStrand::Forward);
+        let right = SeqContigStranded::new(String::from("one"), 1000, 200, ReqStrand::Reverse);
+        let filter = super::EvidenceFilter::default();
+        let (prs, srs) = super::supporting_evidence(&left, &right, &read_evidence, &None, &filter);
+
+        assert!(prs.is_empty());
+        let mut srs = srs.into_iter().collect::<Vec<_>>();
+        srs.sort();
+        assert_eq!(
+            srs,
+            vec![
+                ((1, true), (Some(250), Some(1050))),
+                ((2, true), (None, None)),
+                ((3, true), (Some(250), Some(1050))),
+            ]
+        );
+        assert_eq!(
+            super::most_common(srs.iter().filter_map(|(_, (pos, _))| *pos)),
+            Some(250)
+        );
+        assert_eq!(super::most_common(vec![3, 1, 3, 1].into_iter()), Some(1));
+        assert_eq!(super::most_common(Vec::<i64>::new().into_iter()), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_supporting_evidence_filters() -> Result<(), super::Error> {
+        use super::{EvidenceFilter, PairOrientation, ReqStrand, SeqContigStranded};
+
+        let mut records = Vec::new();
+        // Deletion-type pair with high MAPQ.
+        records.extend(_paired_read(
+            1,
+            (150, 1050),
+            (false, true),
+            (Some(60), Some(60)),
+            true,
+        ));
+        // Deletion-type pair with a multi-mapping mate.
+        records.extend(_paired_read(
+            2,
+            (160, 1060),
+            (false, true),
+            (Some(60), Some(3)),
+            true,
+        ));
+        // Pair without MAPQ and orientation.
+        records.extend(_paired_read(
+            3,
+            (170, 1070),
+            (false, true),
+            (None, None),
+            false,
+        ));
+        // Duplication-type pair.
+        records.extend(_paired_read(
+            4,
+            (180, 1080),
+            (true, false),
+            (Some(60), Some(60)),
+            true,
+        ));
+        let read_evidence = _annot_map(records);
+
+        let supporting = |strands: (ReqStrand, ReqStrand), filter: &EvidenceFilter| {
+            let left = SeqContigStranded::new(String::from("one"), 100, 200, strands.0);
+            let right = SeqContigStranded::new(String::from("one"), 1000, 200, strands.1);
+            let (prs, _) = super::supporting_evidence(&left, &right, &read_evidence, &None, filter);
+            let mut prs = prs.into_iter().collect::<Vec<_>>();
+            prs.sort();
+            prs
+        };
+        let deletion = (ReqStrand::Forward, ReqStrand::Reverse);
+        let duplication = (ReqStrand::Reverse, ReqStrand::Forward);
+
+        assert_eq!(
+            supporting(deletion, &EvidenceFilter::default()),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            supporting(
+                deletion,
+                &EvidenceFilter {
+                    min_mapq: 20,
+                    orientation: None,
+                }
+            ),
+            vec![1, 3]
+        );
+        // The orientation classes agree with the strands matched by the queries.
+        assert_eq!(
+            supporting(
+                deletion,
+                &EvidenceFilter {
+                    min_mapq: 20,
+                    orientation: Some(PairOrientation::FR),
+                }
+            ),
+            vec![1, 3]
+        );
+        assert_eq!(supporting(duplication, &EvidenceFilter::default()), vec![4]);
+        assert_eq!(
+            supporting(
+                duplication,
+                &EvidenceFilter {
+                    min_mapq: 20,
+                    orientation: Some(PairOrientation::RF),
+                }
+            ),
+            vec![4]
+        );
+        assert_eq!(
+            supporting(
+                duplication,
+                &EvidenceFilter {
+                    min_mapq: 20,
+                    orientation: Some(PairOrientation::FR),
+                }
+            ),
+            Vec::<i64>::new()
+        );
+
+        Ok(())
+    }
+}
@@ -1,22 +1,18 @@
-use std::cmp::Ordering;
 /// vcf-cluster -- Cluster VCF files with structural variants.
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::fs;
 use std::iter::FromIterator;
-use std::path::Path;
 
 use bio::data_structures::interval_tree::IntervalTree;
 use bio_types::genome::{AbstractInterval, Interval};
-use clap::{App, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use disjoint_sets::UnionFind;
-use git_version::git_version;
 // use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, error, info, LevelFilter};
+use log::{debug, error, info};
 use rust_htslib::{bcf, bcf::Read};
 
 use lib_common::bcf::{build_vcf_header, collect_contigs, guess_bcf_format};
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
 use lib_common::error::Error;
-use lib_common::parse_region;
 use lib_common::sv::*;
 use lib_config::{ClusterSettings, Config};
 
@@ -43,23 +39,13 @@ impl Options {
     pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
         Ok(Options {
             verbosity: matches.occurrences_of("v"),
-            regions: matches
-                .value_of("regions")
-                .map(|s| {
-                    let x: Result<Vec<Interval>, Error> =
-                        s.split(',').map(|t| parse_region(&t)).collect();
-                    x
-                })
-                .transpose()?,
+            regions: parse_regions(matches)?,
             path_config: matches.value_of("config").map(|s| s.to_string()),
             paths_input: match matches.values_of("input") {
                 Some(xs) => xs.map(String::from).collect(),
                 None => return Err(Error::OptionMissing()),
             },
-            path_output: match matches.value_of("output") {
-                Some(x) => String::from(x),
-                None => return Err(Error::OptionMissing()),
-            },
+            path_output: required_value(matches, "output")?,
             overwrite: matches.occurrences_of("overwrite") > 0,
             setting: matches
                 .value_of("setting")
@@ -127,6 +113,9 @@ fn is_overlap_okay(
     if config.match_sv_type && lhs.sv_type != rhs.sv_type {
         panic!("Comparing SVs of different type!");
     }
+    if lhs.sv_type == "INS" || rhs.sv_type == "INS" {
+        return is_insertion_okay(lhs, rhs, config);
+    }
     let ovl = overlap(&lhs.interval(), &rhs.interval());
     let ovl_lhs = (range_len(&ovl) as f32) / (range_len(&lhs.interval()) as f32);
     let ovl_rhs = (range_len(&ovl) as f32) / (range_len(&rhs.interval()) as f32);
@@ -155,6 +144,20 @@ fn is_overlap_okay(
     if config.match_strands && lhs.strands != rhs.strands {
         return false;
     }
+    if !is_sample_overlap_okay(lhs, rhs, config) {
+        return false;
+    }
+    debug!("Overlap!");
+
+    true
+}
+
+/// Check whether the two records have sufficient sample overlap, if configured.
+fn is_sample_overlap_okay(
+    lhs: &StandardizedRecord,
+    rhs: &StandardizedRecord,
+    config: &ClusterSettings,
+) -> bool {
     if let Some(sample_overlap) = config.sample_overlap {
         let lhs_samples: HashSet<String> = HashSet::from_iter(lhs.samples.iter().cloned());
         let rhs_samples: HashSet<String> = HashSet::from_iter(rhs.samples.iter().cloned());
@@ -173,6 +176,46 @@ fn is_overlap_okay(
             return false;
         }
     }
+
+    true
+}
+
+/// Check whether the two insertions are to be merged.
+///
+/// Insertions have no reference span, so they are compared by the distance of their positions
+/// and the similarity of their lengths (if known for both) instead of by reciprocal overlap.
+fn is_insertion_okay(
+    lhs: &StandardizedRecord,
+    rhs: &StandardizedRecord,
+    config: &ClusterSettings,
+) -> bool {
+    if lhs.sv_type != "INS" || rhs.sv_type != "INS" {
+        return false;
+    }
+    debug!(
+        "ins distance: {} || {}",
+        (lhs.pos - rhs.pos).abs(),
+        config.ins_max_distance
+    );
+    if (lhs.pos - rhs.pos).abs() > config.ins_max_distance {
+        return false;
+    }
+    if let Some(ins_length_similarity) = config.ins_length_similarity {
+        if lhs.sv_len > 0 && rhs.sv_len > 0 {
+            let similarity = (std::cmp::min(lhs.sv_len, rhs.sv_len) as f32)
+                / (std::cmp::max(lhs.sv_len, rhs.sv_len) as f32);
+            debug!(
+                "ins length similarity: {} || {}",
+                similarity, ins_length_similarity
+            );
+            if similarity < ins_length_similarity {
+                return false;
+            }
+        }
+    }
+    if !is_sample_overlap_okay(lhs, rhs, config) {
+        return false;
+    }
     debug!("Overlap!");
 
     true
@@ -191,10 +234,12 @@ fn cluster_records_sv_type(
     for (i, record) in records.iter().enumerate() {
         if !cluster_settings.match_sv_type || record.sv_type == sv_type {
             debug!("inserting...");
-            tree.insert(
-                record.extended_interval(cluster_settings.max_bp_distance.unwrap_or(0)),
-                ids.len(),
-            );
+            let delta = if record.sv_type == "INS" {
+                cluster_settings.ins_max_distance
+            } else {
+                cluster_settings.max_bp_distance.unwrap_or(0)
+            };
+            tree.insert(record.extended_interval(delta), ids.len());
             ids.push(i);
         }
     }
@@ -297,7 +342,23 @@ fn cluster_records_sv_type(
             } else {
                 (end2[end2.len() / 2] + end2[end2.len() / 2 - 1]) / 2
             };
-            record.sv_len = record.end2 - record.pos;
+            record.sv_len = if sv_type == "INS" {
+                // Use median length of the insertions with known length.
+                let mut sv_len: Vec<_> = record_ids
+                    .iter()
+                    .map(|id| records[*id].sv_len)
+                    .filter(|sv_len| *sv_len > 0)
+                    .collect();
+                sv_len.sort();
+                debug!("sv_len = {:?}", &sv_len);
+                match sv_len.len() {
+                    0 => -1,
+                    n if n % 2 == 1 => sv_len[n / 2],
+                    n => (sv_len[n / 2] + sv_len[n / 2 - 1]) / 2,
+                }
+            } else {
+                record.end2 - record.pos
+            };
         }
 
         result.push(record);
@@ -321,7 +382,7 @@ fn cluster_records(
     };
 
     if cluster_settings.match_sv_type {
-        for sv_type in &["DEL", "DUP", "INV", "BND"] {
+        for sv_type in &["DEL", "DUP", "INV", "INS", "BND"] {
             cluster_records_sv_type(records, &cluster_settings, &sv_type, &mut result)?;
         }
     } else {
@@ -432,17 +493,11 @@ fn perform_clustering(options: &Options, config: &Config) -> Result<(), Error> {
     Ok(())
 }
 
-fn main() -> Result<(), Error> {
-    // Setup command line parser and parse options.
-    let matches = App::new("maelstrom-vcf-cluster")
-        .version(git_version!())
-        .author("Manuel Holtgrewe <manuel.holtgrewe@bihealth.de>")
-        .about("Extract and standardize records from tool VCF files")
+/// Build the `vcf-cluster` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("vcf-cluster")
+        .about("Cluster VCF files with structural variants")
         .args(&[
-            Arg::from_usage("-v... 'Increase verbosity'"),
-            Arg::from_usage("--overwrite 'Allow overwriting of output file'"),
-            Arg::from_usage("-c, --config=[FILE] 'Sets a custom config file'"),
-            Arg::from_usage("-r, --regions=[REGIONS] 'comma-separated list of regions'"),
             Arg::from_usage(
                 "-s, --setting=[SETTING] 'Use cluster settings name, one of \
                 {per_tool_pesr,per_tool_doc}, default per_tool_pesr'",
@@ -450,48 +505,19 @@ fn main() -> Result<(), Error> {
             Arg::from_usage("<input>... 'input file to read from'"),
             Arg::from_usage("<output> 'output file to write to'"),
         ])
-        .get_matches();
+}
+
+/// Run the `vcf-cluster` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
     let options = Options::from_arg_matches(&matches)?;
 
     // Output file must not exist yet.
-    if options.path_output != "-"
-        && options.path_output != "/dev/stdout"
-        && Path::new(&options.path_output).exists()
-        && !options.overwrite
-    {
-        return Err(Error::OutputFileExists());
-    }
-
-    // Setup logging verbosity.
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{} [{}] {}",
-                chrono::Local::now().format("[%Y-%m-%d %H:%M:%S]"),
-                record.level(),
-                message
-            ))
-        })
-        .level(match matches.occurrences_of("verbose").cmp(&1) {
-            Ordering::Less => LevelFilter::Info,
-            Ordering::Equal => LevelFilter::Debug,
-            Ordering::Greater => LevelFilter::Trace,
-        })
-        .chain(std::io::stderr())
-        .apply()
-        .unwrap();
-    info!("Starting maelstrom-vcf-cluster");
+    check_output_exists(&options.path_output, options.overwrite)?;
+    info!("Starting maelstrom vcf-cluster");
     info!("options: {:?}", &options);
 
     // Parse further settings from configuration file.
-    let config: Config = match &options.path_config {
-        None => toml::from_str("").unwrap(),
-        Some(path_config) => {
-            debug!("Loading config file: {}", &path_config);
-            let contents = fs::read_to_string(&path_config)?;
-            toml::from_str(&contents).unwrap()
-        }
-    };
+    let config = load_config(&matches)?;
     info!("options: {:?}", &config);
 
     // Perform the record extraction.
@@ -573,4 +599,84 @@ mod tests {
         )?;
         Ok(())
     }
+
+    /// Build insertion record for testing the clustering rules.
+    fn _insertion(pos: i64, sv_len: i64) -> super::StandardizedRecord {
+        super::StandardizedRecord {
+            chrom: "1".to_owned(),
+            pos,
+            reference: "N".to_owned(),
+            alt: "<INS>".to_owned(),
+            chrom2: "1".to_owned(),
+            end2: pos + 1,
+            sv_type: "INS".to_owned(),
+            strands: "+-".to_owned(),
+            sv_len,
+            algorithms: vec!["manta".to_owned()],
+            samples: vec!["sample-1".to_owned()],
+            gts: vec!["0/1".to_owned()],
+            called_by: vec![vec!["manta".to_owned()]],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_insertion_okay() {
+        let config: super::Config = toml::from_str("").unwrap();
+        let settings = &config.vcf_cluster_presets_per_tool_pesr;
+
+        assert!(super::is_insertion_okay(
+            &_insertion(1_000, 300),
+            &_insertion(1_050, 280),
+            settings
+        ));
+        // Too far apart.
+        assert!(!super::is_insertion_okay(
+            &_insertion(1_000, 300),
+            &_insertion(1_200, 300),
+            settings
+        ));
+        // Lengths too different.
+        assert!(!super::is_insertion_okay(
+            &_insertion(1_000, 300),
+            &_insertion(1_000, 6_000),
+            settings
+        ));
+        // Unknown length matches any length.
+        assert!(super::is_insertion_okay(
+            &_insertion(1_000, -1),
+            &_insertion(1_000, 6_000),
+            settings
+        ));
+    }
+
+    #[test]
+    fn test_cluster_insertions() -> Result<(), super::Error> {
+        let options = super::Options {
+            setting: "per_tool_pesr".to_string(),
+            verbosity: 1,
+            regions: None,
+            path_config: None,
+            paths_input: vec![],
+            path_output: "-".to_string(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str("").unwrap();
+        let records = vec![
+            _insertion(1_000, 300),
+            _insertion(1_020, 310),
+            _insertion(1_040, -1),
+            _insertion(5_000, 300),
+        ];
+
+        let result = super::cluster_records(&options, &config, &records)?;
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].pos, 1_020);
+        assert_eq!(result[0].sv_len, 305);
+        assert_eq!(result[0].alt, "<INS>");
+        assert_eq!(result[1].pos, 5_000);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,375 @@
+/// vcf-standardize -- Extract and standardize tool output VCF to standardized VCF.
+use bio_types::genome::{AbstractInterval, Interval};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{info, warn};
+use rust_htslib::{bcf, bcf::Read};
+
+use lib_common::bcf::{build_vcf_header, collect_contigs, guess_bcf_format};
+use lib_common::cli::{check_output_exists, load_config, parse_regions, required_value};
+use lib_common::error::Error;
+use lib_common::standardize::{lookup, Standardizer};
+use lib_config::Config;
+
+/// Command line options
+#[derive(Debug)]
+struct Options {
+    /// Verbosity level
+    verbosity: u64,
+    /// The SV calling tool used for the input file.
+    tool: &'static dyn Standardizer,
+    /// List of regions to call.
+    regions: Option<Vec<Interval>>,
+    /// Path to configuration file to use,
+    path_config: Option<String>,
+    /// Path to input file.
+    path_input: String,
+    /// Path to output file.
+    path_output: String,
+    /// Overwrite output file.
+    overwrite: bool,
+}
+
+impl Options {
+    pub fn from_arg_matches<'a>(matches: &ArgMatches<'a>) -> Result<Self, Error> {
+        Ok(Options {
+            verbosity: matches.occurrences_of("v"),
+            tool: lookup(matches.value_of("tool").ok_or(Error::OptionMissing())?)
+                .ok_or(Error::UnknownTool())?,
+            regions: parse_regions(matches)?,
+            path_config: matches.value_of("config").map(|s| s.to_string()),
+            path_input: required_value(matches, "input")?,
+            path_output: required_value(matches, "output")?,
+            overwrite: matches.occurrences_of("overwrite") > 0,
+        })
+    }
+}
+
+/// Summarize a single BCF record.
+fn summarize_record(
+    src: &mut bcf::Record,
+    src_header: &bcf::header::HeaderView,
+    dst: &mut bcf::Record,
+    dst_header: &bcf::header::HeaderView,
+    config: &Config,
+    tool: &dyn Standardizer,
+) -> Result<bool, Error> {
+    if config.stdvcf_apply_filters
+        && src
+            .filters()
+            .map(|id| String::from_utf8(src.header().id_to_name(id)).unwrap())
+            .any(|f| f != "PASS")
+    {
+        return Ok(false);
+    }
+
+    dst.set_rid(Some(
+        dst_header.name2rid(src_header.rid2name(src.rid().unwrap())?)?,
+    ));
+    dst.set_pos(src.pos());
+
+    tool.summarize_record(src, src_header, dst, dst_header)
+}
+
+/// Main entry point after parsing command line and loading options.
+fn perform_extraction(options: &Options, config: &Config) -> Result<(), Error> {
+    info!("Starting to extract BCF file...");
+    let mut reader = bcf::IndexedReader::from_path(&options.path_input)?;
+    let header = build_vcf_header(reader.header())?;
+    let guessed = guess_bcf_format(&options.path_output);
+    let mut writer = bcf::Writer::from_path(
+        &options.path_output,
+        &header,
+        guessed.uncompressed,
+        guessed.format,
+    )?;
+
+    let spinner_style = ProgressStyle::default_spinner()
+        .tick_chars(".oO@* ")
+        .template("{prefix:.bold.dim} {spinner} {msg} {elapsed}");
+    let spinner = if options.verbosity == 0 {
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_style(spinner_style);
+        spinner.enable_steady_tick(100);
+        Some(spinner)
+    } else {
+        None
+    };
+
+    let regions = if let Some(regions) = &options.regions {
+        regions.clone()
+    } else {
+        collect_contigs(&reader)?
+            .iter()
+            .map(|name| Interval::new(name.clone(), 0..10_000_000_000))
+            .collect()
+    };
+
+    let mut counter: usize = 0;
+    let mut buffer_read = reader.empty_record();
+    for region in &regions {
+        let rid = reader.header().name2rid(region.contig().as_bytes())?;
+        if reader
+            .fetch(rid, region.range().start, region.range().end)
+            .is_err()
+        {
+            warn!("No data for {:?}", &region);
+        }
+
+        while reader.read(&mut buffer_read)? {
+            let mut buffer_write = writer.empty_record();
+            if summarize_record(
+                &mut buffer_read,
+                reader.header(),
+                &mut buffer_write,
+                writer.header(),
+                &config,
+                options.tool,
+            )? {
+                writer.write(&buffer_write)?;
+            }
+
+            counter += 1;
+            if counter % 1_000 == 0 {
+                if let Some(spinner) = &spinner {
+                    spinner.set_message(&format!(
+                        "currently at {}:{}",
+                        &std::str::from_utf8(
+                            reader.header().rid2name(buffer_read.rid().unwrap())?
+                        )?,
+                        buffer_read.pos() + 1
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(spinner) = &spinner {
+        spinner.finish();
+    }
+
+    info!("Done extracting BCF file...");
+    Ok(())
+}
+
+/// Build the `vcf-standardize` sub command.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("vcf-standardize")
+        .about("Extract and standardize records from tool VCF files")
+        .args(&[
+            Arg::from_usage("-t, --tool=<TOOL> 'Name of SV calling tool, e.g., delly or manta'"),
+            Arg::from_usage("<input> 'input file to read from'"),
+            Arg::from_usage("<output> 'output file to write to'"),
+        ])
+}
+
+/// Run the `vcf-standardize` sub command.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let options = Options::from_arg_matches(&matches)?;
+
+    // Output file must not exist yet.
+    check_output_exists(&options.path_output, options.overwrite)?;
+    info!("Starting maelstrom vcf-standardize");
+    info!("options: {:?}", &options);
+
+    // Parse further settings from configuration file.
+    let config = load_config(&matches)?;
+    info!("options: {:?}", &config);
+
+    // Perform the record extraction.
+    perform_extraction(&options, &config)?;
+
+    info!("All done. Have a nice day!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lookup, Interval};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Helper that runs `perform_extraction()` and compares the result.
+    fn _perform_extraction_and_test(
+        tmp_dir: &TempDir,
+        path_input: &str,
+        path_expected: &str,
+        config_str: &str,
+        regions: &Option<Vec<Interval>>,
+        tool: &str,
+    ) -> Result<(), super::Error> {
+        let path_output = String::from(tmp_dir.path().join("out.vcf").to_str().unwrap());
+        let options = super::Options {
+            verbosity: 1, // disable progress bar
+            tool: lookup(tool).unwrap(),
+            regions: regions.clone(),
+            path_config: None,
+            path_input: String::from(path_input),
+            path_output: path_output.clone(),
+            overwrite: false,
+        };
+        let config: super::Config = toml::from_str(config_str).unwrap();
+
+        super::perform_extraction(&options, &config)?;
+
+        assert_eq!(
+            fs::read_to_string(path_expected).unwrap(),
+            fs::read_to_string(&path_output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_delly2_filter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-delly-filter.vcf.gz",
+            "./src/tests/data/ex-delly-filter.expected.vcf",
+            "stdvcf_apply_filters = true",
+            &None,
+            "delly",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_delly2_nofilter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-delly-nofilter.vcf.gz",
+            "./src/tests/data/ex-delly-nofilter.expected.vcf",
+            "stdvcf_apply_filters = false",
+            &None,
+            "delly",
+        )?;
+        Ok(())
+    }
+    #[test]
+    fn test_stdvcf_manta_filter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-manta-filter.vcf.gz",
+            "./src/tests/data/ex-manta-filter.expected.vcf",
+            "stdvcf_apply_filters = true",
+            &None,
+            "manta",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_manta_nofilter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-manta-nofilter.vcf.gz",
+            "./src/tests/data/ex-manta-nofilter.expected.vcf",
+            "stdvcf_apply_filters = false",
+            &None,
+            "manta",
+        )?;
+        Ok(())
+    }
+    #[test]
+    fn test_stdvcf_cnmops_filter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-cnmops-filter.vcf.gz",
+            "./src/tests/data/ex-cnmops-filter.expected.vcf",
+            "stdvcf_apply_filters = true",
+            &None,
+            "cnmops",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_cnmops_nofilter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-cnmops-nofilter.vcf.gz",
+            "./src/tests/data/ex-cnmops-nofilter.expected.vcf",
+            "stdvcf_apply_filters = false",
+            &None,
+            "cnmops",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_gridss_filter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-gridss-filter.vcf.gz",
+            "./src/tests/data/ex-gridss-filter.expected.vcf",
+            "stdvcf_apply_filters = true",
+            &None,
+            "gridss",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_lumpy_nofilter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-lumpy-nofilter.vcf.gz",
+            "./src/tests/data/ex-lumpy-nofilter.expected.vcf",
+            "stdvcf_apply_filters = false",
+            &None,
+            "lumpy",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_sniffles_filter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-sniffles-filter.vcf.gz",
+            "./src/tests/data/ex-sniffles-filter.expected.vcf",
+            "stdvcf_apply_filters = true",
+            &None,
+            "sniffles",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_cnvnator_nofilter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-cnvnator-nofilter.vcf.gz",
+            "./src/tests/data/ex-cnvnator-nofilter.expected.vcf",
+            "stdvcf_apply_filters = false",
+            &None,
+            "cnvnator",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdvcf_gcnv_nofilter() -> Result<(), super::Error> {
+        let tmp_dir = TempDir::new("tests")?;
+        _perform_extraction_and_test(
+            &tmp_dir,
+            "./src/tests/data/ex-gcnv-nofilter.vcf.gz",
+            "./src/tests/data/ex-gcnv-nofilter.expected.vcf",
+            "stdvcf_apply_filters = false",
+            &None,
+            "gcnv",
+        )?;
+        Ok(())
+    }
+}